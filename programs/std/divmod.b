@@ -0,0 +1,9 @@
+divide n by d into remainder and quotient
+
+Cells from the pointer: n d 0 0 0 0 0
+Afterwards:             0 d n%d n/d 0 0 0
+Any d from 1 up works; the pointer ends where it started
+
+>[>>>+>+<<<<-]>>>>[<<<<+>>>>-]<<<<<
+[->>+>>->>+<<[>+<-]>[<+>->[-]<]>[<<<+<[-]<[>>>+>+<<<<-]>>>>[<<<<+>>>>-]>-]<<<<<<]
+>>>>[-]<<<<
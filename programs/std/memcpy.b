@@ -0,0 +1,7 @@
+copy the current cell into the next one
+
+Cells from the pointer: x 0 0
+Afterwards:             x x 0
+The pointer ends where it started
+
+[>+>+<<-]>>[<<+>>-]<<
@@ -0,0 +1,7 @@
+multiply x by y and keep y
+
+Cells from the pointer: x y 0 0
+Afterwards:             x*y y 0 0
+The pointer ends where it started
+
+[>>+<<-]>>[<[<+>>>+<<-]>>[<<+>>-]<-]<<
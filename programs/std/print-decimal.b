@@ -0,0 +1,11 @@
+print the current cell as a decimal number
+
+Cells from the pointer: x then nine zero cells
+Afterwards the cells are unchanged and x is printed without leading zeros
+The pointer ends where it started
+
+>>++++++++++<<[->+>-[>+>>]>[+[-<+>]>+>>]<<<<<<]>>[-]
+>>>>++++++++++<<[->+>-[>+>>]>[+[-<+>]>+>>]<<<<<<]>>[-]
+<[>>>[>++++++[<++++++++>-]<.[-]]<>++++++[<++++++++>-]<.[-]<<[-]]
+<<>++++++[<++++++++>-]<.[-]
+<<[<+>-]<
@@ -0,0 +1,157 @@
+//! Reading programs out of zip and tar archives, for `bf run --member`.
+
+use std::fmt;
+
+/// What sort of archive a file is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Zip,
+    Tar,
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Kind::Zip => f.write_str("zip"),
+            Kind::Tar => f.write_str("tar"),
+        }
+    }
+}
+
+/// The kind of archive `bytes` hold, judged by their magic numbers.
+pub fn kind(bytes: &[u8]) -> Option<Kind> {
+    if bytes.starts_with(b"PK\x03\x04") || bytes.starts_with(b"PK\x05\x06") {
+        Some(Kind::Zip)
+    } else if bytes.get(257..262) == Some(b"ustar") {
+        Some(Kind::Tar)
+    } else {
+        None
+    }
+}
+
+/// The contents of the file named `member` in an archive of `kind`.
+pub fn read_member(kind: Kind, bytes: &[u8], member: &str) -> Result<Vec<u8>, String> {
+    let found = match kind {
+        Kind::Zip => zip_member::read(bytes, member)?,
+        Kind::Tar => tar_member::read(bytes, member)?,
+    };
+    found.ok_or_else(|| match members(kind, bytes) {
+        Ok(names) if names.is_empty() => {
+            format!("no {} in the archive, which holds no programs", member)
+        }
+        Ok(names) => format!(
+            "no {} in the archive; its programs are {}",
+            member,
+            names.join(", ")
+        ),
+        Err(e) => e,
+    })
+}
+
+/// The names in an archive of `kind` that look like programs.
+pub fn members(kind: Kind, bytes: &[u8]) -> Result<Vec<String>, String> {
+    let names = match kind {
+        Kind::Zip => zip_member::names(bytes)?,
+        Kind::Tar => tar_member::names(bytes)?,
+    };
+    Ok(names
+        .into_iter()
+        .filter(|name| name.ends_with(".b") || name.ends_with(".bf"))
+        .collect())
+}
+
+/// `name` without the `./` tar tools like to add.
+#[cfg(feature = "archives")]
+fn normalize(name: &str) -> &str {
+    name.trim_start_matches("./")
+}
+
+#[cfg(feature = "archives")]
+mod zip_member {
+    use std::io::{Cursor, Read};
+
+    fn open(bytes: &[u8]) -> Result<zip::ZipArchive<Cursor<&[u8]>>, String> {
+        zip::ZipArchive::new(Cursor::new(bytes)).map_err(|e| format!("bad zip archive: {}", e))
+    }
+
+    pub fn read(bytes: &[u8], member: &str) -> Result<Option<Vec<u8>>, String> {
+        let mut archive = open(bytes)?;
+        let mut file = match archive.by_name(super::normalize(member)) {
+            Ok(file) => file,
+            Err(zip::result::ZipError::FileNotFound) => return Ok(None),
+            Err(e) => return Err(format!("bad zip archive: {}", e)),
+        };
+        let mut contents = Vec::new();
+        file.read_to_end(&mut contents)
+            .map_err(|e| format!("Failed to read {}: {}", member, e))?;
+        Ok(Some(contents))
+    }
+
+    pub fn names(bytes: &[u8]) -> Result<Vec<String>, String> {
+        open(bytes)?
+            .file_names()
+            .map(|name| {
+                name.map(|name| name.into_owned())
+                    .map_err(|e| format!("bad zip archive: {}", e))
+            })
+            .collect()
+    }
+}
+
+#[cfg(feature = "archives")]
+mod tar_member {
+    use std::io::Read;
+
+    fn entries<T>(
+        bytes: &[u8],
+        mut visit: impl FnMut(&str, &mut dyn Read) -> Option<T>,
+    ) -> Result<Option<T>, String> {
+        let bad = |e: std::io::Error| format!("bad tar archive: {}", e);
+        let mut archive = tar::Archive::new(bytes);
+        for entry in archive.entries().map_err(bad)? {
+            let mut entry = entry.map_err(bad)?;
+            let name = entry.path().map_err(bad)?.to_string_lossy().into_owned();
+            if let Some(found) = visit(super::normalize(&name), &mut entry) {
+                return Ok(Some(found));
+            }
+        }
+        Ok(None)
+    }
+
+    pub fn read(bytes: &[u8], member: &str) -> Result<Option<Vec<u8>>, String> {
+        let member = super::normalize(member);
+        entries(bytes, |name, entry| {
+            (name == member).then(|| {
+                let mut contents = Vec::new();
+                entry.read_to_end(&mut contents).map(|_| contents)
+            })
+        })?
+        .transpose()
+        .map_err(|e| format!("Failed to read {}: {}", member, e))
+    }
+
+    pub fn names(bytes: &[u8]) -> Result<Vec<String>, String> {
+        let mut names = Vec::new();
+        entries::<()>(bytes, |name, _| {
+            names.push(name.to_owned());
+            None
+        })?;
+        Ok(names)
+    }
+}
+
+#[cfg(not(feature = "archives"))]
+mod unsupported {
+    pub fn read(_: &[u8], _: &str) -> Result<Option<Vec<u8>>, String> {
+        Err("reading archives requires the archives feature".into())
+    }
+
+    pub fn names(bytes: &[u8]) -> Result<Vec<String>, String> {
+        read(bytes, "").map(|_| Vec::new())
+    }
+}
+
+#[cfg(not(feature = "archives"))]
+use unsupported as zip_member;
+#[cfg(not(feature = "archives"))]
+use unsupported as tar_member;
@@ -0,0 +1,167 @@
+use serde::{Deserialize, Serialize};
+
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use crate::load;
+use bf::ir::OptLevel;
+use bf::limits::Limits;
+use bf::samples::{quantile, without_outliers};
+use bf::Interpreter;
+
+pub const BENCH_VERSION: u32 = 1;
+
+/// Timings from one `bf bench` run, saved to compare later runs against.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BenchResults {
+    pub version: u32,
+    pub benchmarks: Vec<BenchResult>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BenchResult {
+    pub name: String,
+    /// Median of the timed runs, used for comparisons.
+    pub seconds: f64,
+    #[serde(default)]
+    pub min: f64,
+    #[serde(default)]
+    pub stddev: f64,
+    /// Timed runs kept after outlier rejection.
+    #[serde(default)]
+    pub runs: usize,
+    #[serde(default)]
+    pub outliers: usize,
+}
+
+impl BenchResults {
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let file = BufReader::new(File::open(path)?);
+        let results: Self = serde_json::from_reader(file)?;
+        if results.version != BENCH_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unsupported benchmark version {}", results.version),
+            ));
+        }
+        Ok(results)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let file = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(file, self)?;
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&BenchResult> {
+        self.benchmarks.iter().find(|b| b.name == name)
+    }
+}
+
+/// Runs each program `warmup` times untimed, then `runs` times timed, with
+/// empty input and discarded output.
+pub fn run(
+    files: &[PathBuf],
+    limits: &Limits,
+    level: OptLevel,
+    runs: usize,
+    warmup: usize,
+) -> BenchResults {
+    let benchmarks = files
+        .iter()
+        .map(|file| {
+            let program = load(file, limits);
+            let mut samples: Vec<f64> = (0..warmup + runs)
+                .map(|_| {
+                    let mut interp = Interpreter::new(program.clone());
+                    interp.opt_level = level;
+                    time(file, interp)
+                })
+                .skip(warmup)
+                .collect();
+            summarize(file.display().to_string(), &mut samples)
+        })
+        .collect();
+
+    BenchResults {
+        version: BENCH_VERSION,
+        benchmarks,
+    }
+}
+
+fn time(file: &Path, mut interp: Interpreter) -> f64 {
+    let started = Instant::now();
+    if let Err(e) = interp.execute_all(&mut io::empty(), &mut io::sink()) {
+        eprintln!("{}: {}", file.display(), e);
+    }
+    started.elapsed().as_secs_f64()
+}
+
+/// Drops the outlying samples, then summarizes the rest.
+fn summarize(name: String, samples: &mut [f64]) -> BenchResult {
+    let kept = without_outliers(samples);
+
+    let mean = kept.iter().sum::<f64>() / kept.len() as f64;
+    let variance = kept.iter().map(|s| (s - mean).powi(2)).sum::<f64>() / kept.len() as f64;
+    BenchResult {
+        name,
+        seconds: quantile(&kept, 0.5),
+        min: kept[0],
+        stddev: variance.sqrt(),
+        runs: kept.len(),
+        outliers: samples.len() - kept.len(),
+    }
+}
+
+/// A table of `current` timings against `baseline`, and how many
+/// benchmarks slowed down by more than `threshold` percent.
+pub fn compare(baseline: &BenchResults, current: &BenchResults, threshold: f64) -> (String, usize) {
+    let mut out = format!(
+        "{:<32} {:>12} {:>12} {:>9}\n",
+        "benchmark", "baseline", "current", "change"
+    );
+    let mut regressions = 0;
+    for bench in &current.benchmarks {
+        let base = match baseline.get(&bench.name) {
+            Some(base) => base,
+            None => {
+                out.push_str(&format!(
+                    "{:<32} {:>12} {:>12.6} {:>9}\n",
+                    bench.name, "-", bench.seconds, "new"
+                ));
+                continue;
+            }
+        };
+
+        let change = (bench.seconds / base.seconds - 1.0) * 100.0;
+        let regressed = change > threshold;
+        if regressed {
+            regressions += 1;
+        }
+        out.push_str(&format!(
+            "{:<32} {:>12.6} {:>12.6} {:>+8.1}%{}\n",
+            bench.name,
+            base.seconds,
+            bench.seconds,
+            change,
+            if regressed { "  REGRESSED" } else { "" }
+        ));
+    }
+    (out, regressions)
+}
+
+pub fn table(results: &BenchResults) -> String {
+    let mut out = format!(
+        "{:<32} {:>12} {:>12} {:>12} {:>6} {:>8}\n",
+        "benchmark", "median", "min", "stddev", "runs", "outliers"
+    );
+    for b in &results.benchmarks {
+        out.push_str(&format!(
+            "{:<32} {:>12.6} {:>12.6} {:>12.6} {:>6} {:>8}\n",
+            b.name, b.seconds, b.min, b.stddev, b.runs, b.outliers
+        ));
+    }
+    out
+}
@@ -0,0 +1,206 @@
+//! `bf canon`: a canonical layout for brainfuck source that keeps diffs
+//! and merges local, and a git merge driver that uses it.
+
+use std::fmt::{self, Write as _};
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use bf::limits::Limits;
+use bf::program::{OpIndex, Program, SourcePos};
+use bf::{parse, OpCode};
+
+/// Longest line `canonical` writes, indentation included, unless a loop is
+/// nested too deep for it.
+const WIDTH: usize = 72;
+
+/// One step of a program after folding runs and cancelling moves and
+/// arithmetic that undo each other.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Token {
+    /// Net change to the cell, in wrapping arithmetic.
+    Add(u8),
+    Move(isize),
+    Output,
+    Input,
+    /// `[-]` or `[+]`.
+    Clear,
+    Open,
+    Close,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Token::Add(n) if n < 128 => write!(f, "{}", "+".repeat(n as usize)),
+            Token::Add(n) => write!(f, "{}", "-".repeat(256 - n as usize)),
+            Token::Move(n) if n > 0 => write!(f, "{}", ">".repeat(n as usize)),
+            Token::Move(n) => write!(f, "{}", "<".repeat(n.unsigned_abs())),
+            Token::Output => f.write_str("."),
+            Token::Input => f.write_str(","),
+            Token::Clear => f.write_str("[-]"),
+            Token::Open => f.write_str("["),
+            Token::Close => f.write_str("]"),
+        }
+    }
+}
+
+/// `program` as canonical tokens, each with where it starts.
+pub fn tokens(program: &Program) -> Vec<(Token, SourcePos)> {
+    let ops = program.ops();
+    let positions = program.positions();
+    let mut tokens: Vec<(Token, SourcePos)> = Vec::new();
+    let mut i = 0;
+    while i < ops.len() {
+        let pos = positions[i];
+        let token = match ops[i] {
+            op @ (OpCode::Increment | OpCode::Decrement) => {
+                let delta = if op == OpCode::Increment { 1 } else { 255 };
+                match tokens.last_mut() {
+                    Some((Token::Add(n), _)) => {
+                        *n = n.wrapping_add(delta);
+                        if *n == 0 {
+                            tokens.pop();
+                        }
+                        i += 1;
+                        continue;
+                    }
+                    _ => Token::Add(delta),
+                }
+            }
+            op @ (OpCode::MoveForward | OpCode::MoveBack) => {
+                let by = if op == OpCode::MoveForward { 1 } else { -1 };
+                match tokens.last_mut() {
+                    Some((Token::Move(n), _)) => {
+                        *n += by;
+                        if *n == 0 {
+                            tokens.pop();
+                        }
+                        i += 1;
+                        continue;
+                    }
+                    _ => Token::Move(by),
+                }
+            }
+            OpCode::Output => Token::Output,
+            OpCode::Input => Token::Input,
+            OpCode::JmpStart => {
+                let end = program.partner(OpIndex::new(i)).index();
+                if end == i + 2 && matches!(ops[i + 1], OpCode::Increment | OpCode::Decrement) {
+                    tokens.push((Token::Clear, pos));
+                    i = end + 1;
+                    continue;
+                }
+                Token::Open
+            }
+            OpCode::JmpEnd => Token::Close,
+        };
+        tokens.push((token, pos));
+        i += 1;
+    }
+    tokens
+}
+
+/// `program` with comments dropped, runs folded as by `tokens`, and one
+/// line per straight run of instructions. Loops without nested loops stay
+/// on the line when they fit; others open and close on lines of their own,
+/// with their body indented by two spaces.
+pub fn canonical(program: &Program) -> String {
+    let tokens: Vec<Token> = tokens(program).into_iter().map(|(t, _)| t).collect();
+    let mut out = String::new();
+    format_block(&tokens, 0, &mut out);
+    out
+}
+
+fn format_block(tokens: &[Token], depth: usize, out: &mut String) {
+    let indent = "  ".repeat(depth);
+    let width = WIDTH.saturating_sub(indent.len()).max(16);
+    let mut line = String::new();
+    let mut i = 0;
+    while i < tokens.len() {
+        if tokens[i] != Token::Open {
+            write!(line, "{}", tokens[i]).unwrap();
+            i += 1;
+            continue;
+        }
+        let end = close_of(tokens, i);
+        let body = &tokens[i + 1..end];
+        let inline: String = tokens[i..=end].iter().map(Token::to_string).collect();
+        if !body.contains(&Token::Open) && line.len() + inline.len() <= width {
+            line.push_str(&inline);
+        } else {
+            flush_line(&mut line, &indent, width, out);
+            writeln!(out, "{}[", indent).unwrap();
+            format_block(body, depth + 1, out);
+            writeln!(out, "{}]", indent).unwrap();
+        }
+        i = end + 1;
+    }
+    flush_line(&mut line, &indent, width, out);
+}
+
+/// Writes `line` wrapped at `width`, and empties it.
+fn flush_line(line: &mut String, indent: &str, width: usize, out: &mut String) {
+    // Every character is a single-byte instruction.
+    for chunk in line.as_bytes().chunks(width) {
+        writeln!(out, "{}{}", indent, std::str::from_utf8(chunk).unwrap()).unwrap();
+    }
+    line.clear();
+}
+
+fn close_of(tokens: &[Token], open: usize) -> usize {
+    let mut depth = 0;
+    for (i, token) in tokens.iter().enumerate().skip(open) {
+        match token {
+            Token::Open => depth += 1,
+            Token::Close => {
+                depth -= 1;
+                if depth == 0 {
+                    return i;
+                }
+            }
+            _ => {}
+        }
+    }
+    unreachable!("parser rejects unmatched '['")
+}
+
+fn canonical_file(path: &Path, limits: &Limits) -> Result<String, String> {
+    let source = fs::read(path).map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    let program =
+        parse(source.iter().copied(), limits).map_err(|e| format!("{}: {}", path.display(), e))?;
+    Ok(canonical(&program))
+}
+
+/// Rewrites `path` in canonical form. True if that changed it.
+pub fn rewrite(path: &Path, limits: &Limits) -> Result<bool, String> {
+    let canon = canonical_file(path, limits)?;
+    let changed = fs::read(path).map_or(true, |old| old != canon.as_bytes());
+    if changed {
+        fs::write(path, canon).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    }
+    Ok(changed)
+}
+
+/// Runs as a git merge driver: puts the common ancestor `base` and both
+/// sides in canonical form, merges them with `git merge-file` into `ours`,
+/// and canonicalizes a clean result again. Returns `git merge-file`'s
+/// status, which is the number of conflicts.
+pub fn merge(base: &Path, ours: &Path, theirs: &Path, limits: &Limits) -> Result<i32, String> {
+    for path in [base, ours, theirs] {
+        rewrite(path, limits)?;
+    }
+    let status = Command::new("git")
+        .arg("merge-file")
+        .args([ours, base, theirs])
+        .status()
+        .map_err(|e| format!("Failed to run git merge-file: {}", e))?;
+    match status.code() {
+        Some(0) => {
+            rewrite(ours, limits)?;
+            Ok(0)
+        }
+        Some(conflicts) if conflicts > 0 => Ok(conflicts),
+        _ => Err(format!("git merge-file failed: {}", status)),
+    }
+}
@@ -0,0 +1,148 @@
+//! Optional compression for the files runs leave behind, like traces and
+//! snapshots. Readers tell the formats apart by their magic numbers, so
+//! only writers need to be told which to use.
+
+use std::io::{self, prelude::*};
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+/// How to compress a file as it's written.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Compression {
+    #[default]
+    None,
+    /// Needs the gzip feature.
+    Gzip,
+    /// Needs the zstd feature.
+    Zstd,
+}
+
+impl Compression {
+    /// The compression a stream starting with `head` uses.
+    pub fn detect(head: &[u8]) -> Self {
+        if head.starts_with(GZIP_MAGIC) {
+            Compression::Gzip
+        } else if head.starts_with(ZSTD_MAGIC) {
+            Compression::Zstd
+        } else {
+            Compression::None
+        }
+    }
+
+    /// `self`, or an error if this build lacks the feature it needs.
+    pub fn supported(self) -> io::Result<Self> {
+        match self {
+            Compression::Gzip if !cfg!(feature = "gzip") => Err(unsupported(self)),
+            Compression::Zstd if !cfg!(feature = "zstd") => Err(unsupported(self)),
+            _ => Ok(self),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Compression::None => "none",
+            Compression::Gzip => "gzip",
+            Compression::Zstd => "zstd",
+        }
+    }
+}
+
+/// A writer compressing what it's given with a `Compression`. `finish` must
+/// be called to end the compressed stream.
+pub enum Encoder<W: Write> {
+    Plain(W),
+    #[cfg(feature = "gzip")]
+    Gzip(flate2::write::GzEncoder<W>),
+    #[cfg(feature = "zstd")]
+    Zstd(zstd::Encoder<'static, W>),
+}
+
+impl<W: Write> std::fmt::Debug for Encoder<W> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Encoder").field(&self.compression()).finish()
+    }
+}
+
+impl<W: Write> Encoder<W> {
+    /// Errors if this build lacks the feature `compression` needs.
+    pub fn new(inner: W, compression: Compression) -> io::Result<Self> {
+        match compression {
+            Compression::None => Ok(Encoder::Plain(inner)),
+            #[cfg(feature = "gzip")]
+            Compression::Gzip => Ok(Encoder::Gzip(flate2::write::GzEncoder::new(
+                inner,
+                flate2::Compression::default(),
+            ))),
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => Ok(Encoder::Zstd(zstd::Encoder::new(inner, 0)?)),
+            #[allow(unreachable_patterns)]
+            other => Err(unsupported(other)),
+        }
+    }
+
+    pub fn compression(&self) -> Compression {
+        match self {
+            Encoder::Plain(_) => Compression::None,
+            #[cfg(feature = "gzip")]
+            Encoder::Gzip(_) => Compression::Gzip,
+            #[cfg(feature = "zstd")]
+            Encoder::Zstd(_) => Compression::Zstd,
+        }
+    }
+
+    /// Ends any compressed stream, returning the writer to write after it.
+    pub fn finish(self) -> io::Result<W> {
+        match self {
+            Encoder::Plain(w) => Ok(w),
+            #[cfg(feature = "gzip")]
+            Encoder::Gzip(w) => w.finish(),
+            #[cfg(feature = "zstd")]
+            Encoder::Zstd(w) => w.finish(),
+        }
+    }
+}
+
+impl<W: Write> Write for Encoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Encoder::Plain(w) => w.write(buf),
+            #[cfg(feature = "gzip")]
+            Encoder::Gzip(w) => w.write(buf),
+            #[cfg(feature = "zstd")]
+            Encoder::Zstd(w) => w.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Encoder::Plain(w) => w.flush(),
+            #[cfg(feature = "gzip")]
+            Encoder::Gzip(w) => w.flush(),
+            #[cfg(feature = "zstd")]
+            Encoder::Zstd(w) => w.flush(),
+        }
+    }
+}
+
+/// `reader` decompressed with whatever `Compression` the bytes it starts
+/// with show, or as it is if they show none.
+pub fn decoder<R: BufRead + 'static>(mut reader: R) -> io::Result<Box<dyn Read>> {
+    match Compression::detect(reader.fill_buf()?) {
+        Compression::None => Ok(Box::new(reader)),
+        #[cfg(feature = "gzip")]
+        Compression::Gzip => Ok(Box::new(flate2::bufread::GzDecoder::new(reader))),
+        #[cfg(feature = "zstd")]
+        Compression::Zstd => Ok(Box::new(zstd::Decoder::with_buffer(reader)?)),
+        #[allow(unreachable_patterns)]
+        other => Err(unsupported(other)),
+    }
+}
+
+fn unsupported(compression: Compression) -> io::Error {
+    io::Error::other(format!(
+        "{} compression requires the {} feature",
+        compression.name(),
+        compression.name()
+    ))
+}
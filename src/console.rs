@@ -0,0 +1,128 @@
+//! Adapters for the Windows console, which doesn't pass bytes through the
+//! way a Unix terminal does.
+
+use std::io::{self, prelude::*, stdin, stdout, IsTerminal};
+use std::str;
+
+/// Ends input at a Ctrl+Z typed at the start of a line, the console's
+/// end-of-file convention.
+#[derive(Debug)]
+pub struct ConsoleInput<R> {
+    inner: R,
+    line_start: bool,
+    eof: bool,
+}
+
+impl<R> ConsoleInput<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            line_start: true,
+            eof: false,
+        }
+    }
+}
+
+impl<R: Read> Read for ConsoleInput<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.eof {
+            return Ok(0);
+        }
+        let n = self.inner.read(buf)?;
+        for (i, &byte) in buf[..n].iter().enumerate() {
+            match byte {
+                0x1a if self.line_start => {
+                    self.eof = true;
+                    return Ok(i);
+                }
+                b'\n' => self.line_start = true,
+                _ => self.line_start = false,
+            }
+        }
+        Ok(n)
+    }
+}
+
+/// Writes program output to a console that only accepts UTF-8. Valid UTF-8
+/// passes through, sequences split across writes are held until complete,
+/// and any other byte is shown as the Latin-1 character with that value.
+#[derive(Debug)]
+pub struct ConsoleOutput<W: Write> {
+    inner: W,
+    pending: Vec<u8>,
+}
+
+impl<W: Write> ConsoleOutput<W> {
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            pending: Vec::new(),
+        }
+    }
+
+    fn write_latin1(&mut self, bytes: &[u8]) -> io::Result<()> {
+        let text: String = bytes.iter().map(|&b| b as char).collect();
+        self.inner.write_all(text.as_bytes())
+    }
+}
+
+impl<W: Write> Write for ConsoleOutput<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.pending.extend_from_slice(buf);
+        loop {
+            match str::from_utf8(&self.pending) {
+                Ok(_) => {
+                    self.inner.write_all(&self.pending)?;
+                    self.pending.clear();
+                    break;
+                }
+                Err(e) => {
+                    let valid = e.valid_up_to();
+                    self.inner.write_all(&self.pending[..valid])?;
+                    match e.error_len() {
+                        Some(len) => {
+                            let invalid: Vec<u8> = self.pending[valid..valid + len].to_vec();
+                            self.write_latin1(&invalid)?;
+                            self.pending.drain(..valid + len);
+                        }
+                        // The rest may be the start of a character.
+                        None => {
+                            self.pending.drain(..valid);
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<W: Write> Drop for ConsoleOutput<W> {
+    fn drop(&mut self) {
+        let pending = std::mem::take(&mut self.pending);
+        let _ = self.write_latin1(&pending).and_then(|_| self.inner.flush());
+    }
+}
+
+/// Stdin, ending at Ctrl+Z when it's a Windows console.
+pub fn input() -> Box<dyn Read> {
+    if cfg!(windows) && stdin().is_terminal() {
+        Box::new(ConsoleInput::new(stdin().lock()))
+    } else {
+        Box::new(crate::stdin::input())
+    }
+}
+
+/// Stdout, made safe for arbitrary bytes when it's a Windows console.
+pub fn output() -> Box<dyn Write> {
+    if cfg!(windows) && stdout().is_terminal() {
+        Box::new(ConsoleOutput::new(stdout().lock()))
+    } else {
+        Box::new(stdout().lock())
+    }
+}
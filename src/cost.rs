@@ -0,0 +1,189 @@
+use serde::Serialize;
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use bf::program::{OpIndex, Program};
+use bf::{Interpreter, OpCode};
+
+/// Units charged for each kind of instruction. Deterministic, unlike wall
+/// time, so runs can be compared on any machine.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct CostModel {
+    pub moves: u64,
+    pub arithmetic: u64,
+    pub io: u64,
+    pub loops: u64,
+}
+
+impl Default for CostModel {
+    fn default() -> Self {
+        Self {
+            moves: 1,
+            arithmetic: 1,
+            io: 1,
+            loops: 1,
+        }
+    }
+}
+
+impl CostModel {
+    pub fn of(&self, op: OpCode) -> u64 {
+        match op {
+            OpCode::MoveForward | OpCode::MoveBack => self.moves,
+            OpCode::Increment | OpCode::Decrement => self.arithmetic,
+            OpCode::Output | OpCode::Input => self.io,
+            OpCode::JmpStart | OpCode::JmpEnd => self.loops,
+        }
+    }
+}
+
+impl fmt::Display for CostModel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "move={},arith={},io={},loop={}",
+            self.moves, self.arithmetic, self.io, self.loops
+        )
+    }
+}
+
+/// Parses a cost model such as `op=1,io=10`: `op` sets every kind, and
+/// `move`, `arith`, `io` and `loop` set one. Unnamed kinds cost 1.
+pub fn parse_model(s: &str) -> Result<CostModel, String> {
+    let mut model = CostModel::default();
+    for part in s.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+        let (key, value) = part
+            .split_once('=')
+            .ok_or_else(|| format!("expected KIND=UNITS, found '{}'", part))?;
+        let value: u64 = value
+            .trim()
+            .parse()
+            .map_err(|_| format!("invalid cost '{}' for {}", value.trim(), key))?;
+        match key.trim() {
+            "op" => {
+                model = CostModel {
+                    moves: value,
+                    arithmetic: value,
+                    io: value,
+                    loops: value,
+                }
+            }
+            "move" => model.moves = value,
+            "arith" => model.arithmetic = value,
+            "io" => model.io = value,
+            "loop" => model.loops = value,
+            other => {
+                return Err(format!(
+                    "unknown instruction kind '{}' (expected op, move, arith, io or loop)",
+                    other
+                ))
+            }
+        }
+    }
+    Ok(model)
+}
+
+/// The cost of one run, printed by `--cost`.
+#[derive(Debug, Clone, Serialize)]
+pub struct CostReport {
+    pub program: String,
+    pub model: CostModel,
+    pub total: u64,
+    /// Every loop that ran, in source order, with the cost of everything
+    /// executed inside it.
+    pub loops: Vec<LoopCost>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LoopCost {
+    /// Offset of the loop's `[`.
+    pub offset: usize,
+    pub line: usize,
+    pub col: usize,
+    pub cost: u64,
+}
+
+/// Collects a `CostReport` from `Interpreter::execute_with` callbacks.
+#[derive(Debug)]
+pub struct CostCollector {
+    model: CostModel,
+    total: u64,
+    loops: BTreeMap<OpIndex, u64>,
+    // `[`s of the loops currently executing.
+    stack: Vec<OpIndex>,
+}
+
+impl CostCollector {
+    pub fn new(model: CostModel) -> Self {
+        Self {
+            model,
+            total: 0,
+            loops: BTreeMap::new(),
+            stack: Vec::new(),
+        }
+    }
+
+    pub fn record(&mut self, interp: &Interpreter, at: OpIndex) {
+        let op = interp.program()[at];
+        let cost = self.model.of(op);
+        self.total += cost;
+        for start in &self.stack {
+            *self.loops.entry(*start).or_default() += cost;
+        }
+
+        let fell_through = at.next() == Some(interp.pc());
+        match op {
+            OpCode::JmpStart if fell_through => self.stack.push(at),
+            OpCode::JmpEnd if fell_through => {
+                self.stack.pop();
+            }
+            _ => {}
+        }
+    }
+
+    /// Units charged so far.
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    pub fn finish(self, program: String, ops: &Program) -> CostReport {
+        let positions = ops.positions();
+        CostReport {
+            program,
+            model: self.model,
+            total: self.total,
+            loops: self
+                .loops
+                .into_iter()
+                .map(|(start, cost)| {
+                    let pos = positions[start.index()];
+                    LoopCost {
+                        offset: pos.offset,
+                        line: pos.line,
+                        col: pos.col,
+                        cost,
+                    }
+                })
+                .collect(),
+        }
+    }
+}
+
+impl fmt::Display for CostReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}:", self.program)?;
+        writeln!(f, "  cost: {} units ({})", self.total, self.model)?;
+        for l in &self.loops {
+            writeln!(
+                f,
+                "  loop at {}:{}: {} units ({:.1}%)",
+                l.line,
+                l.col,
+                l.cost,
+                100.0 * l.cost as f64 / self.total.max(1) as f64
+            )?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,174 @@
+//! `bf daemon` and `bf client`: a long-running interpreter that keeps
+//! programs parsed between runs, and the thin client that asks it for one.
+
+use serde::{Deserialize, Serialize};
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, prelude::*, BufReader};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+use bf::limits::{Limits, OutputLimit};
+use bf::{parse_from, InterpreterBuilder, Program};
+
+/// Where the daemon listens unless told otherwise.
+pub fn default_socket() -> PathBuf {
+    std::env::temp_dir().join("bf-daemon.sock")
+}
+
+/// A program to run and the input to run it on, sent as one JSON line.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Request {
+    /// Absolute, since the daemon's working directory isn't the client's.
+    pub program: PathBuf,
+    #[serde(default)]
+    pub input: Vec<u8>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Response {
+    pub output: Vec<u8>,
+    /// Why the program didn't run to completion, if it didn't.
+    pub error: Option<String>,
+}
+
+/// Settings every run the daemon makes shares.
+#[derive(Debug, Clone, Copy)]
+pub struct Sandbox {
+    pub limits: Limits,
+    pub timeout: Duration,
+    pub max_output: Option<usize>,
+}
+
+#[derive(Debug)]
+struct Cached {
+    // The file's length and modification time when it was parsed.
+    modified: (u64, SystemTime),
+    program: Program,
+}
+
+#[derive(Debug)]
+struct Daemon {
+    sandbox: Sandbox,
+    programs: Mutex<HashMap<PathBuf, Cached>>,
+}
+
+impl Daemon {
+    /// The parsed program at `path`, parsing it again only if the file
+    /// changed since it was last seen.
+    fn program(&self, path: &Path) -> Result<Program, String> {
+        let modified = fs::metadata(path)
+            .and_then(|m| Ok((m.len(), m.modified()?)))
+            .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+        if let Some(cached) = self.programs.lock().unwrap().get(path) {
+            if cached.modified == modified {
+                return Ok(cached.program.clone());
+            }
+        }
+        let file = fs::File::open(path)
+            .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+        let program = parse_from(file, &self.sandbox.limits)
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        self.programs.lock().unwrap().insert(
+            path.to_owned(),
+            Cached {
+                modified,
+                program: program.clone(),
+            },
+        );
+        Ok(program)
+    }
+
+    fn run(&self, request: Request) -> Response {
+        let program = match self.program(&request.program) {
+            Ok(program) => program,
+            Err(e) => {
+                return Response {
+                    output: Vec::new(),
+                    error: Some(e),
+                }
+            }
+        };
+        let mut interp = InterpreterBuilder::new(program)
+            .deadline(Some(Instant::now() + self.sandbox.timeout))
+            .build();
+        let mut output = Vec::new();
+        let result = interp.execute_all(
+            &mut request.input.as_slice(),
+            &mut OutputLimit::new(&mut output, self.sandbox.max_output),
+        );
+        Response {
+            output,
+            error: result.err().map(|e| e.to_string()),
+        }
+    }
+
+    fn answer(&self, stream: UnixStream) -> io::Result<()> {
+        let request = serde_json::from_reader(BufReader::new(&stream))?;
+        let response = self.run(request);
+        serde_json::to_writer(&stream, &response)?;
+        (&stream).write_all(b"\n")
+    }
+}
+
+/// Answers run requests on `socket` until killed, each on its own thread.
+pub fn serve(socket: &Path, sandbox: Sandbox) -> Result<(), String> {
+    let listener = bind(socket).map_err(|e| format!("{}: {}", socket.display(), e))?;
+    let daemon = Arc::new(Daemon {
+        sandbox,
+        programs: Mutex::new(HashMap::new()),
+    });
+    eprintln!("bf daemon listening on {}", socket.display());
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                eprintln!("Failed to accept a connection: {}", e);
+                continue;
+            }
+        };
+        let daemon = daemon.clone();
+        thread::spawn(move || {
+            match daemon.answer(stream) {
+                // A connection closed unused, like `bind` checking on us.
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => {}
+                Err(e) => eprintln!("Bad request: {}", e),
+                Ok(()) => {}
+            }
+        });
+    }
+    Ok(())
+}
+
+/// Listens on `socket`, replacing one left behind by a daemon that's gone.
+fn bind(socket: &Path) -> io::Result<UnixListener> {
+    match UnixListener::bind(socket) {
+        Err(e) if e.kind() == io::ErrorKind::AddrInUse => {
+            if UnixStream::connect(socket).is_ok() {
+                return Err(io::Error::new(
+                    io::ErrorKind::AddrInUse,
+                    "another daemon is already listening",
+                ));
+            }
+            fs::remove_file(socket)?;
+            UnixListener::bind(socket)
+        }
+        result => result,
+    }
+}
+
+/// Sends `request` to the daemon on `socket` and waits for its answer.
+pub fn request(socket: &Path, request: &Request) -> Result<Response, String> {
+    let mut stream = UnixStream::connect(socket)
+        .map_err(|e| format!("No daemon on {}: {}", socket.display(), e))?;
+    let failed = |e: io::Error| format!("Failed to send to {}: {}", socket.display(), e);
+    serde_json::to_writer(&stream, request).map_err(|e| failed(e.into()))?;
+    stream.write_all(b"\n").map_err(failed)?;
+    stream.shutdown(std::net::Shutdown::Write).map_err(failed)?;
+    serde_json::from_reader(BufReader::new(stream))
+        .map_err(|e| format!("Bad answer from {}: {}", socket.display(), e))
+}
@@ -0,0 +1,156 @@
+//! Directives in a program's comments, honored when run with
+//! `--directives`. They use no brainfuck symbols, so other interpreters
+//! ignore them:
+//!
+//! ```text
+//! #assert cell 3 == 65    # a fixed cell
+//! #assert cell != 0       # the cell under the pointer
+//! #assert ptr == 4
+//! #readonly 100 120       # cells 100 through 120 can't be written
+//! ```
+//!
+//! Each `#assert` is checked just before the first instruction after its
+//! line runs. `#readonly` applies to the whole run.
+
+use std::fmt;
+use std::ops::Range;
+
+use crate::program::{OpIndex, Program};
+use crate::Interpreter;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Subject {
+    Cell(usize),
+    Current,
+    Pointer,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Assertion {
+    line: usize,
+    subject: Subject,
+    equal: bool,
+    value: usize,
+}
+
+impl fmt::Display for Subject {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Subject::Cell(i) => write!(f, "cell {}", i),
+            Subject::Current => write!(f, "cell"),
+            Subject::Pointer => write!(f, "ptr"),
+        }
+    }
+}
+
+impl Assertion {
+    fn parse(text: &str, line: usize) -> Result<Self, String> {
+        let err = || format!("line {}: malformed #assert '{}'", line, text.trim());
+        let words: Vec<&str> = text.split_whitespace().collect();
+        let (subject, rest) = match words.as_slice() {
+            ["ptr", rest @ ..] => (Subject::Pointer, rest),
+            ["cell", n, rest @ ..] if n.parse::<usize>().is_ok() => {
+                (Subject::Cell(n.parse().unwrap()), rest)
+            }
+            ["cell", rest @ ..] => (Subject::Current, rest),
+            _ => return Err(err()),
+        };
+        let (equal, value) = match rest {
+            ["==", v] => (true, v),
+            ["!=", v] => (false, v),
+            _ => return Err(err()),
+        };
+        Ok(Self {
+            line,
+            subject,
+            equal,
+            value: value.parse().map_err(|_| err())?,
+        })
+    }
+
+    /// An error describing the failure, if `interp` doesn't satisfy this.
+    pub fn check(&self, interp: &Interpreter) -> Result<(), String> {
+        let tape = &interp.inner;
+        let actual = match self.subject {
+            Subject::Cell(i) => match tape.cell(i) {
+                Some(v) => v as usize,
+                None => {
+                    return Err(format!(
+                        "Assertion on line {} names cell {}, past the end of the tape",
+                        self.line, i
+                    ))
+                }
+            },
+            Subject::Current => tape.full_value() as usize,
+            Subject::Pointer => interp.inner.pointer,
+        };
+        if (actual == self.value) == self.equal {
+            return Ok(());
+        }
+        Err(format!(
+            "Assertion failed on line {}: {} {} {} ({} is {})",
+            self.line,
+            self.subject,
+            if self.equal { "==" } else { "!=" },
+            self.value,
+            self.subject,
+            actual
+        ))
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Directives {
+    /// By the instruction they run before, in order.
+    pub assertions: Vec<(OpIndex, Assertion)>,
+    pub read_only: Vec<Range<usize>>,
+}
+
+fn parse_read_only(text: &str, line: usize) -> Result<Range<usize>, String> {
+    let err = || format!("line {}: malformed #readonly '{}'", line, text.trim());
+    let cells: Vec<usize> = text
+        .split_whitespace()
+        .map(|n| n.parse().map_err(|_| err()))
+        .collect::<Result<_, _>>()?;
+    match *cells.as_slice() {
+        [cell] => Ok(cell..cell + 1),
+        [first, last] if first <= last => Ok(first..last + 1),
+        _ => Err(err()),
+    }
+}
+
+/// Finds the directives in `source`.
+pub fn parse(source: &[u8], program: &Program) -> Result<Directives, String> {
+    let positions = program.positions();
+    let mut directives = Directives::default();
+    let mut offset = 0;
+    for (line, text) in source.split(|&b| b == b'\n').enumerate() {
+        let end = offset + text.len();
+        offset = end + 1;
+
+        let text = String::from_utf8_lossy(text);
+        let (name, rest) = match ["#assert", "#readonly"]
+            .iter()
+            .find_map(|name| text.find(name).map(|at| (*name, &text[at + name.len()..])))
+        {
+            Some(found) => found,
+            None => continue,
+        };
+        if rest.contains(|c| "<>+-.,[]".contains(c)) {
+            return Err(format!(
+                "line {}: {} can't contain brainfuck symbols",
+                line + 1,
+                name
+            ));
+        }
+
+        if name == "#assert" {
+            let next = positions.partition_point(|p| p.offset < end);
+            let assertion = Assertion::parse(rest, line + 1)?;
+            directives.assertions.push((OpIndex::new(next), assertion));
+        } else {
+            directives.read_only.push(parse_read_only(rest, line + 1)?);
+        }
+    }
+    Ok(directives)
+}
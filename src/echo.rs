@@ -0,0 +1,76 @@
+//! `--echo-input`: the bytes `,` reads, shown where the program's output
+//! goes or on stderr, so transcripts read like the session did.
+
+use std::cell::RefCell;
+use std::io::{self, prelude::*, stderr};
+use std::rc::Rc;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum EchoTo {
+    /// In the program's output, before what it writes next.
+    Stdout,
+    Stderr,
+}
+
+/// Input that echoes each byte read from `inner`.
+#[derive(Debug)]
+pub struct EchoInput<R> {
+    inner: R,
+    /// Read bytes for `EchoOutput` to write, or `None` to write to stderr.
+    pending: Option<Rc<RefCell<Vec<u8>>>>,
+}
+
+/// Output that writes the bytes an `EchoInput` read ahead of its own.
+#[derive(Debug)]
+pub struct EchoOutput<W> {
+    inner: W,
+    pending: Rc<RefCell<Vec<u8>>>,
+}
+
+/// `reader` and `writer` with reads echoed `to` the given place.
+pub fn echo<R, W>(reader: R, writer: W, to: EchoTo) -> (EchoInput<R>, EchoOutput<W>) {
+    let pending = Rc::default();
+    let input = EchoInput {
+        inner: reader,
+        pending: (to == EchoTo::Stdout).then(|| Rc::clone(&pending)),
+    };
+    let output = EchoOutput {
+        inner: writer,
+        pending,
+    };
+    (input, output)
+}
+
+impl<R: Read> Read for EchoInput<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        match self.pending {
+            Some(ref pending) => pending.borrow_mut().extend_from_slice(&buf[..n]),
+            None => {
+                let mut err = stderr().lock();
+                err.write_all(&buf[..n])?;
+                err.flush()?;
+            }
+        }
+        Ok(n)
+    }
+}
+
+impl<W: Write> EchoOutput<W> {
+    fn write_pending(&mut self) -> io::Result<()> {
+        let pending = std::mem::take(&mut *self.pending.borrow_mut());
+        self.inner.write_all(&pending)
+    }
+}
+
+impl<W: Write> Write for EchoOutput<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.write_pending()?;
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.write_pending()?;
+        self.inner.flush()
+    }
+}
@@ -0,0 +1,51 @@
+//! Program sources given as URLs, for `bf run https://...`.
+
+use std::path::Path;
+
+use sha2::{Digest, Sha256};
+
+/// The URL `path` spells, if it is one.
+pub fn url(path: &Path) -> Option<&str> {
+    path.to_str()
+        .filter(|path| path.starts_with("https://") || path.starts_with("http://"))
+}
+
+/// The body at `url`, refusing anything longer than `limit` bytes.
+#[cfg(feature = "net")]
+pub fn fetch(url: &str, limit: usize) -> Result<Vec<u8>, String> {
+    let mut response = ureq::get(url)
+        .call()
+        .map_err(|e| format!("Failed to fetch {}: {}", url, e))?;
+    response
+        .body_mut()
+        .with_config()
+        .limit(limit as u64)
+        .read_to_vec()
+        .map_err(|e| match e {
+            ureq::Error::BodyExceedsLimit(_) => {
+                format!("{} is larger than --fetch-limit {} bytes", url, limit)
+            }
+            e => format!("Failed to fetch {}: {}", url, e),
+        })
+}
+
+#[cfg(not(feature = "net"))]
+pub fn fetch(url: &str, _: usize) -> Result<Vec<u8>, String> {
+    Err(format!("{}: fetching URLs requires the net feature", url))
+}
+
+/// Checks that `bytes` hash to `expected`, a hex SHA-256 digest.
+pub fn verify(bytes: &[u8], expected: &str) -> Result<(), String> {
+    let actual: String = Sha256::digest(bytes)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    if actual.eq_ignore_ascii_case(expected) {
+        Ok(())
+    } else {
+        Err(format!(
+            "SHA-256 is {}, but --sha256 expects {}",
+            actual, expected
+        ))
+    }
+}
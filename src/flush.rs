@@ -0,0 +1,125 @@
+use std::fmt;
+use std::io::{self, Write};
+use std::str::FromStr;
+
+use crate::units;
+
+/// Bytes `FlushPolicy::Exit` holds before handing them on unflushed.
+const EXIT_BUFFER: usize = 8192;
+
+/// When a `FlushWriter` flushes the program's output.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FlushPolicy {
+    /// After every byte, so output appears as soon as it's written.
+    #[default]
+    Byte,
+    /// After each newline.
+    Line,
+    /// Only when the run ends, or before the program reads input.
+    Exit,
+    /// Whenever this many bytes are waiting.
+    Every(usize),
+}
+
+impl FromStr for FlushPolicy {
+    type Err = String;
+
+    /// `byte`, `line`, `exit`, or a size such as `4KiB` for `Every`.
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s {
+            "byte" => Ok(FlushPolicy::Byte),
+            "line" => Ok(FlushPolicy::Line),
+            "exit" => Ok(FlushPolicy::Exit),
+            _ => match units::parse_size(s) {
+                Ok(0) => Err("flushing every 0 bytes never flushes".into()),
+                Ok(n) => Ok(FlushPolicy::Every(n)),
+                Err(_) => Err(format!(
+                    "unknown flush policy '{}' (expected byte, line, exit or a size)",
+                    s
+                )),
+            },
+        }
+    }
+}
+
+impl fmt::Display for FlushPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FlushPolicy::Byte => f.write_str("byte"),
+            FlushPolicy::Line => f.write_str("line"),
+            FlushPolicy::Exit => f.write_str("exit"),
+            FlushPolicy::Every(n) => n.fmt(f),
+        }
+    }
+}
+
+/// Buffers writes to `inner`, flushing it as `policy` says. Whatever is
+/// left is flushed by `flush` or on drop.
+///
+/// ```
+/// use bf::flush::{FlushPolicy, FlushWriter};
+/// use std::io::Write;
+///
+/// let mut out = FlushWriter::new(Vec::new(), FlushPolicy::Line);
+/// out.write_all(b"one\n").unwrap();
+/// out.write_all(b"tw").unwrap();
+/// assert_eq!(out.get_ref(), b"one\n");
+/// out.flush().unwrap();
+/// assert_eq!(out.get_ref(), b"one\ntw");
+/// ```
+#[derive(Debug)]
+pub struct FlushWriter<W: Write> {
+    inner: W,
+    policy: FlushPolicy,
+    buf: Vec<u8>,
+}
+
+impl<W: Write> FlushWriter<W> {
+    pub fn new(inner: W, policy: FlushPolicy) -> Self {
+        Self {
+            inner,
+            policy,
+            buf: Vec::new(),
+        }
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    fn write_buf(&mut self) -> io::Result<()> {
+        let result = self.inner.write_all(&self.buf);
+        self.buf.clear();
+        result
+    }
+}
+
+impl<W: Write> Write for FlushWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.policy == FlushPolicy::Byte {
+            let n = self.inner.write(buf)?;
+            self.inner.flush()?;
+            return Ok(n);
+        }
+
+        self.buf.extend_from_slice(buf);
+        match self.policy {
+            FlushPolicy::Line if buf.contains(&b'\n') => self.flush()?,
+            FlushPolicy::Every(n) if self.buf.len() >= n => self.flush()?,
+            FlushPolicy::Exit if self.buf.len() >= EXIT_BUFFER => self.write_buf()?,
+            _ => {}
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.write_buf()?;
+        self.inner.flush()
+    }
+}
+
+impl<W: Write> Drop for FlushWriter<W> {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
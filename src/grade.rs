@@ -0,0 +1,306 @@
+//! `bf grade`: runs every submission against the test cases in a spec.
+//!
+//! ```toml
+//! cost = "op=1,io=10"       # optional, see --cost
+//!
+//! [limits]                  # all optional
+//! timeout = "2s"            # per test case
+//! max_output = "64KiB"
+//! max_program_bytes = "16KiB"
+//! max_instructions = "10k"
+//! tape_size = 30000
+//!
+//! [[test]]
+//! name = "echo"
+//! input = "abc"
+//! output = "abc"
+//! ```
+//!
+//! Submissions only ever run in-process under these limits, with a
+//! timeout and an output cap even when the spec names none.
+
+use serde::{Deserialize, Serialize};
+
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use bf::limits::{Limits, OutputLimit};
+use bf::program::Program;
+use bf::tape::ARRAY_SIZE;
+use bf::{parse, units, InterpreterBuilder};
+
+use crate::cost::{self, CostCollector, CostModel};
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+const DEFAULT_MAX_OUTPUT: usize = 1 << 20;
+// Longest expected or actual output shown in a failure.
+const SHOWN_BYTES: usize = 60;
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Spec {
+    #[serde(default)]
+    cost: Option<String>,
+    #[serde(default)]
+    limits: SpecLimits,
+    #[serde(rename = "test")]
+    tests: Vec<TestCase>,
+}
+
+/// Limits as they're written on the command line, e.g. `2s` or `64KiB`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SpecLimits {
+    timeout: Option<String>,
+    max_output: Option<String>,
+    max_program_bytes: Option<String>,
+    max_instructions: Option<String>,
+    tape_size: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TestCase {
+    name: String,
+    #[serde(default)]
+    input: String,
+    output: String,
+}
+
+/// A `Spec` with its values parsed.
+#[derive(Debug)]
+struct Sandbox {
+    model: CostModel,
+    limits: Limits,
+    timeout: Duration,
+    max_output: usize,
+    tape_size: Option<usize>,
+}
+
+impl Spec {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let spec: Self = toml::from_str(&text).map_err(|e| format!("{}: {}", path.display(), e))?;
+        if spec.tests.is_empty() {
+            return Err(format!("{}: no [[test]] cases", path.display()));
+        }
+        Ok(spec)
+    }
+
+    fn sandbox(&self) -> Result<Sandbox, String> {
+        let l = &self.limits;
+        Ok(Sandbox {
+            model: match self.cost {
+                Some(ref model) => cost::parse_model(model)?,
+                None => CostModel::default(),
+            },
+            limits: Limits {
+                bytes: l
+                    .max_program_bytes
+                    .as_deref()
+                    .map(units::parse_size)
+                    .transpose()?,
+                instructions: l
+                    .max_instructions
+                    .as_deref()
+                    .map(units::parse_count)
+                    .transpose()?,
+                ..Limits::default()
+            },
+            timeout: match l.timeout {
+                Some(ref t) => units::parse_duration(t)?,
+                None => DEFAULT_TIMEOUT,
+            },
+            max_output: match l.max_output {
+                Some(ref size) => units::parse_size(size)?,
+                None => DEFAULT_MAX_OUTPUT,
+            },
+            tape_size: match l.tape_size {
+                Some(0) => return Err("tape_size must be at least 1".into()),
+                size => size,
+            },
+        })
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Report {
+    pub submissions: Vec<Submission>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Submission {
+    pub file: String,
+    pub passed: usize,
+    pub total: usize,
+    /// Summed over every test case.
+    pub cost: u64,
+    /// Why the submission couldn't be run at all.
+    pub error: Option<String>,
+    pub tests: Vec<TestResult>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TestResult {
+    pub name: String,
+    pub passed: bool,
+    pub cost: u64,
+    /// Why the program stopped early, if it did.
+    pub error: Option<String>,
+    pub expected: Option<String>,
+    pub actual: Option<String>,
+    /// Byte offset of the first difference in the output.
+    pub differs_at: Option<usize>,
+}
+
+impl Report {
+    pub fn all_passed(&self) -> bool {
+        self.submissions.iter().all(|s| s.passed == s.total)
+    }
+}
+
+/// The `.b` and `.bf` files among `paths`, looking one level into
+/// directories, in a stable order.
+pub fn submissions(paths: &[PathBuf]) -> Result<Vec<PathBuf>, String> {
+    let is_program = |p: &Path| {
+        p.is_file() && matches!(p.extension().and_then(|e| e.to_str()), Some("b" | "bf"))
+    };
+    let mut files = Vec::new();
+    for path in paths {
+        if path.is_dir() {
+            let entries = fs::read_dir(path)
+                .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+            let mut found: Vec<PathBuf> = entries
+                .filter_map(|e| e.ok().map(|e| e.path()))
+                .filter(|p| is_program(p))
+                .collect();
+            found.sort();
+            files.extend(found);
+        } else {
+            files.push(path.clone());
+        }
+    }
+    Ok(files)
+}
+
+pub fn grade(spec: &Spec, files: &[PathBuf]) -> Result<Report, String> {
+    let sandbox = spec.sandbox()?;
+    let submissions = files
+        .iter()
+        .map(|file| grade_one(spec, &sandbox, file))
+        .collect();
+    Ok(Report { submissions })
+}
+
+fn grade_one(spec: &Spec, sandbox: &Sandbox, file: &Path) -> Submission {
+    let mut submission = Submission {
+        file: file.display().to_string(),
+        passed: 0,
+        total: spec.tests.len(),
+        cost: 0,
+        error: None,
+        tests: Vec::new(),
+    };
+    let program = match fs::read(file) {
+        Ok(source) => parse(source, &sandbox.limits).map_err(|e| e.to_string()),
+        Err(e) => Err(format!("Failed to read: {}", e)),
+    };
+    let program = match program {
+        Ok(program) => program,
+        Err(e) => {
+            submission.error = Some(e);
+            return submission;
+        }
+    };
+
+    for case in &spec.tests {
+        let result = run_case(&program, sandbox, case);
+        submission.passed += result.passed as usize;
+        submission.cost += result.cost;
+        submission.tests.push(result);
+    }
+    submission
+}
+
+fn run_case(program: &Program, sandbox: &Sandbox, case: &TestCase) -> TestResult {
+    let mut interp = InterpreterBuilder::new(program.clone())
+        .tape_size(sandbox.tape_size.unwrap_or(ARRAY_SIZE))
+        .deadline(Some(Instant::now() + sandbox.timeout))
+        .build();
+    interp.wait = 0;
+
+    let mut output = Vec::new();
+    let mut cost = CostCollector::new(sandbox.model);
+    let result = interp.execute_with(
+        &mut case.input.as_bytes(),
+        &mut OutputLimit::new(&mut output, Some(sandbox.max_output)),
+        |interp, at| cost.record(interp, at),
+    );
+
+    let expected = case.output.as_bytes();
+    let differs_at = (output != expected).then(|| {
+        output
+            .iter()
+            .zip(expected)
+            .position(|(a, b)| a != b)
+            .unwrap_or_else(|| output.len().min(expected.len()))
+    });
+    let error = result.err().map(|e| e.to_string());
+    TestResult {
+        name: case.name.clone(),
+        passed: error.is_none() && differs_at.is_none(),
+        cost: cost.total(),
+        expected: differs_at.map(|_| shown(expected)),
+        actual: differs_at.map(|_| shown(&output)),
+        differs_at,
+        error,
+    }
+}
+
+fn shown(bytes: &[u8]) -> String {
+    let text = String::from_utf8_lossy(&bytes[..bytes.len().min(SHOWN_BYTES)]);
+    let mut shown = format!("{:?}", text);
+    if bytes.len() > SHOWN_BYTES {
+        shown.push_str("...");
+    }
+    shown
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for s in &self.submissions {
+            if let Some(ref e) = s.error {
+                writeln!(f, "{}: 0/{} passed: {}", s.file, s.total, e)?;
+                continue;
+            }
+            writeln!(
+                f,
+                "{}: {}/{} passed, cost {}",
+                s.file, s.passed, s.total, s.cost
+            )?;
+            for t in &s.tests {
+                if t.passed {
+                    writeln!(f, "  ok    {} ({} units)", t.name, t.cost)?;
+                    continue;
+                }
+                match t.error {
+                    Some(ref e) => writeln!(f, "  FAIL  {}: {}", t.name, e)?,
+                    None => writeln!(f, "  FAIL  {}", t.name)?,
+                }
+                if let Some(at) = t.differs_at {
+                    writeln!(f, "        output differs at byte {}", at)?;
+                    writeln!(
+                        f,
+                        "        expected: {}",
+                        t.expected.as_deref().unwrap_or("")
+                    )?;
+                    writeln!(f, "        actual:   {}", t.actual.as_deref().unwrap_or(""))?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
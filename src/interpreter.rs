@@ -0,0 +1,768 @@
+use std::convert::TryFrom;
+use std::fmt;
+use std::io::prelude::*;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::directives;
+use crate::flush::{FlushPolicy, FlushWriter};
+use crate::ir::{Ir, Op, OptLevel};
+use crate::parser::OpCode;
+use crate::program::{OpIndex, Program};
+use crate::render::{Pacer, Visualizer};
+use crate::snapshot::Snapshot;
+use crate::state::{self, StateFormat};
+use crate::tape::{Action, BfArray, Bounds, CellSize, Eof, Overflow, Storage, ARRAY_SIZE};
+
+/// Stops every interpreter running `execute_with` once set, e.g. by a
+/// Ctrl+C handler.
+pub static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// The outcome of one `Interpreter::step`.
+#[derive(Debug)]
+pub enum Status {
+    /// An instruction ran; there may be more.
+    Running,
+    /// `,` or `.` would block on a non-blocking reader or writer. Nothing
+    /// changed, so stepping again retries the instruction.
+    Blocked,
+    /// There are no instructions left.
+    Halted,
+    /// The output was closed by its reader, e.g. a pipe into `head`.
+    OutputClosed,
+    /// The instruction failed and the program can't continue.
+    Error(String),
+}
+
+/// Why `Interpreter::execute_with` stopped before the program halted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RunError {
+    /// Nothing is reading the program's output any more.
+    OutputClosed,
+    Other(String),
+}
+
+impl fmt::Display for RunError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RunError::OutputClosed => write!(f, "Output closed"),
+            RunError::Other(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+/// Runs a `Program` against a `BfArray`.
+#[derive(Debug)]
+pub struct Interpreter {
+    pub(crate) inner: BfArray,
+    pub(crate) ops: Program,
+    pub(crate) pointer: OpIndex,
+    /// Milliseconds to pause after each instruction, drawing the tape with
+    /// `visualizer`. Zero turns the visualizer off.
+    pub wait: u64,
+    pub visualizer: Visualizer,
+    /// Frames a second to draw the tape at with `visualizer` while the
+    /// program runs on at full speed, instead of pausing like `wait`.
+    pub frame_rate: Option<u32>,
+    /// The cell written by the last `+`, `-` or `,`, while visualizing.
+    last_write: Option<usize>,
+    /// How `execute_all` optimizes the program.
+    pub opt_level: OptLevel,
+    /// When `execute_all` and `execute_with` flush the program's output.
+    /// `step` flushes after every byte.
+    pub flush: FlushPolicy,
+    /// When `execute_with` gives up on the program.
+    pub deadline: Option<Instant>,
+    /// Most iterations any one entry into a loop may run.
+    pub loop_cap: Option<u64>,
+    /// Times each loop currently executing has jumped back, innermost last,
+    /// when `loop_cap` is set.
+    iterations: Vec<u64>,
+    /// `#assert` directives by the instruction they precede, when enabled.
+    pub assertions: Vec<(OpIndex, directives::Assertion)>,
+    /// Cells that instructions may not write to.
+    pub read_only: Vec<std::ops::Range<usize>>,
+    /// The modules that make up the program, in order, when each may only
+    /// write to the cells it owns.
+    pub modules: Vec<Module>,
+}
+
+/// Part of a program, and the cells its instructions may write to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Module {
+    pub code: std::ops::Range<OpIndex>,
+    pub cells: Vec<std::ops::Range<usize>>,
+    /// The line the module starts on, to name it by.
+    pub line: usize,
+}
+
+impl Interpreter {
+    pub fn new(ops: Program) -> Self {
+        let wait = std::env::var("BF_VISUALIZER_TIME")
+            .map(|s| s.parse().unwrap_or(0))
+            .unwrap_or(0);
+
+        Self {
+            inner: Default::default(),
+            ops,
+            pointer: Default::default(),
+            wait,
+            visualizer: Visualizer::default(),
+            frame_rate: None,
+            last_write: None,
+            opt_level: OptLevel::default(),
+            flush: FlushPolicy::default(),
+            deadline: None,
+            loop_cap: None,
+            iterations: Vec::new(),
+            assertions: Vec::new(),
+            read_only: Vec::new(),
+            modules: Vec::new(),
+        }
+    }
+
+    pub fn program(&self) -> &Program {
+        &self.ops
+    }
+
+    /// Index of the next instruction to execute.
+    pub fn pc(&self) -> OpIndex {
+        self.pointer
+    }
+
+    pub fn tape(&self) -> &BfArray {
+        &self.inner
+    }
+
+    pub fn tape_mut(&mut self) -> &mut BfArray {
+        &mut self.inner
+    }
+
+    /// Runs to completion from the first instruction. Unless a debugging
+    /// option needs checking between instructions, `opt_level` is `O0` or
+    /// this is a paranoid build, runs the program lowered to an `Ir`.
+    pub fn execute_all<R: Read, W: Write>(
+        &mut self,
+        reader: &mut R,
+        writer: &mut W,
+    ) -> Result<(), RunError> {
+        if cfg!(feature = "paranoid") || self.has_checks() || self.opt_level == OptLevel::O0 {
+            return self.execute_with(reader, writer, |_, _| {});
+        }
+        self.pointer = OpIndex::default();
+        self.iterations.clear();
+        let writer = &mut FlushWriter::new(writer, self.flush);
+        let result = self.execute_lowered(reader, writer);
+        self.last_frame();
+        finish(writer, result)
+    }
+
+    /// Runs to completion with `input` for `,`, returning what the program
+    /// wrote:
+    ///
+    /// ```
+    /// let program = bf::parse(b",+.".iter().copied(), &Default::default()).unwrap();
+    /// let output = bf::Interpreter::new(program).run_on(b"a").unwrap();
+    /// assert_eq!(output, b"b");
+    /// ```
+    pub fn run_on(&mut self, mut input: &[u8]) -> Result<Vec<u8>, RunError> {
+        let mut output = Vec::new();
+        self.execute_all(&mut input, &mut output)?;
+        Ok(output)
+    }
+
+    /// Runs to completion, calling `on_step` with the index of each
+    /// instruction after it executes.
+    pub fn execute_with<R, W, F>(
+        &mut self,
+        reader: &mut R,
+        writer: &mut W,
+        on_step: F,
+    ) -> Result<(), RunError>
+    where
+        R: Read,
+        W: Write,
+        F: FnMut(&Self, OpIndex),
+    {
+        self.pointer = OpIndex::default();
+        self.iterations.clear();
+        let writer = &mut FlushWriter::new(writer, self.flush);
+        let result = if self.has_checks() {
+            self.execute_as::<true, R, _, F>(reader, writer, on_step)
+        } else {
+            self.execute_as::<false, R, _, F>(reader, writer, on_step)
+        };
+        self.last_frame();
+        finish(writer, result)
+    }
+
+    /// Draws the tape as the run left it, when `frame_rate` is set, since
+    /// the frames before may have missed the end.
+    fn last_frame(&self) {
+        if self.frame_rate.is_some() {
+            let _ = self.visualizer.draw(&self.inner, None);
+        }
+    }
+
+    /// Whether any debugging option needs checking between instructions.
+    fn has_checks(&self) -> bool {
+        !self.assertions.is_empty()
+            || !self.read_only.is_empty()
+            || !self.modules.is_empty()
+            || self.loop_cap.is_some()
+            || self.wait > 0
+    }
+
+    /// The run loop, compiled separately with and without the checks
+    /// `has_checks` reports so plain runs don't branch on them.
+    fn execute_as<const CHECKED: bool, R, W, F>(
+        &mut self,
+        reader: &mut R,
+        writer: &mut W,
+        mut on_step: F,
+    ) -> Result<(), RunError>
+    where
+        R: Read,
+        W: Write,
+        F: FnMut(&Self, OpIndex),
+    {
+        let mut pacer = self.frame_rate.map(Pacer::new);
+        for steps in 0u64.. {
+            if steps.is_multiple_of(4096) {
+                check_stop(self.deadline)?;
+                if pacer.as_mut().is_some_and(Pacer::due) {
+                    let _ = self.visualizer.draw(&self.inner, None);
+                }
+            }
+
+            let at = self.pointer;
+            match self.step_as::<CHECKED, R, W>(reader, writer) {
+                Status::Running => on_step(self, at),
+                Status::Blocked => {
+                    // Waiting on input mustn't outlast the deadline.
+                    check_stop(self.deadline)?;
+                    thread::yield_now();
+                }
+                Status::Halted => break,
+                Status::OutputClosed => return Err(RunError::OutputClosed),
+                Status::Error(s) => return Err(RunError::Other(s)),
+            }
+        }
+        Ok(())
+    }
+
+    /// `execute_as` without checks or callbacks, on the program lowered to
+    /// an `Ir`.
+    fn execute_lowered<R: Read, W: Write>(
+        &mut self,
+        reader: &mut R,
+        writer: &mut W,
+    ) -> Result<(), RunError> {
+        // Folded arithmetic only ever wraps.
+        if !self.inner.is_dense() || self.inner.overflow() != Overflow::Wrap {
+            return self.execute_as::<false, R, W, _>(reader, writer, |_, _| {});
+        }
+        let ir = Ir::lower_at(&self.ops, self.opt_level);
+        let ops = ir.ops();
+        let tape = &mut self.inner;
+        let mut pc = 0;
+        let mut pacer = self.frame_rate.map(Pacer::new);
+
+        for steps in 0u64.. {
+            let op = match ops.get(pc) {
+                Some(&op) => op,
+                None => break,
+            };
+            if steps.is_multiple_of(4096) {
+                if let Err(e) = check_stop(self.deadline) {
+                    self.pointer = ir.origin(pc);
+                    return Err(e);
+                }
+                if pacer.as_mut().is_some_and(Pacer::due) {
+                    let _ = self.visualizer.draw(tape, None);
+                }
+            }
+            let cell = &mut tape.raw.dense_mut()[tape.pointer];
+            match op {
+                Op::Increment(n) => *cell = cell.wrapping_add(n),
+                Op::Decrement(n) => *cell = cell.wrapping_sub(n),
+                Op::SetZero => *cell = 0,
+                Op::Scan(stride) => match tape.scan(stride) {
+                    Some(to) => tape.pointer = to,
+                    None => {
+                        self.pointer = ir.origin(pc);
+                        return self.execute_as::<false, R, W, _>(reader, writer, |_, _| {});
+                    }
+                },
+                Op::Multiply(_) if *cell == 0 => {}
+                Op::Multiply(factors) => {
+                    let value = *cell;
+                    let (low, high) = ir.reach(factors);
+                    let factors = ir.factors(factors);
+                    let (at, len) = (tape.pointer, tape.len());
+                    let target =
+                        |offset: isize| at.checked_add_signed(offset).filter(|&to| to < len);
+                    // The loop may step past the edge on its way between
+                    // the cells it adds to.
+                    if target(low).is_none() || target(high).is_none() {
+                        // Leave the error to the loop itself, as for moves.
+                        self.pointer = ir.origin(pc);
+                        return self.execute_as::<false, R, W, _>(reader, writer, |_, _| {});
+                    }
+                    let raw = tape.raw.dense_mut();
+                    for &(offset, factor) in factors {
+                        let to = target(offset).unwrap();
+                        raw[to] = raw[to].wrapping_add(value.wrapping_mul(factor));
+                    }
+                    raw[at] = 0;
+                }
+                Op::MoveForward(n) | Op::MoveBack(n) => {
+                    let by = isize::try_from(n).unwrap_or(isize::MAX);
+                    let to = match op {
+                        Op::MoveForward(_) => tape.destination(by),
+                        _ => tape.destination(-by),
+                    };
+                    match to {
+                        Some(to) => tape.pointer = to,
+                        // Step from the start of the run so the error
+                        // leaves the pointer at the edge, as it would have.
+                        None => {
+                            self.pointer = ir.origin(pc);
+                            return self.execute_as::<false, R, W, _>(reader, writer, |_, _| {});
+                        }
+                    }
+                }
+                Op::Output | Op::Input => {
+                    let action = match op {
+                        Op::Output => tape.output(writer),
+                        _ => {
+                            // Show any prompt before waiting for an answer.
+                            let _ = writer.flush();
+                            tape.input(reader)
+                        }
+                    };
+                    match action {
+                        Action::None => {}
+                        Action::Block => {
+                            if let Err(e) = check_stop(self.deadline) {
+                                self.pointer = ir.origin(pc);
+                                return Err(e);
+                            }
+                            thread::yield_now();
+                            continue;
+                        }
+                        Action::OutputClosed => {
+                            self.pointer = ir.origin(pc);
+                            return Err(RunError::OutputClosed);
+                        }
+                        Action::Exit(s) => {
+                            self.pointer = ir.origin(pc);
+                            return Err(RunError::Other(s));
+                        }
+                        Action::JumpForward | Action::JumpBack => unreachable!(),
+                    }
+                }
+                Op::JmpStart(end) if *cell == 0 => pc = end,
+                Op::JmpEnd(start) if *cell != 0 => pc = start,
+                Op::JmpStart(_) | Op::JmpEnd(_) => {}
+            }
+            pc += 1;
+        }
+        self.pointer = OpIndex::new(self.ops.positions().len());
+        Ok(())
+    }
+
+    /// The tape, pointers, open loops and a few counts, in a `format`
+    /// described in `state`.
+    pub fn export_state(&self, format: StateFormat) -> String {
+        state::export(self, format)
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            tape: self.inner.cells_in(0..self.inner.len()).into_owned(),
+            pointer: self.inner.pointer,
+            ip: self.pointer,
+            settings: self.inner.settings(),
+        }
+    }
+
+    /// Executes the next instruction, for callers that drive execution
+    /// themselves. A `[` whose cell is zero skips its whole loop in one
+    /// step. `deadline` and `INTERRUPTED` are left to the caller.
+    ///
+    /// ```
+    /// use bf::{parse, Interpreter, Status};
+    ///
+    /// let program = parse(b"+++[-]".iter().copied(), &Default::default()).unwrap();
+    /// let mut interp = Interpreter::new(program);
+    /// for _ in 0..3 {
+    ///     interp.step(&mut std::io::empty(), &mut std::io::sink());
+    /// }
+    /// assert_eq!(interp.tape().value(), 3);
+    /// while let Status::Running = interp.step(&mut std::io::empty(), &mut std::io::sink()) {}
+    /// assert_eq!(interp.tape().value(), 0);
+    /// ```
+    pub fn step<R: Read, W: Write>(&mut self, reader: &mut R, writer: &mut W) -> Status {
+        let writer = &mut FlushWriter::new(writer, FlushPolicy::Byte);
+        self.step_as::<true, R, _>(reader, writer)
+    }
+
+    fn step_as<const CHECKED: bool, R: Read, W: Write>(
+        &mut self,
+        reader: &mut R,
+        writer: &mut W,
+    ) -> Status {
+        #[cfg(feature = "debug-invariants")]
+        self.check_invariants();
+
+        if CHECKED && !self.assertions.is_empty() {
+            let first = self
+                .assertions
+                .partition_point(|&(at, _)| at < self.pointer);
+            for (at, assertion) in &self.assertions[first..] {
+                if *at != self.pointer {
+                    break;
+                }
+                if let Err(e) = assertion.check(self) {
+                    return Status::Error(e);
+                }
+            }
+        }
+
+        let op = match self.ops.get(self.pointer) {
+            Some(op) => op,
+            None => return Status::Halted,
+        };
+        if CHECKED
+            && !self.read_only.is_empty()
+            && matches!(op, OpCode::Increment | OpCode::Decrement | OpCode::Input)
+            && self
+                .read_only
+                .iter()
+                .any(|r| r.contains(&self.inner.pointer))
+        {
+            return Status::Error(format!(
+                "Write to read-only cell {} at {}",
+                self.inner.pointer,
+                self.ops.positions()[self.pointer.index()]
+            ));
+        }
+        if CHECKED
+            && !self.modules.is_empty()
+            && matches!(op, OpCode::Increment | OpCode::Decrement | OpCode::Input)
+        {
+            let at = self.pointer;
+            let module = self
+                .modules
+                .iter()
+                .find(|m| m.code.start <= at && at < m.code.end);
+            if let Some(module) = module {
+                if !module.cells.iter().any(|r| r.contains(&self.inner.pointer)) {
+                    return Status::Error(format!(
+                        "Write to cell {} at {}, which the module from line {} doesn't own",
+                        self.inner.pointer,
+                        self.ops.positions()[at.index()],
+                        module.line
+                    ));
+                }
+            }
+        }
+
+        if *op == OpCode::Input {
+            // A failed flush shows up again on the next write.
+            let _ = writer.flush();
+        }
+        match self.inner.perform_operation(op, reader, writer) {
+            Action::None => {}
+            Action::Block => return Status::Blocked,
+            Action::OutputClosed => return Status::OutputClosed,
+            Action::Exit(s) => return Status::Error(s),
+            Action::JumpForward => {
+                // Land on the op after the matching `]`.
+                self.pointer = self.ops.partner(self.pointer);
+                self.increment_pointer();
+                return Status::Running;
+            }
+            Action::JumpBack => {
+                if let Some(cap) = self.loop_cap.filter(|_| CHECKED) {
+                    // The cap may have been set inside the loop, after its
+                    // `[` would have started the count.
+                    if self.iterations.is_empty() {
+                        self.iterations.push(0);
+                    }
+                    let innermost = self.iterations.len() - 1;
+                    let jumps = &mut self.iterations[innermost];
+                    *jumps += 1;
+                    if *jumps >= cap {
+                        return Status::Error(self.loop_cap_error(cap));
+                    }
+                }
+                // Jump back should land on Op after the matching `[`
+                self.pointer = self.ops.partner(self.pointer);
+                self.increment_pointer();
+                return Status::Running;
+            }
+        }
+
+        // If Action::None
+        match op {
+            OpCode::JmpStart => {
+                if CHECKED && self.loop_cap.is_some() {
+                    self.iterations.push(0);
+                }
+            }
+            OpCode::JmpEnd => {
+                if CHECKED && self.loop_cap.is_some() {
+                    self.iterations.pop();
+                }
+            }
+            _ => {
+                if CHECKED && self.wait > 0 {
+                    if matches!(op, OpCode::Increment | OpCode::Decrement | OpCode::Input) {
+                        self.last_write = Some(self.inner.pointer);
+                    }
+                    let _ = self.visualizer.draw(&self.inner, self.last_write);
+                    thread::sleep(Duration::from_millis(self.wait));
+                }
+            }
+        }
+
+        self.increment_pointer();
+        Status::Running
+    }
+
+    /// Describes the loop ending at the current `]`, and the cells near the
+    /// data pointer.
+    fn loop_cap_error(&self, cap: u64) -> String {
+        let positions = self.ops.positions();
+        let start = self.ops.partner(self.pointer).index();
+        let at = self.inner.pointer;
+        let first = at.saturating_sub(8);
+
+        let mut cells = String::new();
+        let last = (at + 9).min(self.inner.len());
+        for (i, cell) in (first..last).zip(self.inner.values_in(first..last)) {
+            let cell = self.inner.number(cell);
+            if i == at {
+                cells.push_str(&format!(" [{}]", cell));
+            } else {
+                cells.push_str(&format!(" {}", cell));
+            }
+        }
+        format!(
+            "Loop at {}-{} exceeded {} iterations\n  cells from {}:{}",
+            positions[start],
+            positions[self.pointer.index()],
+            cap,
+            first,
+            cells
+        )
+    }
+
+    #[cfg(feature = "debug-invariants")]
+    fn check_invariants(&self) {
+        let fail = |what: String| -> ! {
+            panic!(
+                "Invariant violated: {}\n  ip: {} ({:?})\n  data pointer: {} of {}",
+                what,
+                self.pointer,
+                self.ops.get(self.pointer),
+                self.inner.pointer,
+                self.inner.len(),
+            )
+        };
+
+        if self.pointer > self.ops.end() {
+            fail(format!(
+                "ip is past the end of the program ({})",
+                self.ops.end()
+            ));
+        }
+        if self.inner.pointer >= self.inner.len() {
+            fail("data pointer is outside the tape".into());
+        }
+
+        let matching = match self.ops.get(self.pointer) {
+            Some(OpCode::JmpStart) => Some((']', true)),
+            Some(OpCode::JmpEnd) => Some(('[', false)),
+            _ => None,
+        };
+        if let Some((symbol, forward)) = matching {
+            let other = self.ops.partner(self.pointer);
+            if self.ops.get(other).map(OpCode::symbol) != Some(symbol)
+                || (other > self.pointer) != forward
+                || self.ops.partner(other) != self.pointer
+            {
+                fail(format!("bracket is linked to {}", other));
+            }
+        }
+    }
+
+    #[inline]
+    fn increment_pointer(&mut self) {
+        match self.pointer.next() {
+            Some(x) => {
+                self.pointer = x;
+            }
+            None => panic!("Iter pointer overflow"),
+        }
+    }
+}
+
+/// Flushes what the run left in `writer`, keeping the run's own error if
+/// it had one.
+fn finish<W: Write>(
+    writer: &mut FlushWriter<W>,
+    result: Result<(), RunError>,
+) -> Result<(), RunError> {
+    let flushed = writer.flush();
+    result?;
+    flushed.map_err(|e| match e.kind() {
+        std::io::ErrorKind::BrokenPipe => RunError::OutputClosed,
+        _ => RunError::Other(format!("Write error: {}", e)),
+    })
+}
+
+/// Whether `execute_with` should give up: the deadline has passed or
+/// `INTERRUPTED` is set.
+fn check_stop(deadline: Option<Instant>) -> Result<(), RunError> {
+    if deadline.is_some_and(|d| Instant::now() >= d) {
+        return Err(RunError::Other("Timed out".into()));
+    }
+    if INTERRUPTED.load(Ordering::Relaxed) {
+        return Err(RunError::Other("Interrupted".into()));
+    }
+    Ok(())
+}
+
+/// Configures an `Interpreter` before it runs, for settings that are
+/// awkward to change afterwards, like the tape.
+///
+/// ```
+/// use bf::{parse, InterpreterBuilder};
+///
+/// let program = parse(b">>>+".iter().copied(), &Default::default()).unwrap();
+/// let mut interp = InterpreterBuilder::new(program).tape_size(4).build();
+/// interp.execute_all(&mut std::io::empty(), &mut std::io::sink()).unwrap();
+/// assert_eq!(interp.tape().cells(), [0, 0, 0, 1]);
+/// ```
+#[derive(Debug)]
+pub struct InterpreterBuilder {
+    program: Program,
+    tape_size: usize,
+    bounds: Bounds,
+    storage: Storage,
+    cell_size: CellSize,
+    signed: bool,
+    overflow: Overflow,
+    eof: Eof,
+    opt_level: OptLevel,
+    flush: FlushPolicy,
+    deadline: Option<Instant>,
+    loop_cap: Option<u64>,
+}
+
+impl InterpreterBuilder {
+    pub fn new(program: Program) -> Self {
+        Self {
+            program,
+            tape_size: ARRAY_SIZE,
+            bounds: Bounds::default(),
+            storage: Storage::default(),
+            cell_size: CellSize::default(),
+            signed: false,
+            overflow: Overflow::default(),
+            eof: Eof::default(),
+            opt_level: OptLevel::default(),
+            flush: FlushPolicy::default(),
+            deadline: None,
+            loop_cap: None,
+        }
+    }
+
+    /// Cells on the tape, `ARRAY_SIZE` unless set.
+    pub fn tape_size(mut self, cells: usize) -> Self {
+        self.tape_size = cells;
+        self
+    }
+
+    pub fn bounds(mut self, bounds: Bounds) -> Self {
+        self.bounds = bounds;
+        self
+    }
+
+    pub fn storage(mut self, storage: Storage) -> Self {
+        self.storage = storage;
+        self
+    }
+
+    pub fn cell_size(mut self, size: CellSize) -> Self {
+        self.cell_size = size;
+        self
+    }
+
+    /// Whether cell values count as signed, see `BfArray::with_signed`.
+    pub fn signed(mut self, signed: bool) -> Self {
+        self.signed = signed;
+        self
+    }
+
+    pub fn overflow(mut self, overflow: Overflow) -> Self {
+        self.overflow = overflow;
+        self
+    }
+
+    pub fn eof(mut self, eof: Eof) -> Self {
+        self.eof = eof;
+        self
+    }
+
+    pub fn opt_level(mut self, level: OptLevel) -> Self {
+        self.opt_level = level;
+        self
+    }
+
+    pub fn flush(mut self, policy: FlushPolicy) -> Self {
+        self.flush = policy;
+        self
+    }
+
+    pub fn deadline(mut self, deadline: Option<Instant>) -> Self {
+        self.deadline = deadline;
+        self
+    }
+
+    pub fn loop_cap(mut self, cap: Option<u64>) -> Self {
+        self.loop_cap = cap;
+        self
+    }
+
+    pub fn build(self) -> Interpreter {
+        let mut interp = Interpreter::new(self.program);
+        let tape = match self.storage {
+            Storage::Dense => BfArray::with_len(self.tape_size),
+            Storage::Sparse => BfArray::sparse(self.tape_size),
+            #[cfg(feature = "bignum")]
+            Storage::Bignum => BfArray::with_tape(crate::tape::BigTape::new(self.tape_size)),
+        };
+        interp.inner = tape
+            .with_bounds(self.bounds)
+            .with_cell_size(self.cell_size)
+            .with_signed(self.signed)
+            .with_overflow(self.overflow)
+            .with_eof(self.eof);
+        interp.opt_level = self.opt_level;
+        interp.flush = self.flush;
+        interp.deadline = self.deadline;
+        interp.loop_cap = self.loop_cap;
+        interp
+    }
+}
+
+impl From<Program> for Interpreter {
+    fn from(p: Program) -> Self {
+        Self::new(p)
+    }
+}
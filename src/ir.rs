@@ -0,0 +1,168 @@
+use crate::opt::{self, LoopKind};
+use crate::parser::OpCode;
+use crate::program::{OpIndex, Program};
+
+/// An instruction with runs of identical moves or arithmetic folded into
+/// one, so `+++` adds 3 in a single operation, and common loops replaced
+/// by what they compute.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    MoveForward(usize),
+    MoveBack(usize),
+    /// Wraps like the run it replaces, so 256 `+`s add 0.
+    Increment(u8),
+    Decrement(u8),
+    Output,
+    Input,
+    /// `[-]`, `[+]` or another loop that only steps its cell to zero.
+    SetZero,
+    /// A loop like `[->++>+++<<]` that adds multiples of its cell to cells
+    /// at fixed offsets, then clears it. Indexes `Ir::factors`.
+    Multiply(usize),
+    /// `[>]`, `[<<]` and other loops that only move the pointer by this
+    /// stride until it reaches a zero cell.
+    Scan(isize),
+    /// The index of the matching `JmpEnd`.
+    JmpStart(usize),
+    /// The index of the matching `JmpStart`.
+    JmpEnd(usize),
+}
+
+/// How much `Interpreter::execute_all` optimizes a program before
+/// running it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, clap::ValueEnum)]
+pub enum OptLevel {
+    /// Run each instruction as written, with no `Ir`.
+    #[value(name = "0")]
+    O0,
+    /// Fold runs of moves and arithmetic.
+    #[value(name = "1")]
+    O1,
+    /// Also replace clear, multiply and scan loops.
+    #[default]
+    #[value(name = "2")]
+    O2,
+}
+
+/// A `Program` lowered to `Op`s.
+#[derive(Debug, Clone, Default)]
+pub struct Ir {
+    ops: Vec<Op>,
+    // The first instruction of the program each op was folded from.
+    origins: Vec<OpIndex>,
+    factors: Vec<Vec<(isize, u8)>>,
+    // The lowest and highest offsets each multiply loop's body moves to.
+    reaches: Vec<(isize, isize)>,
+}
+
+impl Ir {
+    /// Lowers `program` with every optimization.
+    pub fn lower(program: &Program) -> Self {
+        Self::lower_at(program, OptLevel::O2)
+    }
+
+    /// Lowers `program` with the optimizations `level` enables. `O0` gets
+    /// the same as `O1`, since the interpreter doesn't lower at `O0`.
+    pub fn lower_at(program: &Program, level: OptLevel) -> Self {
+        let mut ir = Self::default();
+        let mut open = Vec::new();
+        let mut at = OpIndex::default();
+
+        while let Some(&opcode) = program.get(at) {
+            let start = at;
+            if opcode == OpCode::JmpStart && level >= OptLevel::O2 {
+                let end = program.partner(at);
+                let body = &program.ops()[at.index() + 1..end.index()];
+                let op = match opt::classify(body) {
+                    LoopKind::Clear => Some(Op::SetZero),
+                    LoopKind::Scan(stride) => Some(Op::Scan(stride)),
+                    LoopKind::Multiply(factors) => {
+                        ir.factors.push(
+                            factors
+                                .into_iter()
+                                .map(|(offset, factor)| (offset, factor.rem_euclid(256) as u8))
+                                .collect(),
+                        );
+                        ir.reaches.push(reach(body));
+                        Some(Op::Multiply(ir.factors.len() - 1))
+                    }
+                    _ => None,
+                };
+                if let Some(op) = op {
+                    ir.ops.push(op);
+                    ir.origins.push(start);
+                    at = end.next().unwrap();
+                    continue;
+                }
+            }
+            let mut run = 1;
+            at = at.next().unwrap();
+            if matches!(
+                opcode,
+                OpCode::MoveForward | OpCode::MoveBack | OpCode::Increment | OpCode::Decrement
+            ) {
+                while program.get(at) == Some(&opcode) {
+                    run += 1;
+                    at = at.next().unwrap();
+                }
+            }
+
+            let op = match opcode {
+                OpCode::MoveForward => Op::MoveForward(run),
+                OpCode::MoveBack => Op::MoveBack(run),
+                OpCode::Increment => Op::Increment(run as u8),
+                OpCode::Decrement => Op::Decrement(run as u8),
+                OpCode::Output => Op::Output,
+                OpCode::Input => Op::Input,
+                OpCode::JmpStart => {
+                    open.push(ir.ops.len());
+                    // Patched when the `]` is reached.
+                    Op::JmpStart(0)
+                }
+                OpCode::JmpEnd => {
+                    let open = open.pop().expect("parser rejects unmatched ']'");
+                    ir.ops[open] = Op::JmpStart(ir.ops.len());
+                    Op::JmpEnd(open)
+                }
+            };
+            ir.ops.push(op);
+            ir.origins.push(start);
+        }
+        ir
+    }
+
+    pub fn ops(&self) -> &[Op] {
+        &self.ops
+    }
+
+    /// The offsets `Op::Multiply(index)` adds to, each with the multiple of
+    /// the cell it adds, in wrapping arithmetic.
+    pub fn factors(&self, index: usize) -> &[(isize, u8)] {
+        &self.factors[index]
+    }
+
+    /// The lowest and highest offsets from its cell that the loop
+    /// `Op::Multiply(index)` replaces moves the pointer to on the way.
+    pub fn reach(&self, index: usize) -> (isize, isize) {
+        self.reaches[index]
+    }
+
+    /// The first program instruction `ops()[index]` was folded from.
+    pub fn origin(&self, index: usize) -> OpIndex {
+        self.origins[index]
+    }
+}
+
+fn reach(body: &[OpCode]) -> (isize, isize) {
+    let (mut offset, mut low, mut high) = (0isize, 0, 0);
+    for op in body {
+        match op {
+            OpCode::MoveForward => offset += 1,
+            OpCode::MoveBack => offset -= 1,
+            _ => continue,
+        }
+        low = low.min(offset);
+        high = high.max(offset);
+    }
+    (low, high)
+}
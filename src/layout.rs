@@ -0,0 +1,142 @@
+use serde::Serialize;
+
+use std::fmt;
+
+use bf::program::OpIndex;
+use bf::{Interpreter, OpCode};
+
+const READ: u8 = 1;
+const WRITTEN: u8 = 2;
+
+/// Which parts of the tape a run used, printed by `--layout`.
+#[derive(Debug, Clone, Serialize)]
+pub struct Layout {
+    pub program: String,
+    /// Cells whose value the program looked at.
+    pub read: Vec<Region>,
+    /// Cells the program changed or stored input in.
+    pub written: Vec<Region>,
+    /// The longest run of unused cells between used ones.
+    pub largest_gap: Option<Region>,
+    /// Smallest tape the run would have fit in.
+    pub suggested_tape_size: usize,
+}
+
+/// Cells `start` through `end`, inclusive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Region {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Region {
+    fn len(self) -> usize {
+        self.end - self.start + 1
+    }
+}
+
+/// Collects a `Layout` from `Interpreter::execute_with` callbacks.
+#[derive(Debug)]
+pub struct LayoutCollector {
+    access: Vec<u8>,
+    // `BfArray::origin` when last seen, to follow a tape growing left.
+    origin: usize,
+}
+
+impl LayoutCollector {
+    pub fn new(interp: &Interpreter) -> Self {
+        Self {
+            access: vec![0; interp.tape().len()],
+            origin: interp.tape().origin(),
+        }
+    }
+
+    pub fn record(&mut self, interp: &Interpreter, at: OpIndex) {
+        let access = match interp.program()[at] {
+            OpCode::MoveForward | OpCode::MoveBack => return,
+            OpCode::Output | OpCode::JmpStart | OpCode::JmpEnd => READ,
+            OpCode::Increment | OpCode::Decrement => READ | WRITTEN,
+            OpCode::Input => WRITTEN,
+        };
+        let tape = interp.tape();
+        if tape.origin() > self.origin {
+            let grown = tape.origin() - self.origin;
+            self.access.splice(0..0, std::iter::repeat_n(0, grown));
+            self.origin = tape.origin();
+        }
+        let cell = tape.pointer();
+        if cell >= self.access.len() {
+            // The tape grew.
+            self.access.resize(tape.len(), 0);
+        }
+        self.access[cell] |= access;
+    }
+
+    pub fn finish(self, program: String) -> Layout {
+        let regions = |bit: u8| runs(self.access.iter().map(|&a| a & bit != 0));
+        let used = runs(self.access.iter().map(|&a| a != 0));
+
+        let largest_gap = used
+            .windows(2)
+            .map(|pair| Region {
+                start: pair[0].end + 1,
+                end: pair[1].start - 1,
+            })
+            .max_by_key(|gap| gap.len());
+        Layout {
+            program,
+            read: regions(READ),
+            written: regions(WRITTEN),
+            largest_gap,
+            suggested_tape_size: used.last().map_or(0, |r| r.end + 1),
+        }
+    }
+}
+
+fn runs(cells: impl Iterator<Item = bool>) -> Vec<Region> {
+    let mut regions: Vec<Region> = Vec::new();
+    for (i, used) in cells.enumerate() {
+        if !used {
+            continue;
+        }
+        match regions.last_mut() {
+            Some(last) if last.end + 1 == i => last.end = i,
+            _ => regions.push(Region { start: i, end: i }),
+        }
+    }
+    regions
+}
+
+impl fmt::Display for Region {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.start == self.end {
+            write!(f, "{}", self.start)
+        } else {
+            write!(f, "{}..={}", self.start, self.end)
+        }
+    }
+}
+
+fn list(regions: &[Region]) -> String {
+    if regions.is_empty() {
+        return "none".into();
+    }
+    let regions: Vec<String> = regions.iter().map(Region::to_string).collect();
+    regions.join(", ")
+}
+
+impl fmt::Display for Layout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}:", self.program)?;
+        writeln!(f, "  read: {}", list(&self.read))?;
+        writeln!(f, "  written: {}", list(&self.written))?;
+        if let Some(gap) = self.largest_gap {
+            writeln!(f, "  largest gap: {} ({} cells)", gap, gap.len())?;
+        }
+        writeln!(
+            f,
+            "  suggested tape size: {} cells",
+            self.suggested_tape_size
+        )
+    }
+}
@@ -0,0 +1,34 @@
+//! A brainfuck interpreter. `parse` turns source into a `Program`, and an
+//! `Interpreter` runs it on a `BfArray` tape against any `Read` and `Write`:
+//!
+//! ```
+//! let program = bf::parse(b"++++++++[>++++++++<-]>+.".iter().copied(), &Default::default())
+//!     .unwrap();
+//! let mut output = Vec::new();
+//! bf::Interpreter::new(program)
+//!     .execute_all(&mut std::io::empty(), &mut output)
+//!     .unwrap();
+//! assert_eq!(output, b"A");
+//! ```
+
+pub mod compress;
+pub mod directives;
+pub mod flush;
+pub mod interpreter;
+pub mod ir;
+pub mod limits;
+pub mod opt;
+pub mod parser;
+pub mod program;
+pub mod render;
+pub mod samples;
+pub mod schedule;
+pub mod snapshot;
+pub mod state;
+pub mod tape;
+pub mod units;
+
+pub use interpreter::{Interpreter, InterpreterBuilder, RunError, Status};
+pub use parser::{parse, parse_from, OpCode};
+pub use program::{OpIndex, Program};
+pub use tape::{BfArray, Tape};
@@ -0,0 +1,107 @@
+use std::fmt;
+use std::io::{self, Write};
+
+use crate::program::SourcePos;
+use crate::units::{parse_count, parse_size};
+
+/// Deepest loop nesting accepted unless `--max-loop-depth` says otherwise.
+pub const DEFAULT_MAX_DEPTH: usize = 100_000;
+
+/// Caps on what a program may make the front end do, so untrusted source
+/// can't exhaust memory before it runs.
+#[derive(Debug, Clone, Copy, clap::Args)]
+pub struct Limits {
+    /// Reject programs longer than N bytes of source
+    #[arg(long = "max-program-bytes", value_name = "SIZE", value_parser = parse_size)]
+    pub bytes: Option<usize>,
+
+    /// Reject programs with more than N instructions
+    #[arg(long = "max-instructions", value_name = "N", value_parser = parse_count::<usize>)]
+    pub instructions: Option<usize>,
+
+    /// Reject programs with loops nested deeper than N
+    #[arg(
+        long = "max-loop-depth",
+        value_name = "N",
+        value_parser = parse_count::<usize>,
+        default_value_t = DEFAULT_MAX_DEPTH
+    )]
+    pub depth: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            bytes: None,
+            instructions: None,
+            depth: DEFAULT_MAX_DEPTH,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseError {
+    UnmatchedOpen(SourcePos),
+    UnmatchedClose(SourcePos),
+    TooManyBytes(usize),
+    TooManyInstructions(usize),
+    TooDeep(usize, SourcePos),
+    /// Reading the source failed, with the reader's error.
+    Io(String),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::UnmatchedOpen(pos) => write!(f, "Unmatched '[' at {}", pos),
+            ParseError::UnmatchedClose(pos) => write!(f, "Unmatched ']' at {}", pos),
+            ParseError::TooManyBytes(limit) => {
+                write!(f, "Program is longer than {} bytes", limit)
+            }
+            ParseError::TooManyInstructions(limit) => {
+                write!(f, "Program has more than {} instructions", limit)
+            }
+            ParseError::TooDeep(limit, pos) => {
+                write!(f, "Loops nested deeper than {} at {}", limit, pos)
+            }
+            ParseError::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+/// Fails writes once more than `limit` bytes have passed through.
+#[derive(Debug)]
+pub struct OutputLimit<W> {
+    inner: W,
+    remaining: Option<usize>,
+    limit: usize,
+}
+
+impl<W> OutputLimit<W> {
+    pub fn new(inner: W, limit: Option<usize>) -> Self {
+        Self {
+            inner,
+            remaining: limit,
+            limit: limit.unwrap_or(0),
+        }
+    }
+}
+
+impl<W: Write> Write for OutputLimit<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if let Some(ref mut remaining) = self.remaining {
+            if buf.len() > *remaining {
+                return Err(io::Error::other(format!(
+                    "output limit of {} bytes exceeded",
+                    self.limit
+                )));
+            }
+            *remaining -= buf.len();
+        }
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
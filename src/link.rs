@@ -0,0 +1,136 @@
+//! `bf link`: joins modules into one program, each running after the last.
+//! Modules are expanded as by `--preprocess`, and claim the cells they use
+//! with lines like these:
+//!
+//! ```text
+//! @owns 0 9
+//! @owns 12
+//! ```
+//!
+//! Linking fails if two modules claim the same cell. Each module should
+//! leave the pointer where it found it, so the next starts where it
+//! expects to.
+//!
+//! Each module starts with an `@module` line in the linked program, so
+//! `bf run --check-owns` can trap writes a module makes outside its cells.
+//! A program without `@module` lines is checked as a single module.
+
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use bf::interpreter::Module;
+use bf::{OpIndex, Program};
+
+use crate::preprocess;
+
+const MODULE: &[u8] = b"@module\n";
+
+/// Cells a module says it owns.
+#[derive(Debug)]
+struct Claim {
+    cells: Range<usize>,
+    module: PathBuf,
+}
+
+/// The program made of `modules` in order.
+pub fn link(modules: &[PathBuf]) -> Result<Vec<u8>, String> {
+    let mut claims: Vec<Claim> = Vec::new();
+    let mut out = Vec::new();
+    for path in modules {
+        let source =
+            fs::read(path).map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+        let module = preprocess::expand(&source, path)?;
+        let owned = claims_of(&module, path)?;
+        for claim in &owned {
+            let taken = claims
+                .iter()
+                .find(|c| c.cells.start < claim.cells.end && claim.cells.start < c.cells.end);
+            if let Some(taken) = taken {
+                let shared =
+                    taken.cells.start.max(claim.cells.start)..taken.cells.end.min(claim.cells.end);
+                return Err(format!(
+                    "{} and {} both claim {}",
+                    taken.module.display(),
+                    path.display(),
+                    cells(&shared)
+                ));
+            }
+        }
+        claims.extend(owned);
+        out.extend_from_slice(MODULE);
+        out.extend_from_slice(&module);
+        if !out.is_empty() && !out.ends_with(b"\n") {
+            out.push(b'\n');
+        }
+    }
+    Ok(out)
+}
+
+fn claims_of(module: &[u8], path: &Path) -> Result<Vec<Claim>, String> {
+    let mut claims = Vec::new();
+    for line in module.split(|&b| b == b'\n') {
+        if let Some(cells) = owns(line) {
+            claims.push(Claim {
+                cells: cells.map_err(|e| format!("{}: {}", path.display(), e))?,
+                module: path.to_owned(),
+            });
+        }
+    }
+    Ok(claims)
+}
+
+/// The modules of linked `source`, which parsed as `program`, that claim
+/// any cells.
+pub fn modules(source: &[u8], program: &Program) -> Result<Vec<Module>, String> {
+    let positions = program.positions();
+    let code_from = |offset: usize| OpIndex::new(positions.partition_point(|p| p.offset < offset));
+    let mut modules = Vec::new();
+    let mut current = Module {
+        code: OpIndex::default()..OpIndex::default(),
+        cells: Vec::new(),
+        line: 1,
+    };
+    let mut offset = 0;
+    for (i, line) in source.split(|&b| b == b'\n').enumerate() {
+        let start = offset;
+        offset += line.len() + 1;
+        if line.trim_ascii() == b"@module" {
+            current.code.end = code_from(start);
+            let next = Module {
+                code: current.code.end..current.code.end,
+                cells: Vec::new(),
+                line: i + 1,
+            };
+            modules.push(std::mem::replace(&mut current, next));
+        } else if let Some(cells) = owns(line) {
+            current
+                .cells
+                .push(cells.map_err(|e| format!("line {}: {}", i + 1, e))?);
+        }
+    }
+    current.code.end = code_from(offset);
+    modules.push(current);
+    modules.retain(|m| !m.cells.is_empty());
+    Ok(modules)
+}
+
+/// The cells an `@owns` line claims, or `None` for other lines.
+fn owns(line: &[u8]) -> Option<Result<Range<usize>, String>> {
+    let text = String::from_utf8_lossy(line);
+    let rest = text.trim().strip_prefix("@owns")?;
+    let err = || format!("malformed @owns '{}'", text.trim());
+    let bounds: Result<Vec<usize>, _> = rest.split_whitespace().map(|n| n.parse()).collect();
+    Some(match bounds.as_deref() {
+        Ok(&[cell]) => Ok(cell..cell + 1),
+        Ok(&[first, last]) if first <= last => Ok(first..last + 1),
+        _ => Err(err()),
+    })
+}
+
+fn cells(range: &Range<usize>) -> String {
+    match range.len() {
+        1 => format!("cell {}", range.start),
+        _ => format!("cells {} through {}", range.start, range.end - 1),
+    }
+}
@@ -1,333 +1,2074 @@
-use byteorder::WriteBytesExt;
+mod archive;
+mod bench;
+mod canon;
+mod console;
+mod cost;
+#[cfg(unix)]
+mod daemon;
+mod echo;
+mod fetch;
+mod grade;
+mod layout;
+mod link;
+mod output;
+mod package;
+mod pipe;
+mod preprocess;
+mod profile;
+mod prompt;
+mod provenance;
+mod random;
+mod replay;
+mod sched;
+mod sdiff;
+mod selftest;
+mod stats;
+mod stdin;
+mod stdlib;
+mod timing;
+mod trace;
+mod usage;
+mod watch;
+
+use bf::compress::Compression;
+use bf::flush::FlushPolicy;
+use bf::interpreter::INTERRUPTED;
+use bf::ir::OptLevel;
+use bf::limits::{Limits, OutputLimit, ParseError};
+use bf::program::Program;
+use bf::render::{Layout, Renderer, Target, Visualizer};
+use bf::state::StateFormat;
+use bf::tape::{Bounds, CellSize, Eof, Overflow, Storage, ARRAY_SIZE};
+use bf::{directives, opt, parse, parse_from, units, Interpreter, InterpreterBuilder, RunError};
+use clap::parser::ValueSource;
+use clap::{ArgMatches, CommandFactory, FromArgMatches, Parser};
 
 use std::fs::File;
-use std::io::{prelude::*, stdin, stdout};
-use std::path::Path;
-use std::thread;
-use std::time::Duration;
+use std::io::{prelude::*, stdin, stdout, BufReader, IsTerminal};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+
+use cost::{CostCollector, CostModel};
+use echo::EchoTo;
+use grade::Spec;
+use layout::LayoutCollector;
+use output::{Encoded, Encoding, Tee};
+use package::Package;
+use pipe::PipedChild;
+use profile::{Profile, Profiler};
+use prompt::Prompted;
+use provenance::{Provenance, ProvenanceCollector, Stream};
+use random::RandomInput;
+use sched::{Scheduler, Topology};
+use stats::StatsCollector;
+use timing::Timing;
+use trace::{Header, Query, TraceWriter};
+use usage::Usage;
+use watch::Watcher;
+
+/// Exit status after the output is closed, as if killed by `SIGPIPE`.
+const EXIT_OUTPUT_CLOSED: i32 = 141;
+
+/// `--timeout` for runs of programs fetched from URLs, unless one is given.
+const REMOTE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// `--max-output` for runs of programs fetched from URLs, unless one is
+/// given.
+const REMOTE_MAX_OUTPUT: usize = 16 << 20;
+
+/// Runs one program, returning whether it ran to completion.
+fn run_file<R: Read, W: Write>(path: &Path, run: &RunOpt, reader: &mut R, writer: &mut W) -> bool {
+    let started = Instant::now();
+    let source = read_source(path, run);
+    let packaged;
+    let (source, run) = if package::is_package(path) {
+        let package = Package::parse(&source).unwrap_or_else(|e| {
+            eprintln!("{}: {}", path.display(), e);
+            std::process::exit(1);
+        });
+        packaged = exit_on_err(with_package(run, &package.options));
+        (package.source.into_bytes(), &packaged)
+    } else {
+        (source, run)
+    };
+    let (source, embedded_input) = match run.bang_input {
+        true => bf::parser::split_input(&source),
+        false => (&source[..], None),
+    };
+    let program = parse(source.iter().copied(), &run.limits).unwrap_or_else(|e| {
+        eprintln!("{}: {}", path.display(), e);
+        std::process::exit(1);
+    });
+    let parsed = started.elapsed();
+    let mut interp = InterpreterBuilder::new(program)
+        .tape_size(run.tape_size)
+        .bounds(if run.pointer_wrap {
+            Bounds::Wrap
+        } else {
+            run.bounds
+        })
+        .storage(run.tape_storage)
+        .cell_size(run.cell_size)
+        .signed(run.signed)
+        .overflow(run.overflow)
+        .eof(run.eof)
+        .opt_level(run.opt_level)
+        .flush(run.flush)
+        .deadline(run.timeout.map(|timeout| Instant::now() + timeout))
+        .loop_cap(run.loop_cap)
+        .build();
+    if let Some(wait) = run.visualize {
+        match run.visualize_fps {
+            Some(fps) => interp.frame_rate = Some(fps),
+            None => interp.wait = wait,
+        }
+        interp.visualizer = Visualizer {
+            renderer: Renderer {
+                layout: run.visualize_layout,
+                cells: run.visualize_cells.unwrap_or(match run.visualize_layout {
+                    Layout::Row => 7,
+                    Layout::Grid => 64,
+                }),
+                columns: run.visualize_columns,
+                width: run.visualize_width,
+            },
+            target: match run.visualize_to.to_str() {
+                Some("-") => Target::Terminal,
+                _ => Target::File(run.visualize_to.clone()),
+            },
+        };
+    }
+    interp.read_only = run.read_only.clone();
+    if run.directives {
+        let directives = directives::parse(source, interp.program()).unwrap_or_else(|e| {
+            eprintln!("{}: {}", path.display(), e);
+            std::process::exit(1);
+        });
+        interp.assertions = directives.assertions;
+        interp.read_only.extend(directives.read_only);
+    }
+    if run.check_owns {
+        interp.modules = link::modules(source, interp.program()).unwrap_or_else(|e| {
+            eprintln!("{}: {}", path.display(), e);
+            std::process::exit(1);
+        });
+    }
+    let writer = &mut OutputLimit::new(writer, run.max_output);
+    let (mut random, mut embedded);
+    let reader: &mut dyn Read = match (embedded_input, &run.input) {
+        (Some(input), _) => {
+            embedded = input;
+            &mut embedded
+        }
+        (None, InputSource::Random) => {
+            random = RandomInput::new(run.seed);
+            &mut random
+        }
+        (None, _) => reader,
+    };
+    let reader = &mut reader.take(run.input_limit.unwrap_or(u64::MAX));
+    let mut provenance = run.provenance.as_ref().map(|_| ProvenanceCollector::new());
+    let mut counted;
+    let mut reader: &mut dyn Read = match provenance {
+        Some(ref provenance) => {
+            counted = provenance.count(reader);
+            &mut counted
+        }
+        None => reader,
+    };
+    // Echo only what the program reads, not what's counted as unread.
+    let (mut echo_in, mut echo_out);
+    let (mut input, mut output): (&mut dyn Read, &mut dyn Write) = match run.echo_input {
+        Some(to) => {
+            (echo_in, echo_out) = echo::echo(&mut reader, &mut *writer, to);
+            (&mut echo_in, &mut echo_out)
+        }
+        None => (&mut reader, writer),
+    };
+    if run.explain_opt {
+        explain_opt(path, interp.program());
+    }
+
+    let mut trace = run.trace.as_ref().map(|trace| {
+        let header = Header::new(
+            interp.program(),
+            interp.tape().len(),
+            interp.tape().settings(),
+            run.trace_compress,
+        );
+        let out = TraceWriter::create(trace, &header).unwrap_or_else(|e| {
+            eprintln!("Failed to create {}: {}", trace.display(), e);
+            std::process::exit(1);
+        });
+        (trace, out, None)
+    });
+    let mut stats = (run.stats || run.on_exit.is_some()).then(|| StatsCollector::new(&interp));
 
-const ARRAY_SIZE: usize = u16::max_value() as usize;
+    let mut watch = (!run.watch_cell.is_empty()).then(|| Watcher::new(&run.watch_cell, &interp));
+    let mut layout = run.layout.then(|| LayoutCollector::new(&interp));
+    let mut cost = run.cost.map(CostCollector::new);
+
+    let started = Instant::now();
+    let result = if trace.is_none()
+        && stats.is_none()
+        && watch.is_none()
+        && layout.is_none()
+        && cost.is_none()
+        && provenance.is_none()
+    {
+        interp.execute_all(&mut input, &mut output)
+    } else {
+        interp.execute_with(&mut input, &mut output, |interp, at| {
+            if let Some((_, ref mut out, ref mut error @ None)) = trace {
+                *error = out.step(interp, at).err();
+            }
+            if let Some(ref mut stats) = stats {
+                stats.record(interp, at);
+            }
+            if let Some(ref mut watch) = watch {
+                watch.record(interp, at);
+            }
+            if let Some(ref mut layout) = layout {
+                layout.record(interp, at);
+            }
+            if let Some(ref mut cost) = cost {
+                cost.record(interp, at);
+            }
+            if let Some(ref mut provenance) = provenance {
+                provenance.record(interp, at);
+            }
+        })
+    };
+    let executed = started.elapsed();
+    let output_closed = result == Err(RunError::OutputClosed);
+    match result {
+        Err(ref e) if !output_closed => eprintln!("{}", e),
+        _ => {}
+    }
+    let completed = result.is_ok();
+
+    if let Some((trace, out, error)) = trace {
+        if let Some(e) = error.map_or_else(|| out.finish().err(), Some) {
+            eprintln!("Failed to write {}: {}", trace.display(), e);
+        }
+    }
+    if let (Some(provenance), Some(out)) = (provenance, run.provenance.as_ref()) {
+        // Only input nothing else will read can be counted without
+        // blocking or consuming it.
+        let unread = (match run.input {
+            InputSource::Stdin => !run.pipe_back && !stdin().is_terminal(),
+            InputSource::File(_) | InputSource::Bytes(_) => true,
+            InputSource::Random => false,
+        } && completed)
+            .then(|| {
+                let mut rest = reader.take(provenance::UNREAD_INPUT_LIMIT);
+                std::io::copy(&mut rest, &mut std::io::sink()).ok()
+            })
+            .flatten();
+        if let Some(n) = unread.filter(|&n| n > 0) {
+            eprintln!(
+                "warning: {} left {} byte(s) of input unread",
+                path.display(),
+                provenance::unread_bytes(n)
+            );
+        }
+        let provenance = provenance.finish(path.display().to_string(), &interp, unread);
+        if let Err(e) = provenance.save(out) {
+            eprintln!("Failed to write {}: {}", out.display(), e);
+        }
+    }
+    if let Some(stats) = stats {
+        let stats = stats.finish(
+            path.display().to_string(),
+            parsed,
+            executed,
+            result.err().map(|e| e.to_string()),
+        );
+        let json = exit_on_err(serde_json::to_string(&stats));
+        if run.stats {
+            match run.format {
+                StatsFormat::Text => eprint!("{}", stats),
+                StatsFormat::Json => eprintln!("{}", json),
+            }
+        }
+        if let Some(ref cmd) = run.on_exit {
+            on_exit(cmd, &json);
+        }
+    }
+    if let Some(layout) = layout {
+        let layout = layout.finish(path.display().to_string());
+        match run.format {
+            StatsFormat::Text => eprint!("{}", layout),
+            StatsFormat::Json => eprintln!("{}", exit_on_err(serde_json::to_string(&layout))),
+        }
+    }
+    if let Some(cost) = cost {
+        let cost = cost.finish(path.display().to_string(), interp.program());
+        match run.format {
+            StatsFormat::Text => eprint!("{}", cost),
+            StatsFormat::Json => eprintln!("{}", exit_on_err(serde_json::to_string(&cost))),
+        }
+    }
+    if INTERRUPTED.load(Ordering::Relaxed) {
+        std::process::exit(130);
+    }
+    if output_closed {
+        std::process::exit(EXIT_OUTPUT_CLOSED);
+    }
+    if run.print_digest {
+        print_digest(path, &interp);
+    }
+    if let Some(dump) = &run.dump_tape {
+        dump_tape(dump, run.dump_compress, &interp);
+    }
+    if let Some(path) = &run.emit_state_on_exit {
+        let format = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("dot" | "gv") => StateFormat::Dot,
+            _ => StateFormat::Json,
+        };
+        if let Err(e) = std::fs::write(path, interp.export_state(format)) {
+            eprintln!("Failed to write {}: {}", path.display(), e);
+            std::process::exit(1);
+        }
+    }
+    completed
+}
 
-#[derive(Debug)]
-enum OpCode {
-    MoveForward,
-    MoveBack,
-    Increment,
-    Decrement,
-    Output,
-    Input,
-    JmpStart,
-    JmpEnd,
+fn on_exit(cmd: &str, json: &str) {
+    let words = match pipe::words(cmd) {
+        Ok(words) => words,
+        Err(e) => {
+            eprintln!("Failed to run on-exit command: {}", e);
+            return;
+        }
+    };
+    let (program, args) = match words.split_first() {
+        Some(split) => split,
+        None => return,
+    };
+    let result = std::process::Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .spawn()
+        .and_then(|mut child| {
+            let mut stdin = child.stdin.take().unwrap();
+            // The hook may exit without reading its input.
+            let _ = writeln!(stdin, "{}", json);
+            drop(stdin);
+            child.wait()
+        });
+    match result {
+        Ok(status) if !status.success() => eprintln!("On-exit command exited with {}", status),
+        Ok(_) => {}
+        Err(e) => eprintln!("Failed to run on-exit command '{}': {}", cmd, e),
+    }
 }
 
-#[derive(Debug)]
-enum ModifyDirection {
-    Up,
-    Down,
+fn explain_opt(path: &Path, program: &Program) {
+    let positions = program.positions();
+    for (start, kind) in opt::explain(program) {
+        eprintln!("{}:{}: {}", path.display(), positions[start.index()], kind);
+    }
 }
 
-#[derive(Debug)]
-enum JumpFrom {
-    Start,
-    End,
+fn analyze(path: &Path, program: &Program) {
+    let mut report = String::new();
+    for (start, kind) in opt::explain(program) {
+        let end = program.partner(start).index();
+        let body = &program.ops()[start.index() + 1..end];
+        let mut source: String = program.ops()[start.index()..=end]
+            .iter()
+            .map(bf::OpCode::symbol)
+            .collect();
+        if source.len() > 40 {
+            source.truncate(36);
+            source.push_str(" ...");
+        }
+        report.push_str(&format!(
+            "{}:{}: {} ({})\n",
+            path.display(),
+            program.positions()[start.index()],
+            source,
+            kind
+        ));
+        for hint in opt::Balance::of(body).hints() {
+            report.push_str(&format!("  {}\n", hint));
+        }
+    }
+    write_stdout(report.as_bytes());
 }
 
-#[derive(Debug)]
-enum Action {
-    JumpForward,
-    JumpBack,
-    Exit(String),
-    None,
+fn print_digest(path: &Path, interp: &Interpreter) {
+    eprintln!("{}: {:016x}", path.display(), interp.snapshot().digest());
 }
 
-#[derive(Debug)]
-struct BfArray {
-    raw: Vec<u8>,
-    pointer: usize,
+fn dump_tape(path: &Path, compression: Compression, interp: &Interpreter) {
+    let written = File::create(path).and_then(|file| {
+        let snapshot = interp.snapshot();
+        snapshot
+            .write_to(std::io::BufWriter::new(file), compression)?
+            .flush()
+    });
+    if let Err(e) = written {
+        eprintln!("Failed to write {}: {}", path.display(), e);
+        std::process::exit(1);
+    }
 }
 
-impl Default for BfArray {
-    fn default() -> Self {
-        Self::new()
+/// The settings a trace records it was made with, as the `bf run` flags
+/// `--trace` allows. Defaults go unmentioned, as in traces from before
+/// settings were recorded.
+/// `run` with `options` filling in whatever the command line didn't set.
+/// Errors if the command line set something else than the package does,
+/// unless `--allow-mismatch`.
+fn with_package(run: &RunOpt, options: &package::Options) -> Result<RunOpt, String> {
+    let given = |id: &str| run.given.iter().any(|given| given == id);
+    let mut merged = run.clone();
+    let mut mismatches = Vec::new();
+    if let Some(size) = merge(
+        given("cell_size"),
+        options.cell_size()?,
+        &mut merged.cell_size,
+    ) {
+        mismatches.push(format!("--cell-size {}", package::name_of(&size)));
+    }
+    if let Some(storage) = merge(
+        given("tape_storage"),
+        options.tape_storage()?,
+        &mut merged.tape_storage,
+    ) {
+        mismatches.push(format!("--tape-storage {}", package::name_of(&storage)));
+    }
+    if run.pointer_wrap {
+        merged.bounds = Bounds::Wrap;
+        merged.pointer_wrap = false;
+    }
+    let bounds_given = given("bounds") || given("pointer_wrap");
+    if let Some(bounds) = merge(bounds_given, options.bounds()?, &mut merged.bounds) {
+        mismatches.push(format!("--bounds {}", package::name_of(&bounds)));
+    }
+    if let Some(size) = merge(given("tape_size"), options.tape_size, &mut merged.tape_size) {
+        mismatches.push(format!("--tape-size {}", size));
+    }
+    if let Some(overflow) = merge(given("overflow"), options.overflow()?, &mut merged.overflow) {
+        mismatches.push(format!("--overflow {}", package::name_of(&overflow)));
+    }
+    if let Some(eof) = merge(given("eof"), options.eof()?, &mut merged.eof) {
+        mismatches.push(format!("--eof {}", package::name_of(&eof)));
     }
+    if merge(given("signed"), options.signed, &mut merged.signed) == Some(false) {
+        mismatches.push("no --signed".to_owned());
+    }
+    if !mismatches.is_empty() && !run.allow_mismatch {
+        return Err(format!(
+            "packaged to run with {}; pass --allow-mismatch to run it as the command line says",
+            mismatches.join(", ")
+        ));
+    }
+    Ok(merged)
 }
 
-impl BfArray {
-    pub fn new() -> Self {
-        let raw = vec![0; ARRAY_SIZE];
-        Self {
-            raw,
-            pointer: Default::default(),
+/// Sets `current` to `packaged` unless the command line `given` it, then
+/// returning `packaged` if the two differ.
+fn merge<T: PartialEq>(given: bool, packaged: Option<T>, current: &mut T) -> Option<T> {
+    match packaged {
+        Some(packaged) if !given => {
+            *current = packaged;
+            None
         }
+        Some(packaged) if packaged != *current => Some(packaged),
+        _ => None,
     }
+}
 
-    pub fn write_array(&self, to_cell: usize) {
-        let mut file = File::create("visualizer").unwrap();
+/// Runs each of `run.files`, which must be packages, on their test cases,
+/// returning whether every case passed.
+fn check_packages(run: &RunOpt) -> bool {
+    let mut passed = true;
+    for file in &run.files {
+        if !package::is_package(file) {
+            eprintln!(
+                "{}: --check needs a .{} package",
+                file.display(),
+                package::EXTENSION
+            );
+            std::process::exit(1);
+        }
+        let package = Package::parse(&read_source(file, run)).unwrap_or_else(|e| {
+            eprintln!("{}: {}", file.display(), e);
+            std::process::exit(1);
+        });
+        if package.tests.is_empty() {
+            eprintln!("{}: no [[test]] cases", file.display());
+            passed = false;
+        }
+        for (i, case) in package.tests.iter().enumerate() {
+            let name = case
+                .name
+                .clone()
+                .unwrap_or_else(|| format!("test {}", i + 1));
+            let mut output = Vec::new();
+            let completed = run_file(file, run, &mut case.input.as_bytes(), &mut output);
+            if completed && output == case.output.as_bytes() {
+                println!("{}: {}: passed", file.display(), name);
+            } else {
+                passed = false;
+                println!(
+                    "{}: {}: FAILED, expected {:?}, got {:?}",
+                    file.display(),
+                    name,
+                    case.output,
+                    String::from_utf8_lossy(&output)
+                );
+            }
+        }
+    }
+    passed
+}
 
-        for i in 0..=to_cell {
-            write!(file, "{:>4}", i).unwrap();
+/// The source text of `path`, or of its `member` if it's an archive, with
+/// `@include`s expanded if `preprocess`.
+fn read_source(path: &Path, run: &RunOpt) -> Vec<u8> {
+    let bytes = match fetch::url(path) {
+        Some(url) => exit_on_err(fetch::fetch(url, run.fetch_limit)),
+        None => std::fs::read(path).unwrap_or_else(|e| {
+            eprintln!("Failed to open {}: {}", path.display(), e);
+            std::process::exit(1);
+        }),
+    };
+    if let Some(expected) = &run.sha256 {
+        fetch::verify(&bytes, expected).unwrap_or_else(|e| {
+            eprintln!("{}: {}", path.display(), e);
+            std::process::exit(1);
+        });
+    }
+    let source = match (archive::kind(&bytes), run.member.as_deref()) {
+        (None, None) => bytes,
+        (Some(kind), Some(member)) => {
+            archive::read_member(kind, &bytes, member).unwrap_or_else(|e| {
+                eprintln!("{}: {}", path.display(), e);
+                std::process::exit(1);
+            })
+        }
+        (Some(kind), None) => {
+            let programs = archive::members(kind, &bytes).unwrap_or_default();
+            eprintln!(
+                "{} is a {} archive; pick a program in it with --member{}",
+                path.display(),
+                kind,
+                match programs.is_empty() {
+                    true => String::new(),
+                    false => format!(" ({})", programs.join(", ")),
+                }
+            );
+            std::process::exit(1);
+        }
+        (None, Some(_)) => {
+            eprintln!("{}: --member needs a zip or tar archive", path.display());
+            std::process::exit(1);
         }
-        writeln!(file).unwrap();
+    };
+    if !run.preprocess {
+        return source;
+    }
+    exit_on_err(preprocess::expand(&source, path))
+}
 
-        for i in 0..=to_cell {
-            write!(file, "{:>4}", self.raw[i]).unwrap();
+fn load<P: AsRef<Path>>(path: P, limits: &Limits) -> Program {
+    let path = path.as_ref();
+    let file = File::open(path).unwrap_or_else(|e| {
+        eprintln!("Failed to open {}: {}", path.display(), e);
+        std::process::exit(1);
+    });
+    parse_from(file, limits).unwrap_or_else(|e| {
+        match e {
+            ParseError::Io(e) => eprintln!("Failed to read {}: {}", path.display(), e),
+            e => eprintln!("{}: {}", path.display(), e),
         }
-        writeln!(file).unwrap();
+        std::process::exit(1);
+    })
+}
 
-        writeln!(file, "{0:>1$}", "^", (self.pointer * 4) + 4).unwrap();
-    }
+#[derive(Debug, Parser)]
+#[command(
+    name = "bf",
+    version,
+    about = "A brainfuck interpreter",
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
+)]
+struct Opt {
+    #[command(subcommand)]
+    command: Option<Command>,
 
-    pub fn perform_operation<W: Write>(&mut self, opcode: &OpCode, writer: &mut W) -> Action {
-        use JumpFrom::*;
-        use ModifyDirection::*;
-        use OpCode::*;
+    #[command(flatten)]
+    run: RunOpt,
+}
 
-        match opcode {
-            Output => self.output(writer),
-            Input => self.input(),
-            JmpStart => self.jump_from(Start),
-            JmpEnd => self.jump_from(End),
-            Increment => self.modify_value(Up),
-            Decrement => self.modify_value(Down),
-            MoveForward => self.move_pointer(Up),
-            MoveBack => self.move_pointer(Down),
-        }
-    }
+#[derive(Debug, clap::Subcommand)]
+enum Command {
+    /// Execute programs (the default)
+    Run(Box<RunOpt>),
 
-    #[inline]
-    fn value(&self) -> u8 {
-        self.raw[self.pointer]
-    }
+    /// Inspect recorded execution traces
+    #[command(subcommand)]
+    Trace(TraceCommand),
+
+    /// Step through a recorded trace interactively, without the program
+    Replay {
+        /// Trace written by --trace
+        #[arg(long, value_name = "TRACE")]
+        play: PathBuf,
+    },
+
+    /// Show which instruction wrote a byte of output, from --provenance
+    #[command(group(clap::ArgGroup::new("byte_of").required(true).args(["byte", "input_byte"])))]
+    Provenance {
+        /// File written by --provenance
+        #[arg(value_name = "PROVENANCE")]
+        file: PathBuf,
+
+        /// Output byte to explain, counting from 0
+        #[arg(long, value_name = "N")]
+        byte: Option<usize>,
+
+        /// Input byte to explain, counting from 0
+        #[arg(long, value_name = "N")]
+        input_byte: Option<usize>,
+    },
+
+    /// Print what can be inferred about each loop without running it
+    Analyze {
+        #[arg(value_name = "FILE")]
+        file: PathBuf,
+
+        #[command(flatten)]
+        limits: Limits,
+    },
+
+    /// Rewrite programs in a canonical layout, or merge them as a git merge
+    /// driver. Comments are dropped, runs folded and lines broken at loops
+    Canon(CanonOpt),
+
+    /// Compare two versions of a program by their instructions, ignoring
+    /// comments, layout and runs that fold alike. Exits with 1 if they differ
+    Sdiff {
+        #[arg(value_name = "OLD")]
+        old: PathBuf,
+        #[arg(value_name = "NEW")]
+        new: PathBuf,
+
+        #[command(flatten)]
+        limits: Limits,
+    },
+
+    /// Keep programs parsed between runs, running them for `bf client`
+    /// over a Unix socket
+    #[cfg(unix)]
+    Daemon(DaemonOpt),
+
+    /// Run a program on a `bf daemon`, with stdin as its input
+    #[cfg(unix)]
+    Client {
+        #[arg(value_name = "FILE")]
+        file: PathBuf,
+
+        /// Socket the daemon listens on
+        #[arg(long, value_name = "PATH", default_value_os_t = daemon::default_socket())]
+        socket: PathBuf,
+    },
+
+    /// Count how often each instruction and loop runs
+    Profile(ProfileOpt),
+
+    /// Time programs and compare against earlier runs
+    Bench(BenchOpt),
+
+    /// Run submissions against the test cases in a spec and report on each
+    Grade(GradeOpt),
+
+    /// Bundle a program with the options it runs with and test cases into
+    /// a .bfpkg file for `bf run`
+    Pack(PackOpt),
+
+    /// Join modules into one program, each running after the last, failing
+    /// if two claim the same cells with `@owns` lines
+    Link {
+        /// Modules, in the order they run; `@include`s in them are expanded
+        #[arg(required = true, value_name = "MODULE")]
+        modules: Vec<PathBuf>,
+
+        /// Where to write the program [default: stdout]
+        #[arg(short, long, value_name = "PATH")]
+        output: Option<PathBuf>,
+    },
+
+    /// Check the interpreter by running samples under a brainfuck self-interpreter
+    Selftest {
+        /// Also run the torture tests for every supported configuration
+        #[arg(long)]
+        torture: bool,
+    },
+
+    /// Print a shell completion script
+    Completions {
+        #[arg(value_enum)]
+        shell: clap_complete::Shell,
+    },
+
+    /// Print the man page in roff format
+    Man,
+
+    /// Browse the snippets available to `@include <std/NAME>`
+    #[command(subcommand)]
+    Std(StdCommand),
+
+    /// Inspect the local record of commands and flags used, kept only when
+    /// BF_USAGE_FILE names a file
+    #[command(subcommand)]
+    Usage(UsageCommand),
+}
+
+#[derive(Debug, clap::Subcommand)]
+enum UsageCommand {
+    /// Print how often each command and flag has been used
+    Show,
+
+    /// Delete everything recorded so far
+    Clear,
+}
+
+#[derive(Debug, clap::Subcommand)]
+enum StdCommand {
+    /// List the snippets with a summary of each
+    List,
+
+    /// Print a snippet's source
+    Show {
+        #[arg(value_name = "NAME")]
+        name: String,
+    },
+}
+
+#[cfg(unix)]
+#[derive(Debug, clap::Args)]
+struct DaemonOpt {
+    /// Socket to listen on, replacing one a stopped daemon left behind
+    #[arg(long, value_name = "PATH", default_value_os_t = daemon::default_socket())]
+    socket: PathBuf,
+
+    /// Stop each run after DURATION
+    #[arg(long, value_name = "DURATION", default_value = "10s", value_parser = units::parse_duration)]
+    timeout: Duration,
+
+    /// Stop a run once it has written more than SIZE bytes
+    #[arg(long, value_name = "SIZE", value_parser = units::parse_size)]
+    max_output: Option<usize>,
+
+    #[command(flatten)]
+    limits: Limits,
+}
+
+#[derive(Debug, clap::Args)]
+struct CanonOpt {
+    /// Programs to print in canonical form, or with --merge the common
+    /// ancestor, ours and theirs as git passes them: `bf canon --merge %O %A %B`
+    #[arg(required = true, value_name = "FILE")]
+    files: Vec<PathBuf>,
+
+    /// Rewrite the files in place instead of printing them
+    #[arg(short, long, conflicts_with = "merge")]
+    write: bool,
+
+    /// Exit with 1 if any file isn't in canonical form, without changing it
+    #[arg(long, conflicts_with_all = ["write", "merge"])]
+    check: bool,
+
+    /// Act as a git merge driver, leaving the merge in the second file.
+    /// Set it up with `git config merge.bf.driver 'bf canon --merge %O %A %B'`
+    /// and `*.b merge=bf` in .gitattributes
+    #[arg(long)]
+    merge: bool,
+
+    #[command(flatten)]
+    limits: Limits,
+}
+
+#[derive(Debug, clap::Args)]
+struct GradeOpt {
+    /// Test cases, limits and cost model, in TOML
+    #[arg(long, value_name = "FILE")]
+    spec: PathBuf,
+
+    /// Programs to grade, or directories of .b and .bf files
+    #[arg(required = true, value_name = "PATH")]
+    submissions: Vec<PathBuf>,
+
+    /// How the report is printed
+    #[arg(long, value_enum, default_value_t = StatsFormat::Text)]
+    format: StatsFormat,
+}
+
+#[derive(Debug, clap::Args)]
+struct PackOpt {
+    /// Program to package
+    #[arg(value_name = "FILE")]
+    file: PathBuf,
+
+    /// Where to write the package [default: FILE with a .bfpkg extension]
+    #[arg(short, long, value_name = "PATH")]
+    output: Option<PathBuf>,
+
+    /// Expand `@include` lines before packing, as --preprocess would
+    #[arg(long)]
+    preprocess: bool,
+
+    /// Bits in each cell, as for `bf run`
+    #[arg(long, value_enum, value_name = "BITS")]
+    cell_size: Option<CellSize>,
+
+    /// How the tape keeps its cells, as for `bf run`
+    #[arg(long, value_enum, value_name = "STORAGE")]
+    tape_storage: Option<Storage>,
+
+    /// What moving off the tape does, as for `bf run`
+    #[arg(long, value_enum, value_name = "BOUNDS")]
+    bounds: Option<Bounds>,
+
+    /// Number of cells on the tape
+    #[arg(long, value_name = "N", value_parser = parse_tape_size)]
+    tape_size: Option<usize>,
+
+    /// Count cells as signed
+    #[arg(long)]
+    signed: bool,
+
+    /// What counting a cell past either end does, as for `bf run`
+    #[arg(long, value_enum, value_name = "POLICY")]
+    overflow: Option<Overflow>,
+
+    /// What `,` stores at the end of input, as for `bf run`
+    #[arg(long, value_enum, value_name = "EOF")]
+    eof: Option<Eof>,
+
+    /// A test case for `bf run --check`: fed the file IN, the program
+    /// must write the file OUT. Repeatable
+    #[arg(long = "test", num_args = 2, value_names = ["IN", "OUT"])]
+    tests: Vec<PathBuf>,
+}
+
+#[derive(Debug, clap::Args)]
+struct BenchOpt {
+    /// Programs to time
+    #[arg(required = true, value_name = "FILE")]
+    files: Vec<PathBuf>,
+
+    /// Optimization level to time the programs at, as for `bf run`
+    #[arg(short = 'O', value_enum, value_name = "LEVEL", default_value_t = OptLevel::O2)]
+    opt_level: OptLevel,
+
+    /// Timings from an earlier run, as written by --save
+    #[arg(long, value_name = "PATH")]
+    baseline: Option<PathBuf>,
+
+    /// Write this run's timings to PATH
+    #[arg(long, value_name = "PATH")]
+    save: Option<PathBuf>,
+
+    /// Compare against --baseline and fail on regressions
+    #[arg(long, requires = "baseline")]
+    compare: bool,
+
+    /// Percent slowdown counted as a regression
+    #[arg(long, value_name = "PCT", default_value_t = 10.0)]
+    threshold: f64,
+
+    /// Report regressions without failing
+    #[arg(long, requires = "compare")]
+    warn_only: bool,
+
+    /// Timed runs of each program
+    #[arg(long, value_name = "N", default_value_t = 1, value_parser = clap::value_parser!(u64).range(1..))]
+    runs: u64,
+
+    /// Untimed runs of each program before the timed ones
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    warmup: usize,
+
+    /// How timings are printed when not comparing
+    #[arg(long, value_enum, default_value_t = StatsFormat::Text)]
+    format: StatsFormat,
+
+    #[command(flatten)]
+    limits: Limits,
+}
+
+#[derive(Debug, clap::Args)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+struct ProfileOpt {
+    #[command(subcommand)]
+    command: Option<ProfileCommand>,
+
+    /// Program to profile
+    #[arg(required = true, value_name = "FILE")]
+    file: Option<PathBuf>,
+
+    /// Where to write the profile [default: FILE.profile.json]
+    #[arg(short, long, value_name = "PATH")]
+    output: Option<PathBuf>,
+
+    /// Also write loop-nesting folded stacks for flamegraph tools to PATH
+    #[arg(long, value_name = "PATH")]
+    folded: Option<PathBuf>,
+
+    /// Print the N instruction pairs and triples that would save the most dispatches
+    #[arg(long, value_name = "N")]
+    superinstructions: Option<usize>,
+
+    /// Write the superinstruction candidates to PATH as Rust source
+    #[arg(long, value_name = "PATH", requires = "superinstructions")]
+    emit_superinstructions: Option<PathBuf>,
+
+    /// Print the wall time spent in each instruction kind and the hottest loops
+    #[arg(long)]
+    timing: bool,
+
+    /// With --timing, time about one instruction in N
+    #[arg(
+        long,
+        value_name = "N",
+        requires = "timing",
+        value_parser = units::parse_count::<u64>,
+        default_value_t = timing::DEFAULT_SAMPLE_EVERY
+    )]
+    sample_every: u64,
+
+    /// With --timing, how many loops to list
+    #[arg(long, value_name = "K", requires = "timing", default_value_t = 10)]
+    top_loops: usize,
+
+    #[command(flatten)]
+    limits: Limits,
+}
+
+#[derive(Debug, clap::Subcommand)]
+enum ProfileCommand {
+    /// Combine profiles of the same program into one
+    Merge {
+        #[arg(required = true, value_name = "PROFILE")]
+        inputs: Vec<PathBuf>,
+
+        #[arg(short, long, value_name = "PATH")]
+        output: PathBuf,
+    },
+}
+
+#[derive(Debug, clap::Subcommand)]
+enum TraceCommand {
+    /// Print a trace's metadata and verify its checksums
+    Info {
+        #[arg(value_name = "TRACE")]
+        file: PathBuf,
+    },
+
+    /// Find the first step where two traces diverge
+    Diff {
+        #[arg(value_name = "A")]
+        a: PathBuf,
+        #[arg(value_name = "B")]
+        b: PathBuf,
+
+        /// Compare traces recorded with different settings or tape lengths
+        #[arg(long)]
+        allow_mismatch: bool,
+    },
+
+    /// List the numbers of the steps matching a query, counting from 0
+    #[command(group(clap::ArgGroup::new("query").required(true).args(["cell", "offset", "output"])))]
+    Query {
+        #[arg(value_name = "TRACE")]
+        file: PathBuf,
+
+        /// Steps that leave the pointer on cell K
+        #[arg(long, value_name = "K")]
+        cell: Option<u32>,
+
+        /// ...holding this value afterwards
+        #[arg(long, value_name = "V", requires = "cell")]
+        value: Option<u8>,
+
+        /// Steps executing the instruction at this source byte offset
+        #[arg(long, value_name = "N")]
+        offset: Option<usize>,
+
+        /// The step that wrote output byte N, counting from 0
+        #[arg(long, value_name = "N")]
+        output: Option<u64>,
+    },
+}
+
+#[derive(Debug, Clone, clap::Args)]
+struct RunOpt {
+    /// Programs to execute, in order. A FILE starting with https:// or
+    /// http:// is fetched, and runs with a --timeout and --max-output
+    /// unless given ones
+    #[arg(required_unless_present_any = ["pair", "topology"], value_name = "FILE")]
+    files: Vec<PathBuf>,
+
+    /// Run the program named NAME inside each FILE, which must be a zip or
+    /// tar archive
+    #[arg(long, value_name = "NAME", conflicts_with_all = ["pair", "topology"])]
+    member: Option<String>,
+
+    /// Refuse to run FILE unless its SHA-256 digest is HEX
+    #[arg(long, value_name = "HEX", conflicts_with_all = ["pair", "topology"])]
+    sha256: Option<String>,
 
-    #[inline]
-    fn set_value(&mut self, val: u8) {
-        self.raw[self.pointer] = val;
+    /// Refuse programs fetched from URLs that are larger than SIZE
+    #[arg(long, value_name = "SIZE", default_value = "1MiB", value_parser = units::parse_size)]
+    fetch_limit: usize,
+
+    /// Optimization level: 0 runs instructions as written, 1 folds runs of
+    /// moves and arithmetic, 2 also replaces clear, multiply and scan loops
+    #[arg(
+        short = 'O',
+        value_enum,
+        value_name = "LEVEL",
+        default_value_t = OptLevel::O2,
+        conflicts_with_all = ["pair", "topology"]
+    )]
+    opt_level: OptLevel,
+
+    /// Number of cells on the tape
+    #[arg(
+        long,
+        value_name = "N",
+        value_parser = parse_tape_size,
+        default_value_t = ARRAY_SIZE,
+        conflicts_with_all = ["pair", "topology"]
+    )]
+    tape_size: usize,
+
+    /// What moving the pointer off the tape does: `error` stops the
+    /// program, `wrap` comes back on at the other end, `grow` adds cells
+    /// past the end, `unbounded` adds them at either end
+    #[arg(
+        long,
+        value_enum,
+        value_name = "POLICY",
+        default_value_t = Bounds::Error,
+        conflicts_with_all = ["pair", "topology"]
+    )]
+    bounds: Bounds,
+
+    /// Wrap the pointer around the ends of the tape, as some classic
+    /// interpreters do; short for --bounds wrap
+    #[arg(long, conflicts_with_all = ["bounds", "pair", "topology"])]
+    pointer_wrap: bool,
+
+    /// How the tape keeps its cells: `dense` allocates them all up front,
+    /// `sparse` only the pages written to, for programs that roam a huge
+    /// --tape-size. With the bignum feature, `bignum` cells count up
+    /// without ever wrapping
+    #[arg(
+        long,
+        value_enum,
+        value_name = "STORAGE",
+        default_value_t = Storage::Dense,
+        conflicts_with_all = ["pair", "topology"]
+    )]
+    tape_storage: Storage,
+
+    /// Bits in each cell, for programs written for 16- or 32-bit cells.
+    /// `.` writes the low byte
+    #[arg(
+        long,
+        value_enum,
+        value_name = "BITS",
+        default_value_t = CellSize::U8,
+        conflicts_with_all = ["pair", "topology"]
+    )]
+    cell_size: CellSize,
+
+    /// Count cells as signed, so they show and compare as negative past
+    /// half their range
+    #[arg(long, conflicts_with_all = ["pair", "topology"])]
+    signed: bool,
+
+    /// What `+` on a full cell and `-` on an empty one do: `wrap` around,
+    /// `saturate` and stay put, or stop with an `error`
+    #[arg(
+        long,
+        value_enum,
+        value_name = "POLICY",
+        default_value_t = Overflow::Wrap,
+        conflicts_with_all = ["pair", "topology"]
+    )]
+    overflow: Overflow,
+
+    /// What `,` stores once input runs out: `zero`, `minus-one` (the
+    /// cell's largest value), or nothing, leaving the cell `unchanged`
+    #[arg(
+        long,
+        value_enum,
+        value_name = "EOF",
+        default_value_t = Eof::Zero,
+        conflicts_with_all = ["pair", "topology"]
+    )]
+    eof: Eof,
+
+    /// Draw the tape after each instruction, pausing MS milliseconds
+    /// [default: 100]
+    #[arg(
+        long,
+        value_name = "MS",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "100",
+        conflicts_with_all = ["pair", "topology", "tape_storage"]
+    )]
+    visualize: Option<u64>,
+
+    /// Draw --visualize frames FPS times a second while the program runs
+    /// at full speed, instead of pausing after each instruction
+    #[arg(long, value_name = "FPS", value_parser = clap::value_parser!(u32).range(1..), requires = "visualize")]
+    visualize_fps: Option<u32>,
+
+    /// How --visualize lays out the tape: `row` is one row of the first
+    /// cells, `grid` wraps rows paged to follow the pointer and marks the
+    /// cell written last with `*`
+    #[arg(long, value_enum, value_name = "LAYOUT", default_value_t = Layout::Row, requires = "visualize")]
+    visualize_layout: Layout,
+
+    /// Cells --visualize shows at once [default: 7 for row, 64 for grid]
+    #[arg(long, value_name = "N", value_parser = parse_tape_size, requires = "visualize")]
+    visualize_cells: Option<usize>,
+
+    /// Cells per row of --visualize-layout grid
+    #[arg(long, value_name = "N", default_value_t = 16, value_parser = parse_tape_size, requires = "visualize")]
+    visualize_columns: usize,
+
+    /// Characters per cell drawn by --visualize
+    #[arg(long, value_name = "N", default_value_t = 4, requires = "visualize")]
+    visualize_width: usize,
+
+    /// File --visualize overwrites with each frame, or - to redraw on the
+    /// terminal through stderr
+    #[arg(
+        long,
+        value_name = "PATH",
+        default_value = "visualizer",
+        requires = "visualize"
+    )]
+    visualize_to: PathBuf,
+
+    /// Print a digest of the final tape and pointers after each program
+    #[arg(long, conflicts_with_all = ["pair", "topology"])]
+    print_digest: bool,
+
+    /// Write the final tape and pointers to PATH as a snapshot
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["pair", "topology"])]
+    dump_tape: Option<PathBuf>,
+
+    /// Compress --dump-tape with zstd, or with ALGO [possible values:
+    /// zstd, gzip]
+    #[arg(
+        long,
+        value_name = "ALGO",
+        value_parser = parse_compression,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "zstd",
+        default_value = "none",
+        hide_possible_values = true,
+        requires = "dump_tape"
+    )]
+    dump_compress: Compression,
+
+    /// Write the tape, pointers and open loops to PATH as the program
+    /// stops, as Graphviz DOT if PATH ends in .dot or .gv and JSON
+    /// otherwise
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["pair", "topology"])]
+    emit_state_on_exit: Option<PathBuf>,
+
+    /// Record an execution trace of the program to PATH
+    #[arg(
+        long,
+        value_name = "PATH",
+        conflicts_with_all = [
+            "bounds",
+            "pointer_wrap",
+            "tape_storage",
+            "cell_size",
+            "pair",
+            "topology"
+        ]
+    )]
+    trace: Option<PathBuf>,
+
+    /// Record which `.` wrote each output byte and which `,` read each
+    /// input byte to PATH, for `bf provenance`. Warns about input left unread,
+    /// counting up to 1 MiB of it
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["pair", "topology"])]
+    provenance: Option<PathBuf>,
+
+    /// Compress the trace with zstd, or with ALGO [possible values: zstd,
+    /// gzip]
+    #[arg(
+        long,
+        value_name = "ALGO",
+        value_parser = parse_compression,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "zstd",
+        default_value = "none",
+        hide_possible_values = true,
+        requires = "trace"
+    )]
+    trace_compress: Compression,
+
+    /// Spawn CMD and send the program's output to its stdin. CMD is split
+    /// into words like a shell would, so `'a b'`, `"a b"` and `a\ b` are each
+    /// one argument, but nothing is expanded
+    #[arg(long, value_name = "CMD")]
+    pipe_to: Option<String>,
+
+    /// Write the program's output to PATH instead of stdout
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["pipe_to", "pair", "topology"])]
+    output: Option<PathBuf>,
+
+    /// With --output, also write the output to stdout
+    #[arg(long, requires = "output")]
+    also_stdout: bool,
+
+    /// When output is flushed: after each `byte`, each `line`, only on
+    /// `exit`, or whenever a SIZE such as 4KiB is waiting. Pending output
+    /// is also flushed before each `,`
+    #[arg(
+        long,
+        value_name = "POLICY",
+        default_value_t = FlushPolicy::Byte,
+        conflicts_with_all = ["pair", "topology"]
+    )]
+    flush: FlushPolicy,
+
+    /// How output is written to the --output file
+    #[arg(long, value_enum, value_name = "ENCODING", default_value_t = Encoding::Raw)]
+    output_encoding: Encoding,
+
+    /// How output is written to stdout
+    #[arg(
+        long,
+        value_enum,
+        value_name = "ENCODING",
+        default_value_t = Encoding::Raw,
+        conflicts_with_all = ["pair", "topology"]
+    )]
+    stdout_encoding: Encoding,
+
+    /// Feed the stdout of the --pipe-to command back into `,`
+    #[arg(long, requires = "pipe_to", conflicts_with = "input")]
+    pipe_back: bool,
+
+    /// Where `,` reads from: `-` for stdin, `random` for bytes generated from
+    /// --seed, or a file, which programs run in turn read on through
+    #[arg(
+        long,
+        value_name = "SOURCE",
+        value_parser = parse_input,
+        default_value = "-",
+        conflicts_with_all = ["pair", "topology"]
+    )]
+    input: InputSource,
+
+    /// Give `,` at most N bytes of input, then end of file
+    #[arg(
+        long,
+        value_name = "N",
+        value_parser = units::parse_count::<u64>,
+        conflicts_with_all = ["pair", "topology"]
+    )]
+    input_limit: Option<u64>,
+
+    /// Give `,` TEXT as its input, read on through by programs run in turn
+    #[arg(
+        long,
+        value_name = "TEXT",
+        conflicts_with_all = ["input", "pipe_back", "pair", "topology"]
+    )]
+    input_str: Option<String>,
+
+    /// Give `,` whatever follows the first `!` in each program, by the
+    /// `program!input` convention, as all the input it gets: stdin isn't
+    /// read after it, and other input options are refused. Programs
+    /// without a `!` read stdin as usual
+    #[arg(long, conflicts_with_all = ["input", "input_str", "pipe_back", "pair", "topology"])]
+    bang_input: bool,
+
+    /// When stdin is a terminal, show PROMPT on stderr each time `,` runs
+    /// out of input, and read a whole line for the `,`s that follow
+    #[arg(
+        long,
+        value_name = "PROMPT",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "> ",
+        conflicts_with_all = ["pipe_back", "pair", "topology"]
+    )]
+    prompt: Option<String>,
+
+    /// Echo the bytes `,` reads to stdout, among the program's output, or
+    /// to stderr
+    #[arg(
+        long,
+        value_enum,
+        value_name = "TO",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "stdout",
+        conflicts_with_all = ["pair", "topology"]
+    )]
+    echo_input: Option<EchoTo>,
+
+    /// Seed for `--input random`; each program's input starts from it afresh
+    #[arg(long, value_name = "S", default_value_t = 0)]
+    seed: u64,
+
+    /// Run two programs side by side, each one's output feeding the other's
+    /// input. They take turns on one thread, --fuel instructions at a time
+    #[arg(
+        long,
+        num_args = 2,
+        value_names = ["A", "B"],
+        conflicts_with_all = ["files", "pipe_to", "topology"]
+    )]
+    pair: Option<Vec<PathBuf>>,
+
+    /// Run the programs and connections described by a topology file,
+    /// taking turns on one thread as with --pair
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["files", "pipe_to"])]
+    topology: Option<PathBuf>,
+
+    /// Instructions each program runs per scheduler slice
+    #[arg(
+        long,
+        value_name = "N",
+        value_parser = units::parse_count::<usize>,
+        default_value_t = sched::DEFAULT_FUEL
+    )]
+    fuel: usize,
+
+    /// Report a livelock after N instructions without I/O while a program is blocked
+    #[arg(long, value_name = "N", value_parser = units::parse_count::<u64>)]
+    livelock_steps: Option<u64>,
+
+    /// Print per-program scheduling statistics at exit
+    #[arg(long)]
+    sched_stats: bool,
+
+    /// Report which optimization pattern each loop matches, or why none does
+    #[arg(long, conflicts_with_all = ["pair", "topology"])]
+    explain_opt: bool,
+
+    /// Stop each program after it has run for DURATION, e.g. 2m30s
+    #[arg(long, value_name = "DURATION", value_parser = units::parse_duration)]
+    timeout: Option<Duration>,
+
+    /// Stop a program once it has written more than SIZE bytes, e.g. 64MiB
+    #[arg(long, value_name = "SIZE", value_parser = units::parse_size)]
+    max_output: Option<usize>,
+
+    /// Print a line whenever a cell in CELLS changes, e.g. 5 or 10..20
+    #[arg(
+        long,
+        value_name = "CELLS",
+        value_parser = units::parse_cells,
+        conflicts_with_all = ["pair", "topology"]
+    )]
+    watch_cell: Vec<std::ops::Range<usize>>,
+
+    /// Expand `@include <std/NAME>` and `@include "PATH"` lines before parsing
+    #[arg(long, conflicts_with_all = ["pair", "topology"])]
+    preprocess: bool,
+
+    /// Run each FILE, a .bfpkg package, on its test cases instead of
+    /// stdin, and fail unless each writes what its case expects
+    #[arg(long, conflicts_with_all = ["pair", "topology"])]
+    check: bool,
+
+    /// Ids of the flags given on the command line, which packages' options
+    /// don't override.
+    #[arg(skip)]
+    given: Vec<String>,
+
+    /// Run packages even where the command line contradicts the settings
+    /// they were packaged with
+    #[arg(long)]
+    allow_mismatch: bool,
+
+    /// Honor `#assert` and `#readonly` directives in the program's comments
+    #[arg(long, visible_alias = "assertions", conflicts_with_all = ["pair", "topology"])]
+    directives: bool,
+
+    /// Stop with an error when the program writes to CELLS, e.g. 100..120
+    #[arg(
+        long,
+        value_name = "CELLS",
+        value_parser = units::parse_cells,
+        conflicts_with_all = ["pair", "topology"]
+    )]
+    read_only: Vec<std::ops::Range<usize>>,
+
+    /// Stop with an error when a module of a `bf link`ed program writes to
+    /// a cell it has no `@owns` line for
+    #[arg(long, conflicts_with_all = ["pair", "topology"])]
+    check_owns: bool,
+
+    /// Stop when any one loop runs more than N iterations in a row
+    #[arg(long, value_name = "N", value_parser = units::parse_count::<u64>)]
+    loop_cap: Option<u64>,
+
+    /// Run CMD after each program with its --stats JSON on stdin. CMD is
+    /// split into words as for --pipe-to
+    #[arg(long, value_name = "CMD", conflicts_with_all = ["pair", "topology"])]
+    on_exit: Option<String>,
+
+    /// Print resource usage after each program; with --pair or --topology,
+    /// what each did under the scheduler
+    #[arg(long)]
+    stats: bool,
+
+    /// Report which tape cells were read and written after each program
+    #[arg(long, conflicts_with_all = ["pair", "topology"])]
+    layout: bool,
+
+    /// Report each program's cost under MODEL, e.g. op=1,io=10, with a
+    /// breakdown by loop [default: every instruction costs 1]
+    #[arg(
+        long,
+        value_name = "MODEL",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "",
+        value_parser = cost::parse_model,
+        conflicts_with_all = ["pair", "topology"]
+    )]
+    cost: Option<CostModel>,
+
+    /// How --stats, --layout and --cost reports are printed
+    #[arg(long, value_enum, default_value_t = StatsFormat::Text)]
+    format: StatsFormat,
+
+    #[command(flatten)]
+    limits: Limits,
+}
+
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+enum StatsFormat {
+    Text,
+    Json,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum InputSource {
+    Stdin,
+    Random,
+    File(PathBuf),
+    /// From --input-str.
+    Bytes(Vec<u8>),
+}
+
+fn parse_tape_size(s: &str) -> Result<usize, String> {
+    match units::parse_count(s)? {
+        0 => Err("the tape needs at least one cell".into()),
+        n => Ok(n),
     }
+}
+
+/// A `Compression` this build can write, so runs that would fail to once
+/// they're done don't start.
+fn parse_compression(s: &str) -> Result<Compression, String> {
+    let compression = <Compression as clap::ValueEnum>::from_str(s, false)
+        .map_err(|_| "expected none, gzip or zstd".to_owned())?;
+    compression.supported().map_err(|e| e.to_string())
+}
 
-    fn output<W: Write>(&self, writer: &mut W) -> Action {
-        writer.write_u8(self.value()).expect("Write error");
-        writer.flush().unwrap();
-        Action::None
+fn parse_input(s: &str) -> Result<InputSource, String> {
+    match s {
+        "-" => Ok(InputSource::Stdin),
+        "random" => Ok(InputSource::Random),
+        "" => Err("expected -, random or a file".into()),
+        _ => Ok(InputSource::File(s.into())),
     }
+}
+
+/// The ids of the arguments `matches` got from the command line itself.
+fn given(matches: &ArgMatches) -> Vec<String> {
+    matches
+        .ids()
+        .filter(|id| matches.value_source(id.as_str()) == Some(ValueSource::CommandLine))
+        .map(|id| id.to_string())
+        .collect()
+}
 
-    fn input(&mut self) -> Action {
-        let mut input = 0;
-        for byte in stdin().lock().bytes() {
-            if byte.is_ok() {
-                input = byte.unwrap();
-                break;
+fn main() {
+    let cmd = Opt::command();
+    let matches = cmd.clone().get_matches();
+    usage::record(&cmd, &matches);
+    let opt = Opt::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    match opt.command {
+        Some(Command::Run(mut run)) => {
+            run.given = given(matches.subcommand_matches("run").unwrap());
+            run_main(*run)
+        }
+        Some(Command::Trace(cmd)) => trace_main(cmd),
+        Some(Command::Replay { play }) => exit_on_err(replay::play(&play)),
+        Some(Command::Provenance {
+            file,
+            byte,
+            input_byte,
+        }) => {
+            let provenance = exit_on_err(Provenance::load(&file));
+            let report = match (byte, input_byte) {
+                (Some(byte), _) => provenance.explain(Stream::Output, byte),
+                (_, Some(byte)) => provenance.explain(Stream::Input, byte),
+                _ => unreachable!("clap requires a byte"),
+            };
+            write_stdout(exit_on_err(report).as_bytes());
+        }
+        Some(Command::Analyze { file, limits }) => analyze(&file, &load(&file, &limits)),
+        Some(Command::Sdiff { old, new, limits }) => {
+            let (report, differ) = sdiff::sdiff(
+                &old.display().to_string(),
+                &load(&old, &limits),
+                &new.display().to_string(),
+                &load(&new, &limits),
+            );
+            write_stdout(report.as_bytes());
+            if differ {
+                std::process::exit(1);
             }
         }
-
-        self.set_value(input);
-        Action::None
+        Some(Command::Canon(canon)) => canon_main(canon),
+        #[cfg(unix)]
+        Some(Command::Daemon(opt)) => exit_on_err(daemon::serve(
+            &opt.socket,
+            daemon::Sandbox {
+                limits: opt.limits,
+                timeout: opt.timeout,
+                max_output: opt.max_output,
+            },
+        )),
+        #[cfg(unix)]
+        Some(Command::Client { file, socket }) => client_main(&file, &socket),
+        Some(Command::Profile(profile)) => profile_main(profile),
+        Some(Command::Bench(bench)) => bench_main(bench),
+        Some(Command::Grade(grade)) => grade_main(grade),
+        Some(Command::Pack(pack)) => pack_main(pack),
+        Some(Command::Link { modules, output }) => {
+            let program = exit_on_err(link::link(&modules));
+            match output {
+                Some(path) => exit_on_err(
+                    std::fs::write(&path, program)
+                        .map_err(|e| format!("Failed to write {}: {}", path.display(), e)),
+                ),
+                None => write_stdout(&program),
+            }
+        }
+        Some(Command::Completions { shell }) => {
+            let mut script = Vec::new();
+            clap_complete::generate(shell, &mut Opt::command(), "bf", &mut script);
+            write_stdout(&script);
+        }
+        Some(Command::Man) => {
+            let mut page = Vec::new();
+            exit_on_err(clap_mangen::Man::new(Opt::command()).render(&mut page));
+            write_stdout(&page);
+        }
+        Some(Command::Std(StdCommand::List)) => write_stdout(stdlib::list().as_bytes()),
+        Some(Command::Std(StdCommand::Show { name })) => {
+            let name = name.strip_prefix("std/").unwrap_or(&name);
+            match stdlib::get(name) {
+                Some(source) => write_stdout(source.as_bytes()),
+                None => {
+                    eprintln!("No snippet std/{} (see `bf std list`)", name);
+                    std::process::exit(1);
+                }
+            }
+        }
+        Some(Command::Usage(cmd)) => usage_main(cmd),
+        Some(Command::Selftest { torture }) => {
+            let (mut report, mut passed) = selftest::selftest();
+            if torture {
+                let (more, ok) = selftest::torture();
+                report.push_str(&more);
+                passed &= ok;
+            }
+            print!("{}", report);
+            if !passed {
+                std::process::exit(1);
+            }
+        }
+        None => {
+            let mut run = opt.run;
+            run.given = given(&matches);
+            run_main(run)
+        }
     }
+}
 
-    fn jump_from(&self, from: JumpFrom) -> Action {
-        let nonzero = self.value() != 0;
-        match (from, nonzero) {
-            (JumpFrom::Start, false) => Action::JumpForward,
-            (JumpFrom::End, true) => Action::JumpBack,
-            _ => Action::None,
-        }
+#[cfg(unix)]
+fn client_main(file: &Path, socket: &Path) {
+    let program = std::fs::canonicalize(file).unwrap_or_else(|e| {
+        eprintln!("Failed to open {}: {}", file.display(), e);
+        std::process::exit(1);
+    });
+    let mut input = Vec::new();
+    if !stdin().is_terminal() {
+        exit_on_err(stdin().read_to_end(&mut input));
     }
+    let response = exit_on_err(daemon::request(socket, &daemon::Request { program, input }));
+    write_stdout(&response.output);
+    if let Some(e) = response.error {
+        eprintln!("{}: {}", file.display(), e);
+        std::process::exit(1);
+    }
+}
 
-    fn modify_value(&mut self, direction: ModifyDirection) -> Action {
-        let mod_func = match direction {
-            ModifyDirection::Up => u8::wrapping_add,
-            ModifyDirection::Down => u8::wrapping_sub,
-        };
+fn exit_on_err<T, E: std::fmt::Display>(result: Result<T, E>) -> T {
+    result.unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    })
+}
 
-        self.set_value(mod_func(self.value(), 1));
-        Action::None
+/// Writes `bytes` to stdout, exiting quietly if its reader has gone away.
+fn write_stdout(bytes: &[u8]) {
+    let mut out = stdout().lock();
+    match out.write_all(bytes).and_then(|_| out.flush()) {
+        Ok(()) => {}
+        Err(ref e) if e.kind() == std::io::ErrorKind::BrokenPipe => {
+            std::process::exit(EXIT_OUTPUT_CLOSED)
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
     }
+}
 
-    fn move_pointer(&mut self, direction: ModifyDirection) -> Action {
-        let mod_func = match direction {
-            ModifyDirection::Up => usize::checked_add,
-            ModifyDirection::Down => usize::checked_sub,
+fn canon_main(opt: CanonOpt) {
+    if opt.merge {
+        let (base, ours, theirs) = match &opt.files[..] {
+            [base, ours, theirs] => (base, ours, theirs),
+            _ => {
+                eprintln!("--merge takes the common ancestor, ours and theirs");
+                std::process::exit(2);
+            }
         };
+        std::process::exit(exit_on_err(canon::merge(base, ours, theirs, &opt.limits)));
+    }
 
-        match mod_func(self.pointer, 1) {
-            None => Action::Exit("Pointer access violation".into()),
-            Some(x) => {
-                self.pointer = x;
-                Action::None
+    let mut unformatted = false;
+    for file in &opt.files {
+        if opt.write {
+            exit_on_err(canon::rewrite(file, &opt.limits));
+            continue;
+        }
+        let program = load(file, &opt.limits);
+        let canonical = canon::canonical(&program);
+        if opt.check {
+            let source = exit_on_err(std::fs::read(file));
+            if source != canonical.as_bytes() {
+                println!("{}", file.display());
+                unformatted = true;
             }
+        } else {
+            write_stdout(canonical.as_bytes());
         }
     }
+    if unformatted {
+        std::process::exit(1);
+    }
+}
+
+fn grade_main(opt: GradeOpt) {
+    let spec = exit_on_err(Spec::load(&opt.spec));
+    let files = exit_on_err(grade::submissions(&opt.submissions));
+    let report = exit_on_err(grade::grade(&spec, &files));
+    match opt.format {
+        StatsFormat::Text => print!("{}", report),
+        StatsFormat::Json => println!("{}", exit_on_err(serde_json::to_string(&report))),
+    }
+    if !report.all_passed() {
+        std::process::exit(1);
+    }
 }
 
-#[derive(Debug)]
-struct Interpreter {
-    inner: BfArray,
-    ops: Vec<OpCode>,
-    jump_stack: Vec<usize>,
-    pointer: usize,
+fn pack_main(opt: PackOpt) {
+    let read = |path: &Path| {
+        std::fs::read(path).unwrap_or_else(|e| {
+            eprintln!("Failed to open {}: {}", path.display(), e);
+            std::process::exit(1);
+        })
+    };
+    let text = |path: &Path, bytes: Vec<u8>| {
+        String::from_utf8(bytes).unwrap_or_else(|_| {
+            eprintln!("{}: packages only hold UTF-8 text", path.display());
+            std::process::exit(1);
+        })
+    };
+    let mut source = read(&opt.file);
+    if opt.preprocess {
+        source = exit_on_err(preprocess::expand(&source, &opt.file));
+    }
+    if let Err(e) = parse(source.iter().copied(), &Limits::default()) {
+        eprintln!("{}: {}", opt.file.display(), e);
+        std::process::exit(1);
+    }
+    let tests = opt
+        .tests
+        .chunks(2)
+        .map(|case| package::TestCase {
+            name: None,
+            input: text(&case[0], read(&case[0])),
+            output: text(&case[1], read(&case[1])),
+        })
+        .collect();
+    let package = Package {
+        source: text(&opt.file, source),
+        options: package::Options {
+            cell_size: opt.cell_size.as_ref().map(package::name_of),
+            tape_storage: opt.tape_storage.as_ref().map(package::name_of),
+            bounds: opt.bounds.as_ref().map(package::name_of),
+            tape_size: opt.tape_size,
+            signed: opt.signed.then_some(true),
+            overflow: opt.overflow.as_ref().map(package::name_of),
+            eof: opt.eof.as_ref().map(package::name_of),
+        },
+        tests,
+    };
+    let output = match opt.output {
+        Some(output) => output,
+        None => opt.file.with_extension(package::EXTENSION),
+    };
+    let toml = exit_on_err(package.to_toml());
+    if let Err(e) = std::fs::write(&output, toml) {
+        eprintln!("Failed to write {}: {}", output.display(), e);
+        std::process::exit(1);
+    }
 }
 
-impl Interpreter {
-    pub fn new(ops: Vec<OpCode>) -> Self {
-        Self {
-            inner: Default::default(),
-            ops,
-            jump_stack: Default::default(),
-            pointer: Default::default(),
+fn usage_main(cmd: UsageCommand) {
+    let path = usage::file().unwrap_or_else(|| {
+        eprintln!(
+            "Usage statistics are off; set {} to a file to keep them in",
+            usage::USAGE_VAR
+        );
+        std::process::exit(1);
+    });
+    match cmd {
+        UsageCommand::Show => {
+            let usage = exit_on_err(Usage::load(&path));
+            write_stdout(format!("{}:\n{}", path.display(), usage).as_bytes());
         }
+        UsageCommand::Clear => exit_on_err(usage::clear(&path)),
     }
+}
 
-    pub fn execute_all<W: Write>(&mut self, writer: &mut W) {
-        self.pointer = 0;
-        let wait = std::env::var("BF_VISUALIZER_TIME")
-            .map(|s| s.parse().unwrap_or(0))
-            .unwrap_or(0);
+fn trace_main(cmd: TraceCommand) {
+    let report = match cmd {
+        TraceCommand::Info { file } => trace::info(&file),
+        TraceCommand::Diff {
+            a,
+            b,
+            allow_mismatch,
+        } => trace::diff(&a, &b, allow_mismatch),
+        TraceCommand::Query {
+            file,
+            cell,
+            value,
+            offset,
+            output,
+        } => {
+            let query = match (cell, offset, output) {
+                (Some(cell), _, _) => Query::Cell { cell, value },
+                (_, Some(offset), _) => Query::Offset(offset),
+                (_, _, Some(output)) => Query::Output(output),
+                _ => unreachable!("clap requires a query"),
+            };
+            let steps = exit_on_err(trace::query(&file, query));
+            let lines: String = steps.iter().map(|step| format!("{}\n", step)).collect();
+            write_stdout(lines.as_bytes());
+            return;
+        }
+    };
+    print!("{}", exit_on_err(report));
+}
 
-        while let Some(op) = self.ops.get(self.pointer) {
-            match self.inner.perform_operation(op, writer) {
-                Action::None => {}
-                Action::Exit(s) => {
-                    eprintln!("{}", s);
-                    break;
-                }
-                Action::JumpForward => {
-                    self.jmp_forward();
-                    continue;
-                }
-                Action::JumpBack => {
-                    self.pointer = *self.jump_stack.last().unwrap();
-                    continue;
-                }
+fn profile_main(opt: ProfileOpt) {
+    match opt.command {
+        Some(ProfileCommand::Merge { inputs, output }) => {
+            let mut merged = exit_on_err(Profile::load(&inputs[0]));
+            for input in &inputs[1..] {
+                exit_on_err(merged.merge(&exit_on_err(Profile::load(input))));
             }
-
-            // If Action::None
-            match op {
-                OpCode::JmpStart => {
-                    // Jump back should land on Op after current
-                    self.jump_stack.push(self.pointer + 1);
-                }
-                OpCode::JmpEnd => {
-                    self.jump_stack.pop().unwrap();
-                }
-                _ => {
-                    if wait > 0 {
-                        self.inner.write_array(6);
-                        thread::sleep(Duration::from_millis(wait));
+            exit_on_err(merged.save(&output));
+        }
+        None => {
+            let file = opt.file.unwrap();
+            let mut interp: Interpreter = load(&file, &opt.limits).into();
+            let mut profiler = Profiler::new(interp.program());
+            if opt.folded.is_some() {
+                profiler = profiler.with_folded();
+            }
+            if opt.superinstructions.is_some() {
+                profiler = profiler.with_sequences();
+            }
+            let every = opt.sample_every;
+            let mut timing = opt.timing.then(|| Timing::new(every));
+            let result =
+                interp.execute_with(&mut stdin::input(), &mut stdout().lock(), |interp, at| {
+                    // Keep the profiler's own work out of the sampled time.
+                    if let Some(ref mut timing) = timing {
+                        timing.record(interp, at);
                     }
-                }
+                    profiler.record(interp, at);
+                    if let Some(ref mut timing) = timing {
+                        timing.arm();
+                    }
+                });
+            if let Err(e) = result {
+                eprintln!("{}", e);
             }
 
-            self.increment_pointer();
-        }
-    }
-
-    #[inline]
-    fn increment_pointer(&mut self) {
-        match self.pointer.checked_add(1) {
-            Some(x) => {
-                self.pointer = x;
+            if let Some(timing) = timing {
+                eprint!("{}", timing.report(interp.program(), opt.top_loops));
             }
-            None => panic!("Iter pointer overflow"),
-        }
-    }
 
-    fn jmp_forward(&mut self) {
-        let mut jmp_stack = 0usize;
-        loop {
-            self.increment_pointer();
-            let op = &self.ops[self.pointer]; // parser rejects unmatched skips
-            match op {
-                OpCode::JmpStart => {
-                    jmp_stack += 1;
-                }
-                OpCode::JmpEnd => {
-                    if jmp_stack == 0 {
-                        break;
-                    } else {
-                        jmp_stack -= 1;
+            if let Some(ref folded) = opt.folded {
+                exit_on_err(std::fs::write(
+                    folded,
+                    profiler.folded_stacks(interp.program()),
+                ));
+            }
+            if let Some(limit) = opt.superinstructions {
+                let candidates = profiler.superinstructions(limit);
+                match opt.emit_superinstructions {
+                    Some(ref path) => {
+                        exit_on_err(std::fs::write(path, profile::candidate_source(&candidates)))
                     }
+                    None => eprint!("{}", profile::candidate_table(&candidates)),
                 }
-                _ => {}
             }
+
+            let output = opt.output.unwrap_or_else(|| {
+                let mut name = file.clone().into_os_string();
+                name.push(".profile.json");
+                name.into()
+            });
+            exit_on_err(profiler.into_profile(interp.program()).save(&output));
         }
-        self.increment_pointer(); // Skip the JmpEnd that we just landed on.
     }
 }
 
-impl From<Vec<OpCode>> for Interpreter {
-    fn from(v: Vec<OpCode>) -> Self {
-        Self::new(v)
+fn bench_main(opt: BenchOpt) {
+    let results = bench::run(
+        &opt.files,
+        &opt.limits,
+        opt.opt_level,
+        opt.runs as usize,
+        opt.warmup,
+    );
+    if let Some(ref save) = opt.save {
+        exit_on_err(results.save(save));
     }
-}
 
-fn parse_from<R: Read>(reader: R) -> Result<Vec<OpCode>, String> {
-    parse(reader.bytes().map(|r| r.unwrap()))
+    if !opt.compare {
+        match opt.format {
+            StatsFormat::Text => print!("{}", bench::table(&results)),
+            StatsFormat::Json => println!("{}", exit_on_err(serde_json::to_string(&results))),
+        }
+        return;
+    }
+    let baseline = exit_on_err(bench::BenchResults::load(opt.baseline.unwrap()));
+    let (table, regressions) = bench::compare(&baseline, &results, opt.threshold);
+    print!("{}", table);
+    if regressions > 0 {
+        eprintln!(
+            "{} benchmark(s) regressed by more than {}%",
+            regressions, opt.threshold
+        );
+        if !opt.warn_only {
+            std::process::exit(1);
+        }
+    }
 }
 
-fn parse(buf: impl IntoIterator<Item = u8>) -> Result<Vec<OpCode>, String> {
-    let mut ops = Vec::new();
-    let mut open = 0usize;
+fn run_main(mut run: RunOpt) {
+    // Ctrl+C stops the program rather than the process, so reports and
+    // --on-exit hooks still run.
+    exit_on_err(ctrlc::set_handler(|| {
+        INTERRUPTED.store(true, Ordering::Relaxed)
+    }));
+    if run.trace.is_some() && run.files.len() > 1 {
+        eprintln!("--trace records a single program");
+        std::process::exit(1);
+    }
+    if run.provenance.is_some() && run.files.len() > 1 {
+        eprintln!("--provenance records a single program");
+        std::process::exit(1);
+    }
+    if run.dump_tape.is_some() && run.files.len() > 1 {
+        eprintln!("--dump-tape holds a single program's tape");
+        std::process::exit(1);
+    }
+    if run.emit_state_on_exit.is_some() && run.files.len() > 1 {
+        eprintln!("--emit-state-on-exit holds a single program's state");
+        std::process::exit(1);
+    }
+    if run.sha256.is_some() && run.files.len() > 1 {
+        eprintln!("--sha256 pins a single program");
+        std::process::exit(1);
+    }
+    if run.preprocess && run.files.iter().any(|file| package::is_package(file)) {
+        eprintln!("--preprocess can't be used with packages; `bf pack --preprocess` expands them");
+        std::process::exit(1);
+    }
+    if run.check {
+        if !check_packages(&run) {
+            std::process::exit(1);
+        }
+        return;
+    }
+    if run.files.iter().any(|file| fetch::url(file).is_some()) {
+        if run.preprocess {
+            eprintln!("--preprocess can't be used with programs fetched from URLs");
+            std::process::exit(1);
+        }
+        run.timeout.get_or_insert(REMOTE_TIMEOUT);
+        run.max_output.get_or_insert(REMOTE_MAX_OUTPUT);
+    }
 
-    for byte in buf {
-        use OpCode::*;
-        let opcode = match byte {
-            b'>' => MoveForward,
-            b'<' => MoveBack,
-            b'.' => Output,
-            b',' => Input,
-            b'+' => Increment,
-            b'-' => Decrement,
-            b'[' => {
-                open += 1;
-                JmpStart
-            }
-            b']' => {
-                if open == 0 {
-                    return Err("Unmatched ']'".into());
+    let topology = match (run.pair.take(), run.topology.take()) {
+        (Some(mut pair), _) => Some(Ok(Topology::pair(pair.remove(0), pair.remove(0)))),
+        (None, Some(path)) => Some(Topology::from_file(path)),
+        (None, None) => None,
+    };
+    if let Some(topology) = topology {
+        let result = topology.and_then(|t| {
+            let mut sched = Scheduler::new(t, run.fuel, &run.limits)
+                .livelock_steps(run.livelock_steps)
+                .deadline(run.timeout.map(|timeout| Instant::now() + timeout))
+                .loop_cap(run.loop_cap)
+                .max_output(run.max_output);
+            let result = sched.run();
+            let json = run.stats && matches!(run.format, StatsFormat::Json);
+            if json {
+                for stats in sched.stats() {
+                    eprintln!("{}", exit_on_err(serde_json::to_string(stats)));
                 }
-
-                open -= 1;
-                JmpEnd
             }
-            _ => {
-                continue;
+            if run.sched_stats || (run.stats && !json) {
+                eprint!("{}", sched.fairness_table());
             }
-        };
-        ops.push(opcode);
-    }
-
-    if open != 0 {
-        Err("Unmatched '['".into())
-    } else {
-        Ok(ops)
+            result
+        });
+        if let Err(e) = result {
+            eprintln!("{}", e);
+            std::process::exit(if INTERRUPTED.load(Ordering::Relaxed) {
+                130
+            } else {
+                1
+            });
+        }
+        return;
     }
-}
 
-fn run_file<P: AsRef<Path>, W: Write>(path: P, writer: &mut W) {
-    let ops = parse_from(File::open(path).unwrap()).unwrap();
-    let mut interp: Interpreter = ops.into();
-    interp.execute_all(writer);
-}
+    let pipe = run.pipe_to.as_ref().map(|cmd| {
+        PipedChild::spawn(cmd, run.pipe_back).unwrap_or_else(|e| {
+            eprintln!("Failed to spawn '{}': {}", cmd, e);
+            std::process::exit(1);
+        })
+    });
 
-fn usage() {
-    eprintln!("USAGE: bf FILE...")
-}
+    let sink = run.output.as_ref().map(|path| {
+        let file = File::create(path).unwrap_or_else(|e| {
+            eprintln!("Failed to create {}: {}", path.display(), e);
+            std::process::exit(1);
+        });
+        Encoded::new(file, run.output_encoding)
+    });
 
-fn main() {
-    let mut args = std::env::args_os();
-    if args.len() < 2 {
-        eprintln!("No file to execute.");
-        usage();
-        std::process::exit(1);
+    if let Some(text) = run.input_str.take() {
+        run.input = InputSource::Bytes(text.into_bytes());
+    }
+    let input: Option<Box<dyn Read>> = match run.input {
+        InputSource::File(ref path) => match File::open(path) {
+            Ok(file) => Some(Box::new(BufReader::new(file))),
+            Err(e) => {
+                eprintln!("Failed to open {}: {}", path.display(), e);
+                std::process::exit(1);
+            }
+        },
+        InputSource::Bytes(ref bytes) => Some(Box::new(std::io::Cursor::new(bytes.clone()))),
+        InputSource::Stdin if stdin().is_terminal() => run
+            .prompt
+            .clone()
+            .map(|prompt| Box::new(Prompted::new(console::input(), prompt)) as Box<dyn Read>),
+        _ => None,
     };
 
-    for arg in args.by_ref() {
-        if arg == "-h" || arg == "--help" {
-            usage();
-            std::process::exit(0);
+    let mut completed = true;
+    match pipe {
+        Some((mut child, back)) => {
+            let mut reader: Box<dyn Read> = match back {
+                Some(r) => Box::new(r),
+                None => input.unwrap_or_else(|| Box::new(stdin::input())),
+            };
+            for file in &run.files {
+                completed &= run_file(file, &run, &mut reader, &mut child);
+            }
+            drop(reader);
+
+            match child.finish() {
+                Ok(status) if !status.success() => {
+                    eprintln!("Pipe command exited with {}", status);
+                }
+                Ok(_) => {}
+                Err(e) => eprintln!("Failed to wait for pipe command: {}", e),
+            }
+        }
+        None if sink.is_some() || run.stdout_encoding != Encoding::Raw => {
+            let terminal = Encoded::new(console::output(), run.stdout_encoding);
+            let mut writer: Box<dyn Write> = match sink {
+                Some(file) if run.also_stdout => Box::new(Tee::new(file, terminal)),
+                Some(file) => Box::new(file),
+                None => Box::new(terminal),
+            };
+            let mut reader = input.unwrap_or_else(console::input);
+            for file in &run.files {
+                completed &= run_file(file, &run, &mut reader, &mut writer);
+            }
+        }
+        None if cfg!(windows) => {
+            let mut reader = input.unwrap_or_else(console::input);
+            let mut writer = console::output();
+            for file in &run.files {
+                completed &= run_file(file, &run, &mut reader, &mut writer);
+            }
+        }
+        None => {
+            // One reader for every file, so none loses what another buffered.
+            let mut reader = input.unwrap_or_else(|| Box::new(stdin::input()));
+            for file in &run.files {
+                completed &= run_file(file, &run, &mut reader, &mut stdout().lock());
+            }
         }
     }
-
-    for arg in args.skip(1) {
-        run_file(arg, &mut stdout().lock());
+    if !completed {
+        std::process::exit(1);
     }
 }
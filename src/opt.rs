@@ -0,0 +1,293 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use crate::parser::OpCode;
+use crate::program::{OpIndex, Program};
+
+/// The pattern a loop body matches, or why it matches none.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LoopKind {
+    /// `[-]` or `[+]`: sets the cell to zero.
+    Clear,
+    /// `[>]`, `[<<]`, ...: moves by a fixed stride until a zero cell.
+    Scan(isize),
+    /// Adds multiples of the current cell to cells at fixed offsets, then
+    /// clears it.
+    Multiply(BTreeMap<isize, i32>),
+    /// No pattern applies.
+    Unoptimized(Reason),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reason {
+    ContainsIo,
+    NestedLoop,
+    /// The body moves the pointer by this much per iteration.
+    UnbalancedMovement(isize),
+    /// The body changes the loop's own cell by this much per iteration,
+    /// so the iteration count isn't the cell's value.
+    CellStep(i32),
+}
+
+impl fmt::Display for LoopKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoopKind::Clear => write!(f, "clear loop"),
+            LoopKind::Scan(stride) => write!(f, "scan loop (stride {})", stride),
+            LoopKind::Multiply(factors) => {
+                write!(f, "multiply loop (")?;
+                for (i, (offset, factor)) in factors.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "[{:+}] += {}*x", offset, factor)?;
+                }
+                write!(f, ")")
+            }
+            LoopKind::Unoptimized(reason) => write!(f, "not optimized: {}", reason),
+        }
+    }
+}
+
+impl fmt::Display for Reason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Reason::ContainsIo => write!(f, "contains I/O"),
+            Reason::NestedLoop => write!(f, "contains a nested loop"),
+            Reason::UnbalancedMovement(by) => {
+                write!(
+                    f,
+                    "unbalanced movement (pointer moves {:+} per iteration)",
+                    by
+                )
+            }
+            Reason::CellStep(by) => {
+                write!(f, "loop cell changes by {:+} per iteration, not -1", by)
+            }
+        }
+    }
+}
+
+/// Classifies the loop whose body is `body`, excluding its brackets.
+pub fn classify(body: &[OpCode]) -> LoopKind {
+    let mut offset = 0isize;
+    let mut deltas: BTreeMap<isize, i32> = BTreeMap::new();
+    for op in body {
+        match op {
+            OpCode::MoveForward => offset += 1,
+            OpCode::MoveBack => offset -= 1,
+            OpCode::Increment => *deltas.entry(offset).or_default() += 1,
+            OpCode::Decrement => *deltas.entry(offset).or_default() -= 1,
+            OpCode::Input | OpCode::Output => return LoopKind::Unoptimized(Reason::ContainsIo),
+            OpCode::JmpStart | OpCode::JmpEnd => return LoopKind::Unoptimized(Reason::NestedLoop),
+        }
+    }
+    deltas.retain(|_, delta| *delta != 0);
+
+    if offset != 0 {
+        return if deltas.is_empty() {
+            LoopKind::Scan(offset)
+        } else {
+            LoopKind::Unoptimized(Reason::UnbalancedMovement(offset))
+        };
+    }
+    match deltas.remove(&0) {
+        Some(-1 | 1) if deltas.is_empty() => LoopKind::Clear,
+        Some(-1) => LoopKind::Multiply(deltas),
+        step => LoopKind::Unoptimized(Reason::CellStep(step.unwrap_or(0))),
+    }
+}
+
+/// Every loop in `program` by the index of its `[`, with its
+/// classification.
+pub fn explain(program: &Program) -> Vec<(OpIndex, LoopKind)> {
+    let mut loops = Vec::new();
+    let mut open = Vec::new();
+    for i in 0..program.positions().len() {
+        match program[OpIndex::new(i)] {
+            OpCode::JmpStart => open.push(i),
+            OpCode::JmpEnd => {
+                if let Some(start) = open.pop() {
+                    let body: Vec<OpCode> =
+                        (start + 1..i).map(|j| program[OpIndex::new(j)]).collect();
+                    loops.push((OpIndex::new(start), classify(&body)));
+                }
+            }
+            _ => {}
+        }
+    }
+    loops.sort_by_key(|&(start, _)| start);
+    loops
+}
+
+/// What one iteration of a loop body is known to do, from counting its
+/// moves and arithmetic. Offsets are from the cell the iteration starts on.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Balance {
+    /// Net pointer movement, unless a nested loop makes it depend on the
+    /// tape.
+    pub movement: Option<isize>,
+    /// Net change to each cell the body's own `+` and `-` touch.
+    pub deltas: BTreeMap<isize, i32>,
+    /// Cells also changed by nested loops or input, by amounts the
+    /// counting can't follow. `None` when that could be any cell.
+    pub unknown: Option<BTreeSet<isize>>,
+    pub io: bool,
+}
+
+impl Balance {
+    /// Balance of the loop body `body`, excluding its brackets.
+    pub fn of(body: &[OpCode]) -> Self {
+        let mut balance = Self {
+            movement: Some(0),
+            unknown: Some(BTreeSet::new()),
+            ..Self::default()
+        };
+        let mut offset = 0isize;
+        let mut i = 0;
+        while i < body.len() {
+            match body[i] {
+                OpCode::MoveForward => offset += 1,
+                OpCode::MoveBack => offset -= 1,
+                OpCode::Increment => *balance.deltas.entry(offset).or_default() += 1,
+                OpCode::Decrement => *balance.deltas.entry(offset).or_default() -= 1,
+                OpCode::Output => balance.io = true,
+                OpCode::Input => {
+                    balance.io = true;
+                    balance.mark_unknown(offset);
+                }
+                OpCode::JmpStart => {
+                    let end = matching_end(body, i);
+                    let inner = Self::of(&body[i + 1..end]);
+                    balance.io |= inner.io;
+                    if inner.movement != Some(0) {
+                        // Nothing after this loop has a known offset.
+                        balance.movement = None;
+                        balance.unknown = None;
+                        return balance;
+                    }
+                    match inner.unknown {
+                        Some(unknown) => {
+                            for cell in inner.deltas.keys().chain(&unknown) {
+                                balance.mark_unknown(offset + cell);
+                            }
+                        }
+                        None => balance.unknown = None,
+                    }
+                    i = end;
+                }
+                OpCode::JmpEnd => {}
+            }
+            i += 1;
+        }
+        balance.deltas.retain(|_, delta| *delta != 0);
+        balance.movement = Some(offset);
+        balance
+    }
+
+    fn mark_unknown(&mut self, cell: isize) {
+        if let Some(ref mut unknown) = self.unknown {
+            unknown.insert(cell);
+        }
+    }
+
+    /// How much an iteration changes the loop's own cell, if that's fixed.
+    pub fn cell_step(&self) -> Option<i32> {
+        match self.unknown {
+            Some(ref unknown) if !unknown.contains(&0) => {
+                Some(self.deltas.get(&0).copied().unwrap_or(0))
+            }
+            _ => None,
+        }
+    }
+
+    /// What follows about the loop, one fact per line.
+    pub fn hints(&self) -> Vec<String> {
+        let movement = match self.movement {
+            Some(movement) => movement,
+            None => {
+                return vec![
+                    "pointer movement depends on the tape, through a nested loop that moves it"
+                        .into(),
+                ]
+            }
+        };
+        if movement != 0 {
+            return vec![format!(
+                "net pointer delta {:+} per iteration → runs until the pointer reaches a zero cell",
+                movement
+            )];
+        }
+
+        let mut hints = vec![match self.cell_step() {
+            None => "net pointer delta 0, but nested loops or input change cell 0 by amounts that can't be followed".into(),
+            Some(step) => {
+                let fact = match step {
+                    0 => "cell 0 unchanged per iteration".to_string(),
+                    -1 => "cell 0 decremented by 1 per iteration".to_string(),
+                    1 => "cell 0 incremented by 1 per iteration".to_string(),
+                    _ => format!("cell 0 changed by {:+} per iteration", step),
+                };
+                format!("net pointer delta 0, {} → {}", fact, runs(step))
+            }
+        }];
+        let exact = self.cell_step() == Some(-1);
+        for (offset, delta) in self.deltas.iter().filter(|&(&offset, _)| offset != 0) {
+            if self
+                .unknown
+                .as_ref()
+                .is_some_and(|unknown| unknown.contains(offset))
+            {
+                continue;
+            }
+            let mut hint = format!("cell {:+} changes by {:+} per iteration", offset, delta);
+            if exact {
+                hint.push_str(&format!(
+                    " → {:+} × initial(cell0) over the whole loop",
+                    delta
+                ));
+            }
+            hints.push(hint);
+        }
+        if self.io {
+            hints.push("does I/O each iteration".into());
+        }
+        hints
+    }
+}
+
+/// How often a balanced loop whose cell changes by `step` each iteration
+/// runs, in wrapping arithmetic.
+fn runs(step: i32) -> String {
+    let step = step.rem_euclid(256);
+    match step {
+        0 => "never ends once entered".into(),
+        255 => "runs exactly initial(cell0) times".into(),
+        1 => "runs exactly 256 − initial(cell0) times".into(),
+        _ if step % 2 == 1 => {
+            "ends after a fixed number of iterations for any initial(cell0)".into()
+        }
+        _ => format!(
+            "ends only if initial(cell0) is a multiple of {}, otherwise never",
+            1 << step.trailing_zeros()
+        ),
+    }
+}
+
+/// The index of the `]` matching the `[` at `start` in `ops`.
+fn matching_end(ops: &[OpCode], start: usize) -> usize {
+    let mut depth = 0;
+    for (i, op) in ops.iter().enumerate().skip(start) {
+        match op {
+            OpCode::JmpStart => depth += 1,
+            OpCode::JmpEnd => {
+                depth -= 1;
+                if depth == 0 {
+                    return i;
+                }
+            }
+            _ => {}
+        }
+    }
+    unreachable!("parser rejects unmatched '['")
+}
@@ -0,0 +1,107 @@
+//! Writers that sit between a program's `.` and where its output ends up.
+
+use std::io::{self, Write};
+
+/// How bytes are written to an output sink.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Encoding {
+    /// Bytes as written.
+    Raw,
+    /// Two hex digits per byte, sixteen bytes to a line.
+    Hex,
+    /// One decimal byte value per line.
+    Decimal,
+    /// Printable ASCII as is, everything else as `\xNN`.
+    Escaped,
+}
+
+#[derive(Debug)]
+pub struct Encoded<W: Write> {
+    inner: W,
+    encoding: Encoding,
+    // Bytes written so far, for hex line breaks.
+    written: u64,
+}
+
+impl<W: Write> Encoded<W> {
+    pub fn new(inner: W, encoding: Encoding) -> Self {
+        Self {
+            inner,
+            encoding,
+            written: 0,
+        }
+    }
+}
+
+impl<W: Write> Write for Encoded<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.encoding == Encoding::Raw {
+            return self.inner.write(buf);
+        }
+
+        let mut text = String::new();
+        for &byte in buf {
+            match self.encoding {
+                Encoding::Raw => unreachable!(),
+                Encoding::Hex => {
+                    if self.written > 0 {
+                        text.push(if self.written.is_multiple_of(16) {
+                            '\n'
+                        } else {
+                            ' '
+                        });
+                    }
+                    text.push_str(&format!("{:02x}", byte));
+                }
+                Encoding::Decimal => text.push_str(&format!("{}\n", byte)),
+                Encoding::Escaped => match byte {
+                    b'\\' => text.push_str("\\\\"),
+                    b'\n' | b' '..=b'~' => text.push(byte as char),
+                    _ => text.push_str(&format!("\\x{:02x}", byte)),
+                },
+            }
+            self.written += 1;
+        }
+        self.inner.write_all(text.as_bytes())?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<W: Write> Drop for Encoded<W> {
+    fn drop(&mut self) {
+        if self.encoding == Encoding::Hex && self.written > 0 {
+            let _ = self.inner.write_all(b"\n");
+        }
+        let _ = self.inner.flush();
+    }
+}
+
+/// Writes everything to both `a` and `b`.
+#[derive(Debug)]
+pub struct Tee<A, B> {
+    a: A,
+    b: B,
+}
+
+impl<A: Write, B: Write> Tee<A, B> {
+    pub fn new(a: A, b: B) -> Self {
+        Self { a, b }
+    }
+}
+
+impl<A: Write, B: Write> Write for Tee<A, B> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.a.write_all(buf)?;
+        self.b.write_all(buf)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.a.flush()?;
+        self.b.flush()
+    }
+}
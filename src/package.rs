@@ -0,0 +1,146 @@
+//! `.bfpkg` packages: a program, the options it needs to run correctly
+//! and cases to check it against, in one TOML file made by `bf pack`:
+//!
+//! ```toml
+//! source = "++++++++[>++++++++<-]>+."
+//!
+//! [options]                 # all optional, named as for `bf run`
+//! cell_size = "16"
+//! tape_storage = "sparse"
+//! bounds = "wrap"
+//! tape_size = 30000
+//! signed = true
+//! overflow = "error"
+//! eof = "minus-one"
+//!
+//! [[test]]
+//! name = "prints A"         # optional
+//! input = ""
+//! output = "A"
+//! ```
+//!
+//! `bf run pkg.bfpkg` takes the options as defaults the command line can
+//! still override, and `bf run --check` runs the test cases.
+
+use serde::{Deserialize, Serialize};
+
+use std::path::Path;
+
+use bf::tape::{Bounds, CellSize, Eof, Overflow, Storage};
+
+/// Files `bf run` reads as packages rather than as source.
+pub const EXTENSION: &str = "bfpkg";
+
+pub fn is_package(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == EXTENSION)
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Package {
+    pub source: String,
+    #[serde(default)]
+    pub options: Options,
+    #[serde(default, rename = "test", skip_serializing_if = "Vec::is_empty")]
+    pub tests: Vec<TestCase>,
+}
+
+/// Settings for the run, by the names `bf run` gives their flags.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Options {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cell_size: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tape_storage: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bounds: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tape_size: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signed: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub overflow: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub eof: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TestCase {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub input: String,
+    pub output: String,
+}
+
+impl Package {
+    pub fn parse(bytes: &[u8]) -> Result<Self, String> {
+        let text = std::str::from_utf8(bytes).map_err(|e| e.to_string())?;
+        let package: Self = toml::from_str(text).map_err(|e| e.to_string())?;
+        // Catch unknown option values now rather than at run time.
+        package.options.cell_size()?;
+        package.options.tape_storage()?;
+        package.options.bounds()?;
+        package.options.overflow()?;
+        package.options.eof()?;
+        Ok(package)
+    }
+
+    pub fn to_toml(&self) -> Result<String, String> {
+        toml::to_string(self).map_err(|e| e.to_string())
+    }
+}
+
+impl Options {
+    pub fn cell_size(&self) -> Result<Option<CellSize>, String> {
+        value_of("cell_size", self.cell_size.as_deref())
+    }
+
+    pub fn tape_storage(&self) -> Result<Option<Storage>, String> {
+        value_of("tape_storage", self.tape_storage.as_deref())
+    }
+
+    pub fn bounds(&self) -> Result<Option<Bounds>, String> {
+        value_of("bounds", self.bounds.as_deref())
+    }
+
+    pub fn overflow(&self) -> Result<Option<Overflow>, String> {
+        value_of("overflow", self.overflow.as_deref())
+    }
+
+    pub fn eof(&self) -> Result<Option<Eof>, String> {
+        value_of("eof", self.eof.as_deref())
+    }
+}
+
+/// The value `name` spells in the same form its `bf run` flag takes.
+fn value_of<T: clap::ValueEnum>(option: &str, name: Option<&str>) -> Result<Option<T>, String> {
+    name.map(|name| {
+        T::from_str(name, false).map_err(|_| bad_value(option, name, T::value_variants()))
+    })
+    .transpose()
+}
+
+fn bad_value<T: clap::ValueEnum>(option: &str, name: &str, variants: &[T]) -> String {
+    let names: Vec<String> = variants
+        .iter()
+        .filter_map(|v| v.to_possible_value())
+        .map(|v| v.get_name().to_owned())
+        .collect();
+    format!(
+        "{} can't be '{}' (one of: {})",
+        option,
+        name,
+        names.join(", ")
+    )
+}
+
+/// The name `value` goes by on the command line and in packages.
+pub fn name_of<T: clap::ValueEnum>(value: &T) -> String {
+    value
+        .to_possible_value()
+        .map(|v| v.get_name().to_owned())
+        .unwrap_or_default()
+}
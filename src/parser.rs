@@ -0,0 +1,123 @@
+use std::io::{prelude::*, BufReader};
+
+use crate::limits::{Limits, ParseError};
+use crate::program::{OpIndex, Program, SourcePos};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OpCode {
+    MoveForward,
+    MoveBack,
+    Increment,
+    Decrement,
+    Output,
+    Input,
+    JmpStart,
+    JmpEnd,
+}
+
+impl OpCode {
+    pub fn symbol(&self) -> char {
+        use OpCode::*;
+
+        match self {
+            MoveForward => '>',
+            MoveBack => '<',
+            Increment => '+',
+            Decrement => '-',
+            Output => '.',
+            Input => ',',
+            JmpStart => '[',
+            JmpEnd => ']',
+        }
+    }
+}
+
+/// Parses the source `reader` reads, failing with `ParseError::Io` if
+/// reading does.
+pub fn parse_from<R: Read>(reader: R, limits: &Limits) -> Result<Program, ParseError> {
+    let mut error = None;
+    let bytes = BufReader::new(reader)
+        .bytes()
+        .map_while(|byte| byte.map_err(|e| error = Some(e)).ok());
+    let program = parse(bytes, limits);
+    match error {
+        Some(e) => Err(ParseError::Io(e.to_string())),
+        None => program,
+    }
+}
+
+/// Splits `source` that follows the `program!input` convention at its first
+/// `!`, into the program and any input for it:
+///
+/// ```
+/// let (program, input) = bf::parser::split_input(b",[.,]!hello");
+/// assert_eq!((program, input), (&b",[.,]"[..], Some(&b"hello"[..])));
+/// ```
+pub fn split_input(source: &[u8]) -> (&[u8], Option<&[u8]>) {
+    match source.iter().position(|&b| b == b'!') {
+        Some(at) => (&source[..at], Some(&source[at + 1..])),
+        None => (source, None),
+    }
+}
+
+/// Parses brainfuck source, ignoring every byte that isn't an instruction.
+pub fn parse(buf: impl IntoIterator<Item = u8>, limits: &Limits) -> Result<Program, ParseError> {
+    let mut ops = Program::new();
+    // The `[`s still waiting for their `]`.
+    let mut open: Vec<(SourcePos, OpIndex)> = Vec::new();
+    let (mut line, mut col) = (1, 0);
+
+    for (offset, byte) in buf.into_iter().enumerate() {
+        use OpCode::*;
+
+        if let Some(max) = limits.bytes.filter(|&max| offset >= max) {
+            return Err(ParseError::TooManyBytes(max));
+        }
+        if byte == b'\n' {
+            line += 1;
+            col = 0;
+            continue;
+        }
+        col += 1;
+        let pos = SourcePos { offset, line, col };
+
+        let opcode = match byte {
+            b'>' => MoveForward,
+            b'<' => MoveBack,
+            b'.' => Output,
+            b',' => Input,
+            b'+' => Increment,
+            b'-' => Decrement,
+            b'[' => {
+                if open.len() == limits.depth {
+                    return Err(ParseError::TooDeep(limits.depth, pos));
+                }
+
+                open.push((pos, OpIndex::new(ops.positions().len())));
+                JmpStart
+            }
+            b']' => {
+                if open.is_empty() {
+                    return Err(ParseError::UnmatchedClose(pos));
+                }
+
+                JmpEnd
+            }
+            _ => {
+                continue;
+            }
+        };
+        if limits.instructions == Some(ops.positions().len()) {
+            return Err(ParseError::TooManyInstructions(ops.positions().len()));
+        }
+        let at = ops.push(opcode, pos);
+        if let JmpEnd = opcode {
+            ops.link(open.pop().unwrap().1, at);
+        }
+    }
+
+    match open.pop() {
+        Some((pos, _)) => Err(ParseError::UnmatchedOpen(pos)),
+        None => Ok(ops),
+    }
+}
@@ -0,0 +1,197 @@
+use std::io::{self, prelude::*};
+use std::process::{Child, ChildStdin, Command, ExitStatus, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+// Number of chunks the child may run ahead of the program's `,` reads.
+const PIPE_BACKLOG: usize = 8;
+const CHUNK_SIZE: usize = 4096;
+// How long a read waits for the child before letting the interpreter
+// check whether it should stop, as stdin reads do.
+const POLL: Duration = Duration::from_millis(50);
+
+/// A child process fed by the program's output.
+#[derive(Debug)]
+pub struct PipedChild {
+    child: Child,
+    stdin: Option<ChildStdin>,
+    reader: Option<JoinHandle<()>>,
+}
+
+impl PipedChild {
+    /// Spawns `command`, split into words as by `words`. When `pipe_back`
+    /// is set, the child's stdout is returned as a reader suitable for `,`;
+    /// otherwise it is inherited.
+    pub fn spawn(command: &str, pipe_back: bool) -> io::Result<(Self, Option<ChannelReader>)> {
+        let words = words(command).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        let (program, args) = words
+            .split_first()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Empty pipe command"))?;
+
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(if pipe_back {
+                Stdio::piped()
+            } else {
+                Stdio::inherit()
+            })
+            .spawn()?;
+
+        let stdin = child.stdin.take();
+        let (reader, channel) = match child.stdout.take() {
+            Some(stdout) => {
+                let (tx, rx) = mpsc::sync_channel(PIPE_BACKLOG);
+                let handle = thread::spawn(move || {
+                    let mut stdout = stdout;
+                    let mut buf = [0; CHUNK_SIZE];
+                    loop {
+                        match stdout.read(&mut buf) {
+                            Ok(0) => break,
+                            Ok(n) => {
+                                // Receiver gone means the program finished.
+                                if tx.send(buf[..n].to_vec()).is_err() {
+                                    break;
+                                }
+                            }
+                            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                            Err(_) => break,
+                        }
+                    }
+                });
+                (Some(handle), Some(ChannelReader::new(rx)))
+            }
+            None => (None, None),
+        };
+
+        Ok((
+            Self {
+                child,
+                stdin,
+                reader,
+            },
+            channel,
+        ))
+    }
+
+    /// Closes the child's stdin and waits for it to exit.
+    ///
+    /// Any `ChannelReader` returned by `spawn` must be dropped first.
+    pub fn finish(mut self) -> io::Result<ExitStatus> {
+        drop(self.stdin.take());
+        let status = self.child.wait()?;
+        if let Some(handle) = self.reader.take() {
+            handle.join().ok();
+        }
+        Ok(status)
+    }
+}
+
+impl Write for PipedChild {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self.stdin {
+            Some(ref mut stdin) => stdin.write(buf),
+            None => Err(io::ErrorKind::BrokenPipe.into()),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self.stdin {
+            Some(ref mut stdin) => stdin.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+/// `command` split into words the way a shell would, without running one:
+/// whitespace separates words, `'...'` quotes text as is, `"..."` quotes
+/// text in which `\` escapes `"` and `\`, and elsewhere `\` escapes the
+/// next character. Nothing is expanded.
+pub fn words(command: &str) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut chars = command.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => words.extend(word.take()),
+            '\'' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => return Err(format!("Unterminated ' in '{}'", command)),
+                    }
+                }
+            }
+            '"' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '\\')) => word.push(c),
+                            Some(c) => {
+                                word.push('\\');
+                                word.push(c);
+                            }
+                            None => return Err(format!("Unterminated \" in '{}'", command)),
+                        },
+                        Some(c) => word.push(c),
+                        None => return Err(format!("Unterminated \" in '{}'", command)),
+                    }
+                }
+            }
+            '\\' => {
+                let escaped = chars
+                    .next()
+                    .ok_or_else(|| format!("Trailing \\ in '{}'", command))?;
+                word.get_or_insert_with(String::new).push(escaped);
+            }
+            c => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    words.extend(word);
+    Ok(words)
+}
+
+/// Reads bytes sent over a bounded channel, reporting EOF once the sender
+/// hangs up and `WouldBlock` when nothing comes for a while, so a deadline
+/// or Ctrl+C can stop a program waiting on the child.
+#[derive(Debug)]
+pub struct ChannelReader {
+    rx: Receiver<Vec<u8>>,
+    buf: Vec<u8>,
+    pos: usize,
+}
+
+impl ChannelReader {
+    pub fn new(rx: Receiver<Vec<u8>>) -> Self {
+        Self {
+            rx,
+            buf: Vec::new(),
+            pos: 0,
+        }
+    }
+}
+
+impl Read for ChannelReader {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        while self.pos == self.buf.len() {
+            match self.rx.recv_timeout(POLL) {
+                Ok(chunk) => {
+                    self.buf = chunk;
+                    self.pos = 0;
+                }
+                Err(RecvTimeoutError::Timeout) => return Err(io::ErrorKind::WouldBlock.into()),
+                Err(RecvTimeoutError::Disconnected) => return Ok(0),
+            }
+        }
+
+        let n = out.len().min(self.buf.len() - self.pos);
+        out[..n].copy_from_slice(&self.buf[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
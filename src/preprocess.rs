@@ -0,0 +1,78 @@
+//! Textual expansion run before parsing when `--preprocess` is given. A
+//! line `@include <std/NAME>` is replaced by a built-in snippet and
+//! `@include "PATH"` by a file, relative to the including file; included
+//! text is expanded in turn.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::stdlib;
+
+const MAX_INCLUDE_DEPTH: usize = 64;
+
+/// Expands the `@include`s in `source`, which was read from `path`.
+pub fn expand(source: &[u8], path: &Path) -> Result<Vec<u8>, String> {
+    let mut out = Vec::with_capacity(source.len());
+    let mut including = vec![path.display().to_string()];
+    expand_into(source, path, &mut including, &mut out)?;
+    Ok(out)
+}
+
+fn expand_into(
+    source: &[u8],
+    path: &Path,
+    including: &mut Vec<String>,
+    out: &mut Vec<u8>,
+) -> Result<(), String> {
+    for (i, line) in source.split_inclusive(|&b| b == b'\n').enumerate() {
+        let text = String::from_utf8_lossy(line);
+        let target = match text.trim().strip_prefix("@include") {
+            Some(target) => target.trim(),
+            None => {
+                out.extend_from_slice(line);
+                continue;
+            }
+        };
+        let at = |e: String| format!("{}:{}: {}", path.display(), i + 1, e);
+
+        let (name, text, next): (String, Vec<u8>, PathBuf) = if let Some(name) = target
+            .strip_prefix("<std/")
+            .and_then(|t| t.strip_suffix('>'))
+        {
+            let snippet = stdlib::get(name)
+                .ok_or_else(|| at(format!("no snippet std/{} (see `bf std list`)", name)))?;
+            (format!("<std/{}>", name), snippet.into(), path.to_owned())
+        } else if let Some(file) = target.strip_prefix('"').and_then(|t| t.strip_suffix('"')) {
+            let file = path.parent().unwrap_or_else(|| Path::new("")).join(file);
+            let text = fs::read(&file)
+                .map_err(|e| at(format!("can't include {}: {}", file.display(), e)))?;
+            (file.display().to_string(), text, file)
+        } else {
+            return Err(at(format!(
+                "expected @include <std/NAME> or @include \"PATH\", found '{}'",
+                text.trim()
+            )));
+        };
+
+        if including.contains(&name) {
+            return Err(at(format!(
+                "include cycle: {} -> {}",
+                including.join(" -> "),
+                name
+            )));
+        }
+        if including.len() == MAX_INCLUDE_DEPTH {
+            return Err(at(format!(
+                "includes nested deeper than {}",
+                MAX_INCLUDE_DEPTH
+            )));
+        }
+        including.push(name);
+        expand_into(&text, &next, including, out)?;
+        including.pop();
+        if !out.ends_with(b"\n") {
+            out.push(b'\n');
+        }
+    }
+    Ok(())
+}
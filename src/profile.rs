@@ -0,0 +1,340 @@
+use serde::{Deserialize, Serialize};
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter};
+use std::path::Path;
+
+use bf::program::{OpIndex, Program};
+use bf::{Interpreter, OpCode};
+
+pub const PROFILE_VERSION: u32 = 1;
+
+/// Execution counts for one program, keyed by source offset so profiles
+/// stay meaningful to tools that only have the source text.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Profile {
+    pub version: u32,
+    /// `Program::hash` of the profiled program, in hex.
+    pub program_hash: String,
+    pub runs: u64,
+    pub counts: Vec<OpCount>,
+    pub loops: Vec<LoopProfile>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OpCount {
+    pub offset: usize,
+    pub line: usize,
+    pub col: usize,
+    pub op: char,
+    pub count: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LoopProfile {
+    /// Offset of the loop's `[`.
+    pub offset: usize,
+    pub line: usize,
+    pub col: usize,
+    pub entries: u64,
+    /// Number of times the loop ran for exactly N iterations.
+    pub iterations: BTreeMap<u64, u64>,
+}
+
+impl Profile {
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let file = BufReader::new(File::open(path)?);
+        let profile: Self = serde_json::from_reader(file)?;
+        if profile.version != PROFILE_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unsupported profile version {}", profile.version),
+            ));
+        }
+        Ok(profile)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let file = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(file, self)?;
+        Ok(())
+    }
+
+    /// Adds the runs recorded in `other`, which must profile the same program.
+    pub fn merge(&mut self, other: &Profile) -> Result<(), String> {
+        if self.program_hash != other.program_hash {
+            return Err(format!(
+                "profiles are for different programs ({} and {})",
+                self.program_hash, other.program_hash
+            ));
+        }
+
+        self.runs += other.runs;
+        for count in &other.counts {
+            match self.counts.iter_mut().find(|c| c.offset == count.offset) {
+                Some(mine) => mine.count += count.count,
+                None => self.counts.push(count.clone()),
+            }
+        }
+        for lp in &other.loops {
+            match self.loops.iter_mut().find(|l| l.offset == lp.offset) {
+                Some(mine) => {
+                    mine.entries += lp.entries;
+                    for (&n, &times) in &lp.iterations {
+                        *mine.iterations.entry(n).or_default() += times;
+                    }
+                }
+                None => self.loops.push(lp.clone()),
+            }
+        }
+
+        self.counts.sort_by_key(|c| c.offset);
+        self.loops.sort_by_key(|l| l.offset);
+        Ok(())
+    }
+}
+
+/// Collects a `Profile` from `Interpreter::execute_with` callbacks.
+#[derive(Debug)]
+pub struct Profiler {
+    counts: Vec<u64>,
+    loops: BTreeMap<OpIndex, BTreeMap<u64, u64>>,
+    // Loops currently executing: their `[` and iterations so far.
+    active: Vec<(OpIndex, u64)>,
+    // Instructions executed under each stack of enclosing loops, when
+    // folded stacks were requested. `stack` mirrors the `[`s in `active`.
+    folded: Option<HashMap<Vec<OpIndex>, u64>>,
+    stack: Vec<OpIndex>,
+    sequences: Option<Sequences>,
+}
+
+/// Dynamic frequencies of consecutively executed instruction kinds.
+#[derive(Debug, Default)]
+struct Sequences {
+    pairs: HashMap<[u8; 2], u64>,
+    triples: HashMap<[u8; 3], u64>,
+    last: [u8; 2],
+    executed: u64,
+}
+
+impl Sequences {
+    fn record(&mut self, op: OpCode) {
+        let sym = op.symbol() as u8;
+        if self.executed >= 1 {
+            *self.pairs.entry([self.last[1], sym]).or_default() += 1;
+        }
+        if self.executed >= 2 {
+            *self
+                .triples
+                .entry([self.last[0], self.last[1], sym])
+                .or_default() += 1;
+        }
+        self.last = [self.last[1], sym];
+        self.executed += 1;
+    }
+}
+
+/// A sequence worth fusing into one instruction, and how many dispatches
+/// fusing it would have saved.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Candidate {
+    pub sequence: String,
+    pub count: u64,
+    pub saved: u64,
+}
+
+impl Profiler {
+    pub fn new(program: &Program) -> Self {
+        Self {
+            counts: vec![0; program.positions().len()],
+            loops: BTreeMap::new(),
+            active: Vec::new(),
+            folded: None,
+            stack: Vec::new(),
+            sequences: None,
+        }
+    }
+
+    /// Also count instruction pairs and triples, for `superinstructions`.
+    pub fn with_sequences(mut self) -> Self {
+        self.sequences = Some(Sequences::default());
+        self
+    }
+
+    /// Also attribute instructions to their loop nesting, for
+    /// `folded_stacks`.
+    pub fn with_folded(mut self) -> Self {
+        self.folded = Some(HashMap::new());
+        self
+    }
+
+    /// Records the instruction at `at`, which `interp` has just executed.
+    pub fn record(&mut self, interp: &Interpreter, at: OpIndex) {
+        self.counts[at.index()] += 1;
+        if let Some(ref mut folded) = self.folded {
+            match folded.get_mut(&self.stack[..]) {
+                Some(count) => *count += 1,
+                None => {
+                    folded.insert(self.stack.clone(), 1);
+                }
+            }
+        }
+
+        if let Some(ref mut sequences) = self.sequences {
+            sequences.record(interp.program()[at]);
+        }
+
+        let fell_through = at.next() == Some(interp.pc());
+        match interp.program()[at] {
+            OpCode::JmpStart if fell_through => {
+                self.active.push((at, 1));
+                self.stack.push(at);
+            }
+            OpCode::JmpStart => self.finish_loop(at, 0),
+            OpCode::JmpEnd if fell_through => {
+                if let Some((start, iterations)) = self.active.pop() {
+                    self.stack.pop();
+                    self.finish_loop(start, iterations);
+                }
+            }
+            OpCode::JmpEnd => {
+                if let Some(top) = self.active.last_mut() {
+                    top.1 += 1;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn finish_loop(&mut self, start: OpIndex, iterations: u64) {
+        *self
+            .loops
+            .entry(start)
+            .or_default()
+            .entry(iterations)
+            .or_default() += 1;
+    }
+
+    /// Instruction counts in the folded-stacks format read by flamegraph
+    /// tools, one line per loop nesting: `main;[@1:9;[@1:19 1234`.
+    pub fn folded_stacks(&self, program: &Program) -> String {
+        let positions = program.positions();
+        let mut lines: Vec<String> = self
+            .folded
+            .iter()
+            .flatten()
+            .map(|(stack, count)| {
+                let mut line = "main".to_owned();
+                for start in stack {
+                    line.push_str(&format!(";[@{}", positions[start.index()]));
+                }
+                format!("{} {}", line, count)
+            })
+            .collect();
+        lines.sort();
+
+        let mut out = lines.join("\n");
+        out.push('\n');
+        out
+    }
+
+    /// The `limit` pairs and triples that would save the most dispatches.
+    pub fn superinstructions(&self, limit: usize) -> Vec<Candidate> {
+        let sequences = match self.sequences {
+            Some(ref sequences) => sequences,
+            None => return Vec::new(),
+        };
+        let candidate = |seq: &[u8], count: u64| Candidate {
+            sequence: String::from_utf8_lossy(seq).into_owned(),
+            count,
+            saved: count * (seq.len() as u64 - 1),
+        };
+
+        let mut candidates: Vec<Candidate> = sequences
+            .pairs
+            .iter()
+            .map(|(seq, &count)| candidate(seq, count))
+            .chain(
+                sequences
+                    .triples
+                    .iter()
+                    .map(|(seq, &count)| candidate(seq, count)),
+            )
+            .collect();
+        candidates.sort_by(|a, b| {
+            b.saved
+                .cmp(&a.saved)
+                .then_with(|| a.sequence.cmp(&b.sequence))
+        });
+        candidates.truncate(limit);
+        candidates
+    }
+
+    pub fn into_profile(self, program: &Program) -> Profile {
+        let positions = program.positions();
+        let counts = self
+            .counts
+            .iter()
+            .enumerate()
+            .filter(|&(_, &count)| count > 0)
+            .map(|(i, &count)| OpCount {
+                offset: positions[i].offset,
+                line: positions[i].line,
+                col: positions[i].col,
+                op: program[OpIndex::new(i)].symbol(),
+                count,
+            })
+            .collect();
+
+        let loops = self
+            .loops
+            .into_iter()
+            .map(|(start, iterations)| {
+                let pos = positions[start.index()];
+                LoopProfile {
+                    offset: pos.offset,
+                    line: pos.line,
+                    col: pos.col,
+                    entries: iterations.values().sum(),
+                    iterations,
+                }
+            })
+            .collect();
+
+        Profile {
+            version: PROFILE_VERSION,
+            program_hash: format!("{:016x}", program.hash()),
+            runs: 1,
+            counts,
+            loops,
+        }
+    }
+}
+
+/// A table of candidates for `superinstructions`, one per line.
+pub fn candidate_table(candidates: &[Candidate]) -> String {
+    let mut out = format!("{:<8} {:>12} {:>12}\n", "sequence", "count", "saved");
+    for c in candidates {
+        out.push_str(&format!(
+            "{:<8} {:>12} {:>12}\n",
+            c.sequence, c.count, c.saved
+        ));
+    }
+    out
+}
+
+/// Rust source listing the candidates, for maintainers experimenting with
+/// fused instructions in the dispatch loop.
+pub fn candidate_source(candidates: &[Candidate]) -> String {
+    let mut out = String::from(
+        "// Generated by `bf profile --emit-superinstructions`.\n\
+         // Candidate superinstructions with the dispatches each would have saved.\n\
+         pub const SUPERINSTRUCTIONS: &[(&str, u64)] = &[\n",
+    );
+    for c in candidates {
+        out.push_str(&format!("    ({:?}, {}),\n", c.sequence, c.saved));
+    }
+    out.push_str("];\n");
+    out
+}
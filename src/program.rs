@@ -0,0 +1,119 @@
+use std::fmt;
+use std::ops::Index;
+
+use crate::snapshot::Fnv;
+use crate::OpCode;
+
+/// Position of an instruction within a `Program`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct OpIndex(usize);
+
+impl OpIndex {
+    pub fn new(index: usize) -> Self {
+        OpIndex(index)
+    }
+
+    pub fn index(self) -> usize {
+        self.0
+    }
+
+    pub fn next(self) -> Option<Self> {
+        self.0.checked_add(1).map(OpIndex)
+    }
+}
+
+impl fmt::Display for OpIndex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// Where an instruction appears in the source text. Lines and columns are
+/// 1-based; `offset` is in bytes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SourcePos {
+    pub offset: usize,
+    pub line: usize,
+    pub col: usize,
+}
+
+impl fmt::Display for SourcePos {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.col)
+    }
+}
+
+/// Append-only instruction storage. Indices handed out by `push` stay valid
+/// for the life of the program. Only the parser builds programs, so every
+/// bracket has a partner.
+#[derive(Debug, Clone, Default)]
+pub struct Program {
+    ops: Vec<OpCode>,
+    positions: Vec<SourcePos>,
+    // The matching bracket of each `[` and `]`, resolved by `link`.
+    jumps: Vec<OpIndex>,
+}
+
+impl Program {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn push(&mut self, op: OpCode, pos: SourcePos) -> OpIndex {
+        self.ops.push(op);
+        self.positions.push(pos);
+        self.jumps.push(OpIndex(self.ops.len() - 1));
+        OpIndex(self.ops.len() - 1)
+    }
+
+    /// Records that the `[` at `start` and the `]` at `end` match.
+    pub(crate) fn link(&mut self, start: OpIndex, end: OpIndex) {
+        self.jumps[start.0] = end;
+        self.jumps[end.0] = start;
+    }
+
+    /// The bracket matching the one at `at`, as recorded by `link`.
+    #[inline]
+    pub fn partner(&self, at: OpIndex) -> OpIndex {
+        self.jumps[at.0]
+    }
+
+    pub fn get(&self, at: OpIndex) -> Option<&OpCode> {
+        self.ops.get(at.0)
+    }
+
+    pub fn ops(&self) -> &[OpCode] {
+        &self.ops
+    }
+
+    pub fn positions(&self) -> &[SourcePos] {
+        &self.positions
+    }
+
+    /// The program as brainfuck source, one symbol per instruction.
+    pub fn source(&self) -> String {
+        self.ops.iter().map(OpCode::symbol).collect()
+    }
+
+    /// A stable hash of the instructions, identifying the program in
+    /// recorded artifacts.
+    pub fn hash(&self) -> u64 {
+        let mut hash = Fnv::new();
+        hash.write(self.source().as_bytes());
+        hash.finish()
+    }
+
+    /// The index one past the last instruction, where execution halts.
+    #[cfg(feature = "debug-invariants")]
+    pub fn end(&self) -> OpIndex {
+        OpIndex(self.ops.len())
+    }
+}
+
+impl Index<OpIndex> for Program {
+    type Output = OpCode;
+
+    fn index(&self, at: OpIndex) -> &OpCode {
+        &self.ops[at.0]
+    }
+}
@@ -0,0 +1,70 @@
+//! Input typed at a terminal, asked for a line at a time with `--prompt`.
+
+use std::io::{self, prelude::*, stderr};
+
+/// Shows `prompt` on stderr whenever `,` needs input and none is left,
+/// then reads a whole line to hand out to the `,`s that follow.
+#[derive(Debug)]
+pub struct Prompted<R> {
+    inner: R,
+    prompt: String,
+    line: Vec<u8>,
+    /// How much of `line` has been handed out.
+    at: usize,
+    /// Whether the prompt is showing for a line still being read.
+    asked: bool,
+    eof: bool,
+}
+
+impl<R> Prompted<R> {
+    pub fn new(inner: R, prompt: String) -> Self {
+        Self {
+            inner,
+            prompt,
+            line: Vec::new(),
+            at: 0,
+            asked: false,
+            eof: false,
+        }
+    }
+}
+
+impl<R: Read> Read for Prompted<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.at == self.line.len() {
+            if self.eof {
+                return Ok(0);
+            }
+            if !self.asked {
+                self.line.clear();
+                self.at = 0;
+                let mut err = stderr().lock();
+                err.write_all(self.prompt.as_bytes())?;
+                err.flush()?;
+                self.asked = true;
+            }
+            // A read that would block leaves what's read so far for the
+            // next try, without asking again.
+            let mut byte = [0];
+            loop {
+                match self.inner.read(&mut byte)? {
+                    0 => {
+                        self.eof = true;
+                        break;
+                    }
+                    _ => {
+                        self.line.push(byte[0]);
+                        if byte[0] == b'\n' {
+                            break;
+                        }
+                    }
+                }
+            }
+            self.asked = false;
+        }
+        let n = buf.len().min(self.line.len() - self.at);
+        buf[..n].copy_from_slice(&self.line[self.at..self.at + n]);
+        self.at += n;
+        Ok(n)
+    }
+}
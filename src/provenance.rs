@@ -0,0 +1,195 @@
+use serde::{Deserialize, Serialize};
+
+use std::cell::Cell;
+use std::fs::File;
+use std::io::{self, prelude::*, BufReader, BufWriter};
+use std::path::Path;
+use std::rc::Rc;
+
+use bf::program::OpIndex;
+use bf::{Interpreter, OpCode};
+
+const PROVENANCE_VERSION: u32 = 1;
+
+/// How much unread input is counted before giving up, so endless input
+/// can't keep a finished run from exiting.
+pub const UNREAD_INPUT_LIMIT: u64 = 1 << 20;
+
+/// Describes `n` bytes of unread input, which may have been cut short at
+/// `UNREAD_INPUT_LIMIT`.
+pub fn unread_bytes(n: u64) -> String {
+    if n >= UNREAD_INPUT_LIMIT {
+        format!("at least {}", n)
+    } else {
+        n.to_string()
+    }
+}
+
+/// Which `.` wrote each byte of a run's output and which `,` read each byte
+/// of its input, recorded by `--provenance`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Provenance {
+    pub version: u32,
+    pub program: String,
+    /// `Program::hash` of the program, in hex.
+    pub program_hash: String,
+    /// One entry per output byte, in order.
+    pub bytes: Vec<Origin>,
+    /// One entry per input byte the program read, in order.
+    #[serde(default)]
+    pub input: Vec<Origin>,
+    /// Input still waiting when the program ended, if it could be counted,
+    /// up to `UNREAD_INPUT_LIMIT`.
+    #[serde(default)]
+    pub unread_input: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Origin {
+    pub value: u8,
+    /// Instructions executed before this one, so it matches the step
+    /// numbers of `bf trace query`.
+    pub step: u64,
+    pub offset: usize,
+    pub line: usize,
+    pub col: usize,
+}
+
+/// Which of a run's streams `Provenance::explain` looks in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stream {
+    Output,
+    Input,
+}
+
+impl Provenance {
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let file = BufReader::new(File::open(path)?);
+        let provenance: Self = serde_json::from_reader(file)?;
+        if provenance.version != PROVENANCE_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unsupported provenance version {}", provenance.version),
+            ));
+        }
+        Ok(provenance)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let file = BufWriter::new(File::create(path)?);
+        serde_json::to_writer(file, self)?;
+        Ok(())
+    }
+
+    /// Describes where byte `byte` of `stream` came from or went, counting
+    /// from 0.
+    pub fn explain(&self, stream: Stream, byte: usize) -> Result<String, String> {
+        let (origins, name, op, verb) = match stream {
+            Stream::Output => (&self.bytes, "output", '.', "written"),
+            Stream::Input => (&self.input, "input", ',', "read"),
+        };
+        let origin = origins.get(byte).ok_or_else(|| {
+            let mut e = format!(
+                "{} {} {} byte(s) of {}, so it has no byte {}",
+                self.program,
+                verb,
+                origins.len(),
+                name,
+                byte
+            );
+            if let (Stream::Input, Some(n)) = (stream, self.unread_input.filter(|&n| n > 0)) {
+                e.push_str(&format!("; {} more were left unread", unread_bytes(n)));
+            }
+            e
+        })?;
+        Ok(format!(
+            "{} byte {}: {:?} {} at step {} by '{}' at {}:{}:{} (offset {})\n",
+            name,
+            byte,
+            char::from(origin.value),
+            verb,
+            origin.step,
+            op,
+            self.program,
+            origin.line,
+            origin.col,
+            origin.offset
+        ))
+    }
+}
+
+/// Collects a `Provenance` from `Interpreter::execute_with` callbacks.
+#[derive(Debug, Default)]
+pub struct ProvenanceCollector {
+    steps: u64,
+    bytes: Vec<Origin>,
+    input: Vec<Origin>,
+    // Bytes read through `count` so far, to tell input from end of file.
+    read: Rc<Cell<u64>>,
+}
+
+impl ProvenanceCollector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Wraps the program's input so the collector sees which `,`s get a
+    /// byte.
+    pub fn count<R: Read>(&self, inner: R) -> Counted<R> {
+        Counted {
+            inner,
+            read: self.read.clone(),
+        }
+    }
+
+    pub fn record(&mut self, interp: &Interpreter, at: OpIndex) {
+        let origins = match interp.program()[at] {
+            OpCode::Output => &mut self.bytes,
+            OpCode::Input if self.read.get() > self.input.len() as u64 => &mut self.input,
+            _ => {
+                self.steps += 1;
+                return;
+            }
+        };
+        let pos = interp.program().positions()[at.index()];
+        origins.push(Origin {
+            value: interp.tape().value(),
+            step: self.steps,
+            offset: pos.offset,
+            line: pos.line,
+            col: pos.col,
+        });
+        self.steps += 1;
+    }
+
+    pub fn finish(
+        self,
+        program: String,
+        interp: &Interpreter,
+        unread_input: Option<u64>,
+    ) -> Provenance {
+        Provenance {
+            version: PROVENANCE_VERSION,
+            program,
+            program_hash: format!("{:016x}", interp.program().hash()),
+            bytes: self.bytes,
+            input: self.input,
+            unread_input,
+        }
+    }
+}
+
+/// A reader that tells its `ProvenanceCollector` how much it has read.
+#[derive(Debug)]
+pub struct Counted<R> {
+    inner: R,
+    read: Rc<Cell<u64>>,
+}
+
+impl<R: Read> Read for Counted<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.read.set(self.read.get() + n as u64);
+        Ok(n)
+    }
+}
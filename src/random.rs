@@ -0,0 +1,47 @@
+//! Deterministic pseudo-random numbers, so anything generated from a seed
+//! can be reproduced exactly on every platform.
+
+use std::io::{self, Read};
+
+/// SplitMix64: small, fast, and good enough for test data. Not for
+/// anything that needs to be unpredictable.
+#[derive(Debug, Clone)]
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Rng(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    pub fn fill(&mut self, buf: &mut [u8]) {
+        for chunk in buf.chunks_mut(8) {
+            let bytes = self.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+}
+
+/// An endless stream of bytes from an `Rng`.
+#[derive(Debug, Clone)]
+pub struct RandomInput(Rng);
+
+impl RandomInput {
+    pub fn new(seed: u64) -> Self {
+        RandomInput(Rng::new(seed))
+    }
+}
+
+impl Read for RandomInput {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.fill(buf);
+        Ok(buf.len())
+    }
+}
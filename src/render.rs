@@ -0,0 +1,210 @@
+use std::fmt;
+use std::fs::File;
+use std::io::{self, prelude::*, stderr};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use crate::tape::{BfArray, CellSize};
+
+/// How the visualizer lays out the tape.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Layout {
+    /// One row of the first `cells` cells, with a `^` under the pointer
+    /// even when it's past them. The original visualizer format.
+    Row,
+    /// `cells` cells in rows of `columns`, paged so the pointer is always
+    /// shown, each row with its indices above and markers below: `^` for
+    /// the pointer and `*` for the cell written last.
+    Grid,
+}
+
+/// Draws the tape as text for the visualizer.
+#[derive(Debug, Clone)]
+pub struct Renderer {
+    pub layout: Layout,
+    /// Cells shown at once.
+    pub cells: usize,
+    /// Cells per row, for `Layout::Grid`.
+    pub columns: usize,
+    /// Characters per cell. `Layout::Grid` widens them as needed to keep
+    /// columns apart.
+    pub width: usize,
+}
+
+impl Default for Renderer {
+    fn default() -> Self {
+        Self {
+            layout: Layout::Row,
+            cells: 7,
+            columns: 16,
+            width: 4,
+        }
+    }
+}
+
+impl Renderer {
+    /// Draws `tape` with the data pointer at `pointer`.
+    pub fn render<C: fmt::Display, W: Write>(
+        &self,
+        tape: &[C],
+        pointer: usize,
+        last_write: Option<usize>,
+        out: &mut W,
+    ) -> io::Result<()> {
+        match self.layout {
+            Layout::Row => self.render_row(tape, pointer, out),
+            Layout::Grid => self.render_grid(tape, pointer, last_write, out),
+        }
+    }
+
+    fn render_row<C: fmt::Display, W: Write>(
+        &self,
+        tape: &[C],
+        pointer: usize,
+        out: &mut W,
+    ) -> io::Result<()> {
+        let cells = &tape[..self.cells.clamp(1, tape.len())];
+        for i in 0..cells.len() {
+            write!(out, "{:>1$}", i, self.width)?;
+        }
+        writeln!(out)?;
+        for cell in cells {
+            write!(out, "{:>1$}", cell, self.width)?;
+        }
+        writeln!(out)?;
+        writeln!(out, "{0:>1$}", "^", (pointer + 1) * self.width)
+    }
+
+    fn render_grid<C: fmt::Display, W: Write>(
+        &self,
+        tape: &[C],
+        pointer: usize,
+        last_write: Option<usize>,
+        out: &mut W,
+    ) -> io::Result<()> {
+        let page = self.cells.max(1);
+        let first = pointer / page * page;
+        let last = (first + page).min(tape.len());
+        // Room for the largest index or value and a space.
+        let width = self.width.max(digits(last - 1).max(3) + 1);
+
+        for start in (first..last).step_by(self.columns.max(1)) {
+            let row = start..(start + self.columns.max(1)).min(last);
+            let mut line = String::new();
+            for i in row.clone() {
+                line.push_str(&format!("{:>1$}", i, width));
+            }
+            writeln!(out, "{}", line)?;
+
+            line.clear();
+            for i in row.clone() {
+                line.push_str(&format!("{:>1$}", tape[i], width));
+            }
+            writeln!(out, "{}", line)?;
+
+            line.clear();
+            for i in row {
+                let marker = if i == pointer {
+                    "^"
+                } else if Some(i) == last_write {
+                    "*"
+                } else {
+                    ""
+                };
+                line.push_str(&format!("{:>1$}", marker, width));
+            }
+            writeln!(out, "{}", line.trim_end())?;
+        }
+        Ok(())
+    }
+}
+
+fn digits(n: usize) -> usize {
+    n.to_string().len()
+}
+
+/// Where the visualizer draws the tape.
+#[derive(Debug, Clone)]
+pub enum Target {
+    /// Overwrite this file with each frame.
+    File(PathBuf),
+    /// Redraw in place on the terminal, via stderr.
+    Terminal,
+}
+
+/// The visualizer `Interpreter::wait` or `Interpreter::frame_rate` turns
+/// on.
+#[derive(Debug, Clone)]
+pub struct Visualizer {
+    pub renderer: Renderer,
+    pub target: Target,
+}
+
+impl Default for Visualizer {
+    fn default() -> Self {
+        Self {
+            renderer: Renderer::default(),
+            target: Target::File("visualizer".into()),
+        }
+    }
+}
+
+impl Visualizer {
+    pub fn draw(&self, tape: &BfArray, last_write: Option<usize>) -> io::Result<()> {
+        let mut frame = Vec::new();
+        let (all, pointer) = (0..tape.len(), tape.pointer());
+        match tape.cell_size() {
+            _ if tape.is_signed() => {
+                let numbers: Vec<i64> = tape
+                    .values_in(all)
+                    .into_iter()
+                    .map(|v| tape.number(v))
+                    .collect();
+                self.renderer
+                    .render(&numbers, pointer, last_write, &mut frame)?
+            }
+            CellSize::U8 => {
+                self.renderer
+                    .render(&tape.cells_in(all), pointer, last_write, &mut frame)?
+            }
+            _ => self
+                .renderer
+                .render(&tape.values_in(all), pointer, last_write, &mut frame)?,
+        }
+        match self.target {
+            Target::File(ref path) => File::create(path)?.write_all(&frame),
+            Target::Terminal => {
+                let mut err = stderr().lock();
+                // Home the cursor and clear the screen first.
+                err.write_all(b"\x1b[H\x1b[2J")?;
+                err.write_all(&frame)
+            }
+        }
+    }
+}
+
+/// Says when the next of `fps` frames a second is due.
+#[derive(Debug)]
+pub(crate) struct Pacer {
+    every: Duration,
+    next: Instant,
+}
+
+impl Pacer {
+    pub(crate) fn new(fps: u32) -> Self {
+        Self {
+            every: Duration::from_secs(1) / fps.max(1),
+            next: Instant::now(),
+        }
+    }
+
+    /// Whether a frame is due, counting it drawn if so.
+    pub(crate) fn due(&mut self) -> bool {
+        let now = Instant::now();
+        if now < self.next {
+            return false;
+        }
+        self.next = now + self.every;
+        true
+    }
+}
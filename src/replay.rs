@@ -0,0 +1,192 @@
+//! `bf replay --play`: steps through a recorded trace without the program,
+//! rebuilding the tape from the cell writes it records.
+
+use std::fmt::Write as _;
+use std::io::{self, prelude::*};
+use std::path::Path;
+use std::sync::atomic::Ordering;
+use std::thread;
+use std::time::Duration;
+
+use bf::interpreter::INTERRUPTED;
+use bf::render::{Layout, Renderer};
+
+use crate::trace::{Header, Step, TraceReader};
+
+const HELP: &str = "\
+commands:
+  s [N]      step forward N steps (default 1; a blank line steps once)
+  b [N]      step back N steps
+  g N        go to step N
+  o          run to the next output byte
+  c K        run to the next step that visits cell K
+  p [MS]     play, one step every MS milliseconds (default 100);
+             Ctrl+C pauses
+  h          show this help
+  q          quit
+^ marks the pointer and * the cell the last step wrote.
+";
+
+/// A trace's state after some number of its steps.
+#[derive(Debug)]
+pub struct Player {
+    header: Header,
+    steps: Vec<Step>,
+    /// Steps applied so far.
+    at: usize,
+    tape: Vec<u8>,
+    pointer: usize,
+    output: Vec<u8>,
+    renderer: Renderer,
+}
+
+impl Player {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let mut trace = TraceReader::open(path)?;
+        let mut steps = Vec::new();
+        while let Some(step) = trace.next_step()? {
+            if step.pointer as u64 >= trace.header.tape_len {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "cell write outside the recorded tape",
+                ));
+            }
+            steps.push(step);
+        }
+        Ok(Self {
+            tape: vec![0; trace.header.tape_len as usize],
+            header: trace.header,
+            steps,
+            at: 0,
+            pointer: 0,
+            output: Vec::new(),
+            renderer: Renderer {
+                layout: Layout::Grid,
+                cells: 32,
+                columns: 16,
+                width: 4,
+            },
+        })
+    }
+
+    /// Applies the next step. False at the end of the trace.
+    fn forward(&mut self) -> bool {
+        let step = match self.steps.get(self.at) {
+            Some(&step) => step,
+            None => return false,
+        };
+        self.pointer = step.pointer as usize;
+        self.tape[self.pointer] = step.value;
+        if self.header.op(step.ip) == Some('.') {
+            self.output.push(step.value);
+        }
+        self.at += 1;
+        true
+    }
+
+    /// Moves to just after step `n`, replaying from the start to go back.
+    pub fn seek(&mut self, n: usize) {
+        if n < self.at {
+            self.tape.iter_mut().for_each(|cell| *cell = 0);
+            self.pointer = 0;
+            self.output.clear();
+            self.at = 0;
+        }
+        while self.at < n && self.forward() {}
+    }
+
+    /// Runs forward until a step matching `stop` has been applied. False if
+    /// the trace ended first.
+    pub fn run_until(&mut self, mut stop: impl FnMut(&Header, &Step) -> bool) -> bool {
+        while let Some(&step) = self.steps.get(self.at) {
+            self.forward();
+            if stop(&self.header, &step) {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// The tape, the step that comes next and the output so far.
+    pub fn frame(&self) -> String {
+        let mut frame = format!("step {}/{}", self.at, self.steps.len());
+        match self.steps.get(self.at) {
+            Some(step) => write!(frame, ", next {}", self.header.describe(step.ip)).unwrap(),
+            None => frame.push_str(", end of trace"),
+        }
+        frame.push('\n');
+
+        let mut tape = Vec::new();
+        let last_write = self.at.checked_sub(1).and_then(|last| {
+            let step = self.steps[last];
+            matches!(self.header.op(step.ip), Some('+' | '-' | ','))
+                .then_some(step.pointer as usize)
+        });
+        self.renderer
+            .render(&self.tape, self.pointer, last_write, &mut tape)
+            .unwrap();
+        frame.push_str(&String::from_utf8_lossy(&tape));
+        writeln!(frame, "output: {:?}", String::from_utf8_lossy(&self.output)).unwrap();
+        frame
+    }
+}
+
+/// Reads commands from stdin until `q` or end of input.
+pub fn play(path: &Path) -> io::Result<()> {
+    let mut player = Player::open(path)?;
+    ctrlc::set_handler(|| INTERRUPTED.store(true, Ordering::Relaxed)).map_err(io::Error::other)?;
+
+    let mut out = io::stdout().lock();
+    write!(out, "{}(h for help) > ", player.frame())?;
+    out.flush()?;
+    for line in io::stdin().lock().lines() {
+        let line = line?;
+        let mut words = line.split_whitespace();
+        let command = words.next().unwrap_or("s");
+        let arg = match words.next().map(str::parse::<usize>).transpose() {
+            Ok(arg) => arg,
+            Err(_) => {
+                write!(out, "expected a number\n> ")?;
+                out.flush()?;
+                continue;
+            }
+        };
+
+        let note = match (command, arg) {
+            ("q", _) => return Ok(()),
+            ("h", _) => Some(HELP.to_owned()),
+            ("s", n) => {
+                player.seek(player.at + n.unwrap_or(1));
+                None
+            }
+            ("b", n) => {
+                player.seek(player.at.saturating_sub(n.unwrap_or(1)));
+                None
+            }
+            ("g", Some(n)) => {
+                player.seek(n);
+                None
+            }
+            ("o", None) => (!player.run_until(|header, step| header.op(step.ip) == Some('.')))
+                .then(|| "no more output\n".to_owned()),
+            ("c", Some(k)) => (!player.run_until(|_, step| step.pointer as usize == k))
+                .then(|| format!("cell {} isn't visited again\n", k)),
+            ("p", ms) => {
+                INTERRUPTED.store(false, Ordering::Relaxed);
+                let delay = Duration::from_millis(ms.unwrap_or(100) as u64);
+                while !INTERRUPTED.load(Ordering::Relaxed) && player.forward() {
+                    write!(out, "\x1b[H\x1b[2J{}", player.frame())?;
+                    out.flush()?;
+                    thread::sleep(delay);
+                }
+                INTERRUPTED
+                    .swap(false, Ordering::Relaxed)
+                    .then(|| "paused\n".to_owned())
+            }
+            _ => Some(format!("unknown command '{}' (h for help)\n", line.trim())),
+        };
+        write!(out, "{}{}> ", player.frame(), note.unwrap_or_default())?;
+        out.flush()?;
+    }
+    Ok(())
+}
@@ -0,0 +1,21 @@
+//! Summaries of repeated timings, as `bf bench` reports them.
+
+/// The `q` quantile of `sorted`, interpolating between neighbours.
+pub fn quantile(sorted: &[f64], q: f64) -> f64 {
+    let at = q * (sorted.len() - 1) as f64;
+    let (lo, hi) = (at.floor() as usize, at.ceil() as usize);
+    sorted[lo] + (sorted[hi] - sorted[lo]) * (at - lo as f64)
+}
+
+/// Sorts `samples` and returns those no more than 1.5 interquartile
+/// ranges outside the middle half, in order.
+pub fn without_outliers(samples: &mut [f64]) -> Vec<f64> {
+    samples.sort_by(f64::total_cmp);
+    let (q1, q3) = (quantile(samples, 0.25), quantile(samples, 0.75));
+    let fence = 1.5 * (q3 - q1);
+    samples
+        .iter()
+        .copied()
+        .filter(|&s| s >= q1 - fence && s <= q3 + fence)
+        .collect()
+}
@@ -0,0 +1,547 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::fs;
+use std::io::{self, prelude::*, stdout};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::{load, stdin};
+use bf::interpreter::INTERRUPTED;
+use bf::limits::Limits;
+use bf::schedule::ProgramStats;
+use bf::{Interpreter, OpCode, Status};
+
+// Bytes a program may write ahead of its readers' `,`.
+const QUEUE_CAPACITY: usize = 64;
+pub const DEFAULT_FUEL: usize = 1000;
+// How long to wait for more input once every program is waiting on stdin.
+const STDIN_WAIT: Duration = Duration::from_millis(10);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Port {
+    Stdio,
+    Program(usize),
+}
+
+/// Which programs run, who pipes to whom, and which programs share a tape.
+///
+/// In the text form each line is one of
+///
+/// ```text
+/// name = path/to/program.bf
+/// from -> to          # `stdin` and `stdout` name the terminal
+/// share name name...
+/// ```
+#[derive(Debug, Default)]
+pub struct Topology {
+    programs: Vec<(String, PathBuf)>,
+    links: Vec<(Port, Port)>,
+    shares: Vec<Vec<usize>>,
+}
+
+impl Topology {
+    /// Two programs, each one's output feeding the other's input.
+    pub fn pair(a: PathBuf, b: PathBuf) -> Self {
+        Self {
+            programs: vec![("a".into(), a), ("b".into(), b)],
+            links: vec![
+                (Port::Program(0), Port::Program(1)),
+                (Port::Program(1), Port::Program(0)),
+            ],
+            shares: Vec::new(),
+        }
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let base = path.parent().unwrap_or_else(|| Path::new(""));
+        Self::parse(&text, base).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    pub fn parse(text: &str, base: &Path) -> Result<Self, String> {
+        let mut topology = Self::default();
+        let mut pending = Vec::new();
+
+        for (n, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let err = |msg: &str| format!("line {}: {}", n + 1, msg);
+
+            if let Some((from, to)) = line.split_once("->") {
+                pending.push((n + 1, from.trim().to_owned(), to.trim().to_owned()));
+            } else if let Some((name, path)) = line.split_once('=') {
+                let name = name.trim();
+                if name.is_empty() || name == "stdin" || name == "stdout" {
+                    return Err(err("invalid program name"));
+                }
+                if topology.index_of(name).is_some() {
+                    return Err(err(&format!("program '{}' defined twice", name)));
+                }
+                topology
+                    .programs
+                    .push((name.to_owned(), base.join(path.trim())));
+            } else if let Some(names) = line.strip_prefix("share ") {
+                let group = names
+                    .split_whitespace()
+                    .map(|name| {
+                        topology
+                            .index_of(name)
+                            .ok_or_else(|| err(&format!("unknown program '{}'", name)))
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                topology.shares.push(group);
+            } else {
+                return Err(err("expected 'name = path', 'from -> to' or 'share ...'"));
+            }
+        }
+
+        for (n, from, to) in pending {
+            let port = |name: &str, stdio: &str| {
+                if name == stdio {
+                    Ok(Port::Stdio)
+                } else {
+                    topology
+                        .index_of(name)
+                        .map(Port::Program)
+                        .ok_or_else(|| format!("line {}: unknown program '{}'", n, name))
+                }
+            };
+            let link = (port(&from, "stdin")?, port(&to, "stdout")?);
+            topology.links.push(link);
+        }
+
+        if topology.programs.is_empty() {
+            return Err("no programs defined".into());
+        }
+        Ok(topology)
+    }
+
+    fn index_of(&self, name: &str) -> Option<usize> {
+        self.programs.iter().position(|(n, _)| n == name)
+    }
+}
+
+#[derive(Debug)]
+struct Links {
+    inbox: Vec<VecDeque<u8>>,
+    sources: Vec<Vec<Port>>,
+    targets: Vec<Vec<Port>>,
+    halted: Vec<bool>,
+    stdin: stdin::Stdin,
+    stdin_eof: bool,
+    /// Whether a program found no input on stdin yet, since last cleared.
+    stdin_waiting: bool,
+    transfers: u64,
+    sent: Vec<u64>,
+    received: Vec<u64>,
+    /// Most bytes any one program may write.
+    max_output: Option<u64>,
+}
+
+impl Links {
+    // A program's input is exhausted once every source has finished.
+    fn exhausted(&self, me: usize) -> bool {
+        self.sources[me].iter().all(|source| match *source {
+            Port::Stdio => self.stdin_eof,
+            Port::Program(i) => self.halted[i],
+        })
+    }
+}
+
+#[derive(Debug)]
+struct Endpoint<'a> {
+    links: &'a RefCell<Links>,
+    me: usize,
+}
+
+impl Read for Endpoint<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        let mut links = self.links.borrow_mut();
+        if let Some(byte) = links.inbox[self.me].pop_front() {
+            buf[0] = byte;
+            links.transfers += 1;
+            links.received[self.me] += 1;
+            return Ok(1);
+        }
+
+        if links.sources[self.me].contains(&Port::Stdio) && !links.stdin_eof {
+            match links.stdin.read(&mut buf[..1]) {
+                Ok(0) => links.stdin_eof = true,
+                Ok(n) => {
+                    links.transfers += 1;
+                    links.received[self.me] += n as u64;
+                    return Ok(n);
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => links.stdin_waiting = true,
+                Err(e) => return Err(e),
+            }
+        }
+
+        if links.exhausted(self.me) {
+            Ok(0)
+        } else {
+            Err(io::ErrorKind::WouldBlock.into())
+        }
+    }
+}
+
+impl Write for Endpoint<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut links = self.links.borrow_mut();
+        let links = &mut *links;
+        if let Some(limit) = links.max_output {
+            if links.sent[self.me] + buf.len() as u64 > limit {
+                return Err(io::Error::other(format!(
+                    "output limit of {} bytes exceeded",
+                    limit
+                )));
+            }
+        }
+        let targets = &links.targets[self.me];
+        if targets.is_empty() {
+            links.sent[self.me] += buf.len() as u64;
+            return Ok(buf.len());
+        }
+
+        let mut room = usize::MAX;
+        let mut live = false;
+        for target in targets {
+            match *target {
+                Port::Stdio => live = true,
+                Port::Program(i) if !links.halted[i] => {
+                    live = true;
+                    room = room.min(QUEUE_CAPACITY - links.inbox[i].len());
+                }
+                Port::Program(_) => {}
+            }
+        }
+
+        if !live {
+            return Err(io::ErrorKind::BrokenPipe.into());
+        }
+        if room == 0 {
+            return Err(io::ErrorKind::WouldBlock.into());
+        }
+
+        let n = room.min(buf.len());
+        for target in targets {
+            match *target {
+                Port::Stdio => stdout().write_all(&buf[..n])?,
+                Port::Program(i) if !links.halted[i] => links.inbox[i].extend(&buf[..n]),
+                Port::Program(_) => {}
+            }
+        }
+        links.transfers += 1;
+        links.sent[self.me] += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        stdout().flush()
+    }
+}
+
+/// Runs several programs in round-robin slices of `fuel` instructions,
+/// interleaved on the calling thread rather than each on its own.
+#[derive(Debug)]
+pub struct Scheduler {
+    names: Vec<String>,
+    interps: Vec<Interpreter>,
+    tape_of: Vec<Option<usize>>,
+    tapes: Vec<Vec<u8>>,
+    links: RefCell<Links>,
+    blocked: Vec<bool>,
+    stats: Vec<ProgramStats>,
+    fuel: usize,
+    livelock_steps: Option<u64>,
+    deadline: Option<Instant>,
+}
+
+impl Scheduler {
+    pub fn new(topology: Topology, fuel: usize, limits: &Limits) -> Self {
+        let n = topology.programs.len();
+        let mut sources = vec![Vec::new(); n];
+        let mut targets = vec![Vec::new(); n];
+        for &(from, to) in &topology.links {
+            if let Port::Program(i) = to {
+                sources[i].push(from);
+            }
+            if let Port::Program(i) = from {
+                targets[i].push(to);
+            }
+        }
+
+        let mut tape_of = vec![None; n];
+        let mut tapes = Vec::new();
+        for group in &topology.shares {
+            for &i in group {
+                tape_of[i] = Some(tapes.len());
+            }
+            tapes.push(Vec::new());
+        }
+
+        let (names, interps): (Vec<String>, _) = topology
+            .programs
+            .into_iter()
+            .map(|(name, path)| (name, load(path, limits).into()))
+            .unzip();
+        let stats = names
+            .iter()
+            .map(|name| ProgramStats {
+                program: name.clone(),
+                ..Default::default()
+            })
+            .collect();
+
+        let mut sched = Self {
+            names,
+            interps,
+            tape_of,
+            tapes,
+            links: RefCell::new(Links {
+                inbox: vec![VecDeque::new(); n],
+                sources,
+                targets,
+                halted: vec![false; n],
+                stdin: stdin::nonblocking(),
+                stdin_eof: false,
+                stdin_waiting: false,
+                transfers: 0,
+                sent: vec![0; n],
+                received: vec![0; n],
+                max_output: None,
+            }),
+            blocked: vec![false; n],
+            stats,
+            fuel: fuel.max(1),
+            livelock_steps: None,
+            deadline: None,
+        };
+
+        // Every member of a group starts out with its own fresh tape; keep
+        // one per group and hand it around.
+        for (i, tape) in sched.tape_of.iter().enumerate() {
+            if let Some(t) = *tape {
+                if sched.tapes[t].is_empty() {
+                    sched.interps[i].tape_mut().swap_cells(&mut sched.tapes[t]);
+                }
+            }
+        }
+        sched
+    }
+
+    /// Treats `steps` instructions without any I/O or halting, while some
+    /// program is blocked, as a livelock.
+    pub fn livelock_steps(mut self, steps: Option<u64>) -> Self {
+        self.livelock_steps = steps;
+        self
+    }
+
+    /// Stops the run with an error once `deadline` passes.
+    pub fn deadline(mut self, deadline: Option<Instant>) -> Self {
+        self.deadline = deadline;
+        self
+    }
+
+    /// Stops a program that runs any one entry into a loop `cap` times.
+    pub fn loop_cap(mut self, cap: Option<u64>) -> Self {
+        for interp in &mut self.interps {
+            interp.loop_cap = cap;
+        }
+        self
+    }
+
+    /// Stops a program that writes more than `limit` bytes.
+    pub fn max_output(self, limit: Option<usize>) -> Self {
+        self.links.borrow_mut().max_output = limit.map(|limit| limit as u64);
+        self
+    }
+
+    /// Runs until every program halts. Errors, stopping them all, if one
+    /// faults, they deadlock or livelock, the deadline passes or
+    /// `INTERRUPTED` is set.
+    pub fn run(&mut self) -> Result<(), String> {
+        let mut idle = 0u64;
+        let mut transfers = 0;
+
+        loop {
+            if INTERRUPTED.load(Ordering::Relaxed) {
+                return Err(self.report("Interrupted"));
+            }
+            if self.deadline.is_some_and(|d| Instant::now() >= d) {
+                return Err(self.report("Timed out"));
+            }
+            let mut live = false;
+            let mut progressed = false;
+            let mut halted = false;
+            self.links.borrow_mut().stdin_waiting = false;
+
+            for i in 0..self.interps.len() {
+                if self.links.borrow().halted[i] {
+                    continue;
+                }
+                live = true;
+
+                let start = Instant::now();
+                let (ran, status) = self.run_slice(i);
+                progressed |= ran > 0;
+                idle += ran as u64;
+                self.blocked[i] = matches!(status, Status::Blocked);
+
+                let stats = &mut self.stats[i];
+                let links = self.links.borrow();
+                stats.busy += start.elapsed().as_secs_f64();
+                stats.steps += ran as u64;
+                stats.slices += 1;
+                if self.blocked[i] {
+                    stats.blocked_polls += 1;
+                }
+                stats.bytes_sent = links.sent[i];
+                stats.bytes_received = links.received[i];
+                drop(links);
+                match status {
+                    Status::Running | Status::Blocked => continue,
+                    Status::Halted | Status::OutputClosed => {}
+                    // The first fault ends the whole run, as it would a
+                    // pipeline, rather than leaving its peers to deadlock.
+                    Status::Error(e) => return Err(format!("{}: {}", self.names[i], e)),
+                }
+                self.links.borrow_mut().halted[i] = true;
+                halted = true;
+            }
+
+            if !live {
+                return Ok(());
+            }
+            // Waiting on the terminal is neither a deadlock nor a livelock;
+            // the input may yet come.
+            let waiting = self.links.borrow().stdin_waiting;
+            if !progressed && !halted {
+                if !waiting {
+                    return Err(self.report("Deadlock detected"));
+                }
+                thread::sleep(STDIN_WAIT);
+            }
+
+            let now = self.links.borrow().transfers;
+            if halted || waiting || now != transfers {
+                transfers = now;
+                idle = 0;
+            }
+            if let Some(limit) = self.livelock_steps {
+                if idle >= limit && self.blocked.iter().any(|&b| b) {
+                    return Err(self.report(&format!(
+                        "Livelock detected: no I/O in {} instructions",
+                        idle
+                    )));
+                }
+            }
+        }
+    }
+
+    pub fn stats(&self) -> &[ProgramStats] {
+        &self.stats
+    }
+
+    /// A table of how the scheduler's time was shared between programs.
+    pub fn fairness_table(&self) -> String {
+        let total: u64 = self.stats().iter().map(|s| s.steps).sum();
+        let width = self.names.iter().map(String::len).max().unwrap_or(0).max(7);
+
+        let mut table = format!(
+            "{:<w$} {:>14} {:>7} {:>8} {:>8} {:>6} {:>10}\n",
+            "program",
+            "instructions",
+            "share",
+            "slices",
+            "blocked",
+            "util",
+            "busy",
+            w = width
+        );
+        for s in self.stats() {
+            let share = percent(s.steps, total);
+            let util = percent(s.steps, s.slices * self.fuel as u64);
+            table.push_str(&format!(
+                "{:<w$} {:>14} {:>6.1}% {:>8} {:>8} {:>5.1}% {:>8.1}ms\n",
+                s.program,
+                s.steps,
+                share,
+                s.slices,
+                s.blocked_polls,
+                util,
+                s.busy * 1000.0,
+                w = width
+            ));
+        }
+        table
+    }
+
+    fn report(&self, headline: &str) -> String {
+        let links = self.links.borrow();
+        let mut report = format!("{}:", headline);
+        for (i, interp) in self.interps.iter().enumerate() {
+            let state = if links.halted[i] {
+                "halted".to_owned()
+            } else {
+                let at = interp.pc();
+                let op = interp.program().get(at).map_or('?', OpCode::symbol);
+                match (self.blocked[i], op) {
+                    (true, ',') => format!("blocked reading at instruction {} (',')", at),
+                    (true, '.') => format!("blocked writing at instruction {} ('.')", at),
+                    _ => format!("running at instruction {} ('{}')", at, op),
+                }
+            };
+            report.push_str(&format!("\n  {}: {}", self.names[i], state));
+        }
+        report
+    }
+
+    fn run_slice(&mut self, i: usize) -> (usize, Status) {
+        if let Some(t) = self.tape_of[i] {
+            self.interps[i].tape_mut().swap_cells(&mut self.tapes[t]);
+        }
+
+        let mut endpoint = Endpoint {
+            links: &self.links,
+            me: i,
+        };
+        let mut writer = Endpoint {
+            links: &self.links,
+            me: i,
+        };
+
+        let mut ran = 0;
+        let status = loop {
+            if ran == self.fuel {
+                break Status::Running;
+            }
+            match self.interps[i].step(&mut endpoint, &mut writer) {
+                Status::Running => ran += 1,
+                status => break status,
+            }
+        };
+
+        if let Some(t) = self.tape_of[i] {
+            self.interps[i].tape_mut().swap_cells(&mut self.tapes[t]);
+        }
+        (ran, status)
+    }
+}
+
+fn percent(part: u64, whole: u64) -> f64 {
+    if whole == 0 {
+        0.0
+    } else {
+        part as f64 * 100.0 / whole as f64
+    }
+}
@@ -0,0 +1,22 @@
+//! Accounting for programs run side by side with `--pair` or `--topology`,
+//! printed by `--stats` and `--sched-stats`.
+
+use serde::Serialize;
+
+/// What one program did under the scheduler.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ProgramStats {
+    pub program: String,
+    /// Instructions run.
+    pub steps: u64,
+    /// Slices the scheduler gave it.
+    pub slices: u64,
+    /// Slices that ended with it waiting on a read or a full queue.
+    pub blocked_polls: u64,
+    /// Bytes it wrote, to other programs or stdout.
+    pub bytes_sent: u64,
+    /// Bytes it read, from other programs or stdin.
+    pub bytes_received: u64,
+    /// Seconds spent running its slices.
+    pub busy: f64,
+}
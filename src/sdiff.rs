@@ -0,0 +1,137 @@
+//! `bf sdiff`: compares two programs by what they do rather than how
+//! they're written.
+
+use std::fmt::Write as _;
+
+use bf::program::{Program, SourcePos};
+
+use crate::canon::{tokens, Token};
+
+/// Largest table of token pairs compared exactly. Bigger changes are shown
+/// as one hunk.
+const MAX_TABLE: usize = 1 << 22;
+
+/// A run of tokens that differ, by index range in each program.
+#[derive(Debug)]
+struct Hunk {
+    old: std::ops::Range<usize>,
+    new: std::ops::Range<usize>,
+}
+
+/// The hunks turning `old` into `new`, from a longest common subsequence.
+fn hunks<T: PartialEq>(old: &[T], new: &[T]) -> Vec<Hunk> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let (a, b) = (
+        &old[prefix..old.len() - suffix],
+        &new[prefix..new.len() - suffix],
+    );
+    if a.is_empty() && b.is_empty() {
+        return Vec::new();
+    }
+    if (a.len() + 1).saturating_mul(b.len() + 1) > MAX_TABLE {
+        return vec![Hunk {
+            old: prefix..prefix + a.len(),
+            new: prefix..prefix + b.len(),
+        }];
+    }
+
+    // lcs[i][j]: longest common subsequence of a[i..] and b[j..].
+    let width = b.len() + 1;
+    let mut lcs = vec![0u32; (a.len() + 1) * width];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i * width + j] = if a[i] == b[j] {
+                lcs[(i + 1) * width + j + 1] + 1
+            } else {
+                lcs[(i + 1) * width + j].max(lcs[i * width + j + 1])
+            };
+        }
+    }
+
+    let mut hunks: Vec<Hunk> = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            i += 1;
+            j += 1;
+            continue;
+        }
+        let (from_old, from_new) = (i, j);
+        if j == b.len() || (i < a.len() && lcs[(i + 1) * width + j] >= lcs[i * width + j + 1]) {
+            i += 1;
+        } else {
+            j += 1;
+        }
+        match hunks.last_mut() {
+            Some(hunk)
+                if hunk.old.end == prefix + from_old && hunk.new.end == prefix + from_new =>
+            {
+                hunk.old.end = prefix + i;
+                hunk.new.end = prefix + j;
+            }
+            _ => hunks.push(Hunk {
+                old: prefix + from_old..prefix + i,
+                new: prefix + from_new..prefix + j,
+            }),
+        }
+    }
+    hunks
+}
+
+/// Describes how `new` differs from `old` in behavior. True if it does.
+pub fn sdiff(old_name: &str, old: &Program, new_name: &str, new: &Program) -> (String, bool) {
+    let (old_tokens, new_tokens) = (tokens(old), tokens(new));
+    let strip = |tokens: &[(Token, SourcePos)]| -> Vec<Token> {
+        tokens.iter().map(|(token, _)| token.clone()).collect()
+    };
+    let hunks = hunks(&strip(&old_tokens), &strip(&new_tokens));
+
+    let mut report = String::new();
+    if hunks.is_empty() {
+        let how = if old.source() == new.source() {
+            "have the same instructions; any differences are comments or layout"
+        } else {
+            "are equivalent: their instructions differ only in runs that fold the same way"
+        };
+        writeln!(report, "{} and {} {}", old_name, new_name, how).unwrap();
+        return (report, false);
+    }
+
+    let at = |tokens: &[(Token, SourcePos)], index: usize| match tokens.get(index) {
+        Some((_, pos)) => pos.to_string(),
+        None => "end".into(),
+    };
+    let text = |tokens: &[(Token, SourcePos)], range: std::ops::Range<usize>| {
+        let mut text: String = tokens[range].iter().map(|(t, _)| t.to_string()).collect();
+        if text.len() > 60 {
+            text.truncate(56);
+            text.push_str(" ...");
+        }
+        text
+    };
+    for hunk in &hunks {
+        writeln!(
+            report,
+            "@ {} {}, {} {}",
+            old_name,
+            at(&old_tokens, hunk.old.start),
+            new_name,
+            at(&new_tokens, hunk.new.start)
+        )
+        .unwrap();
+        if !hunk.old.is_empty() {
+            writeln!(report, "- {}", text(&old_tokens, hunk.old.clone())).unwrap();
+        }
+        if !hunk.new.is_empty() {
+            writeln!(report, "+ {}", text(&new_tokens, hunk.new.clone())).unwrap();
+        }
+    }
+    writeln!(report, "{} semantic change(s)", hunks.len()).unwrap();
+    (report, true)
+}
@@ -0,0 +1,213 @@
+use bf::limits::Limits;
+use bf::{parse, Interpreter, Status};
+
+/// Daniel B. Cristofani's brainfuck self-interpreter. It reads a program
+/// up to a `!`, then runs it on the rest of its input.
+const DBFI: &str = include_str!("../programs/dbfi.b");
+
+const HELLO: &str = "++++++++[>++++[>++>+++>+++>+<<<<-]>+>+>->>+[<]<-]>>.>---.+++++++..+++.>>.<-.<.+++.------.--------.>>+.>++.";
+
+struct Sample {
+    name: &'static str,
+    program: String,
+    input: String,
+    expected: String,
+}
+
+fn samples() -> Vec<Sample> {
+    let depth = 32;
+    let text = "The quick brown fox jumps over the lazy dog.\n".repeat(8);
+    vec![
+        Sample {
+            name: "hello",
+            program: HELLO.into(),
+            input: String::new(),
+            expected: "Hello World!\n".into(),
+        },
+        Sample {
+            name: "cat",
+            program: ",[.,]".into(),
+            input: text.clone(),
+            expected: text,
+        },
+        Sample {
+            name: "nesting",
+            program: format!(
+                "+{}>{}.[-]<{}",
+                "[>+".repeat(depth),
+                "+".repeat(33),
+                "<-]".repeat(depth)
+            ),
+            input: String::new(),
+            expected: "!".into(),
+        },
+    ]
+}
+
+fn run(program: &str, input: &str) -> Result<String, String> {
+    let output = run_bytes(program, input.as_bytes())?;
+    Ok(String::from_utf8_lossy(&output).into_owned())
+}
+
+fn run_bytes(program: &str, mut input: &[u8]) -> Result<Vec<u8>, String> {
+    let mut interp =
+        Interpreter::new(parse(program.bytes(), &Limits::default()).map_err(|e| e.to_string())?);
+    let mut output = Vec::new();
+    loop {
+        match interp.step(&mut input, &mut output) {
+            Status::Running => {}
+            Status::Halted => return Ok(output),
+            Status::Blocked => return Err("blocked on in-memory I/O".into()),
+            Status::OutputClosed => return Err("in-memory output closed".into()),
+            Status::Error(e) => return Err(e),
+        }
+    }
+}
+
+/// Runs each sample directly and under the self-interpreter, returning a
+/// report and whether every run produced the expected output.
+pub fn selftest() -> (String, bool) {
+    let mut report = String::new();
+    let mut passed = true;
+    for sample in samples() {
+        let dbfi_input = format!("{}!{}", sample.program, sample.input);
+        for (how, result) in [
+            ("direct", run(&sample.program, &sample.input)),
+            ("dbfi", run(DBFI, &dbfi_input)),
+        ] {
+            let verdict = match result {
+                Ok(ref out) if *out == sample.expected => "ok".to_owned(),
+                Ok(out) => format!("FAILED: expected {:?}, got {:?}", sample.expected, out),
+                Err(e) => format!("FAILED: {}", e),
+            };
+            passed &= verdict == "ok";
+            report.push_str(&format!("{:<8} {:<6} {}\n", sample.name, how, verdict));
+        }
+    }
+    (report, passed)
+}
+
+/// The semantics a torture test's expectations assume. Each test lists the
+/// outcome under every configuration the interpreter supports.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Config {
+    /// 8-bit wrapping cells, 65535 cells starting at the left edge, and
+    /// `,` at end of input storing 0.
+    Standard,
+}
+
+const CONFIGS: &[Config] = &[Config::Standard];
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expect {
+    Output(Vec<u8>),
+    /// The run stops with an error message containing this text.
+    Error(&'static str),
+}
+
+struct Torture {
+    name: &'static str,
+    program: String,
+    input: &'static [u8],
+    expect: fn(Config) -> Expect,
+}
+
+fn output(bytes: &[u8]) -> Expect {
+    Expect::Output(bytes.to_vec())
+}
+
+fn tortures() -> Vec<Torture> {
+    vec![
+        // Daniel B. Cristofani's checks for comments, `[]` at the start,
+        // and skipped loops containing brackets.
+        Torture {
+            name: "obscure",
+            program: "[]++++++++++[>>+>+>++++++[<<+<+++>>>-]<<<<-]\"A*$\";?@![#>>+<<]>[>>]<<<<[>++<[-]]>.>.".into(),
+            input: b"",
+            expect: |_| output(b"H\n"),
+        },
+        // Prints "#" only with at least 30000 cells.
+        Torture {
+            name: "tape-30000",
+            program: "++++[>++++++<-]>[>+++++>+++++++<<-]>>++++<[[>[[>>+<<-]<]>>>-]>-[>+>+<<-]>]+++++[>+++++++<<++>-]>.<<.".into(),
+            input: b"",
+            expect: |_| output(b"#\n"),
+        },
+        Torture {
+            name: "wrap-down",
+            program: "-.".into(),
+            input: b"",
+            expect: |_| output(&[255]),
+        },
+        Torture {
+            name: "wrap-up",
+            program: format!("{}[.]+.", "+".repeat(256)),
+            input: b"",
+            expect: |_| output(&[1]),
+        },
+        Torture {
+            name: "eof",
+            program: ",+.,+.".into(),
+            input: b"a",
+            expect: |_| output(b"b\x01"),
+        },
+        Torture {
+            name: "left-edge",
+            program: "+<".into(),
+            input: b"",
+            expect: |_| Expect::Error("Pointer access violation"),
+        },
+        Torture {
+            name: "right-edge",
+            program: format!("{}+.>", ">".repeat(65534)),
+            input: b"",
+            expect: |_| Expect::Error("Pointer access violation"),
+        },
+        Torture {
+            name: "deep-skip",
+            program: format!("{}{}+.", "[".repeat(10_000), "]".repeat(10_000)),
+            input: b"",
+            expect: |_| output(&[1]),
+        },
+        Torture {
+            name: "pointer-dance",
+            program: format!("{}+[<]>.", ">+".repeat(1000)),
+            input: b"",
+            expect: |_| output(&[1]),
+        },
+    ]
+}
+
+fn outcome(result: Result<Vec<u8>, String>, expect: &Expect) -> bool {
+    match (result, expect) {
+        (Ok(out), Expect::Output(expected)) => out == *expected,
+        (Err(e), Expect::Error(expected)) => e.contains(expected),
+        _ => false,
+    }
+}
+
+/// Runs every torture test under every configuration, in the same form as
+/// `selftest`.
+pub fn torture() -> (String, bool) {
+    let mut report = String::new();
+    let mut passed = true;
+    for &config in CONFIGS {
+        for test in tortures() {
+            let expect = (test.expect)(config);
+            let result = run_bytes(&test.program, test.input);
+            let verdict = if outcome(result.clone(), &expect) {
+                "ok".to_owned()
+            } else {
+                passed = false;
+                format!("FAILED: expected {:?}, got {:?}", expect, result)
+            };
+            report.push_str(&format!(
+                "{:<14} {:<9} {}\n",
+                test.name,
+                format!("{:?}", config).to_lowercase(),
+                verdict
+            ));
+        }
+    }
+    (report, passed)
+}
@@ -0,0 +1,161 @@
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+
+use std::convert::TryFrom;
+use std::io::{self, prelude::*};
+
+use crate::compress::{self, Compression, Encoder};
+use crate::program::OpIndex;
+
+const MAGIC: &[u8; 4] = b"BFSN";
+pub const VERSION: u16 = 2;
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// A copy of the interpreter's observable state at one point in execution.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    pub tape: Vec<u8>,
+    pub pointer: usize,
+    pub ip: OpIndex,
+    /// The tape's `BfArray::settings`, which a reader must share to make
+    /// sense of the cells.
+    pub settings: String,
+}
+
+impl Snapshot {
+    /// A hash of the tape and both pointers that is stable across runs,
+    /// platforms and builds (FNV-1a over a fixed little-endian layout).
+    pub fn digest(&self) -> u64 {
+        Self::digest_of(&self.tape, self.pointer, self.ip)
+    }
+
+    /// `digest` without having to copy the tape into a snapshot first.
+    pub fn digest_of(tape: &[u8], pointer: usize, ip: OpIndex) -> u64 {
+        let mut hash = Fnv::new();
+        hash.write(&(ip.index() as u64).to_le_bytes());
+        hash.write(&(pointer as u64).to_le_bytes());
+        hash.write(&(tape.len() as u64).to_le_bytes());
+        hash.write(tape);
+        hash.finish()
+    }
+
+    /// Writes the snapshot to `out`, compressed with `compression`, all
+    /// integers little-endian: magic "BFSN", version u16, settings (u32
+    /// length + UTF-8), the ip, pointer and tape length as u64s, the tape,
+    /// then its `digest` as a u64.
+    pub fn write_to<W: Write>(&self, out: W, compression: Compression) -> io::Result<W> {
+        let mut out = Encoder::new(out, compression)?;
+        out.write_all(MAGIC)?;
+        out.write_u16::<LittleEndian>(VERSION)?;
+        out.write_u32::<LittleEndian>(self.settings.len() as u32)?;
+        out.write_all(self.settings.as_bytes())?;
+        out.write_u64::<LittleEndian>(self.ip.index() as u64)?;
+        out.write_u64::<LittleEndian>(self.pointer as u64)?;
+        out.write_u64::<LittleEndian>(self.tape.len() as u64)?;
+        out.write_all(&self.tape)?;
+        out.write_u64::<LittleEndian>(self.digest())?;
+        out.finish()
+    }
+
+    /// Reads back a snapshot `write_to` wrote, however it was compressed.
+    /// Errors if it was taken with other `settings` than `expected`, unless
+    /// `allow_mismatch`, or if its digest doesn't match what was read.
+    pub fn read_from<R: BufRead + 'static>(
+        input: R,
+        expected: &str,
+        allow_mismatch: bool,
+    ) -> io::Result<Self> {
+        let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidData, msg);
+        let mut input = compress::decoder(input)?;
+        let mut magic = [0; 4];
+        input.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid("not a snapshot file".into()));
+        }
+        let version = input.read_u16::<LittleEndian>()?;
+        if version != VERSION {
+            return Err(invalid(format!(
+                "unsupported snapshot version {} (expected {})",
+                version, VERSION
+            )));
+        }
+        let len = input.read_u32::<LittleEndian>()?;
+        let mut settings = Vec::new();
+        input
+            .by_ref()
+            .take(u64::from(len))
+            .read_to_end(&mut settings)?;
+        if settings.len() != len as usize {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        let settings = String::from_utf8(settings).map_err(|e| invalid(e.to_string()))?;
+        if settings != expected && !allow_mismatch {
+            return Err(invalid(format!(
+                "snapshot taken with {}, not {}",
+                shown(&settings),
+                shown(expected)
+            )));
+        }
+        let mut next = || -> io::Result<usize> {
+            usize::try_from(input.read_u64::<LittleEndian>()?)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+        };
+        let (ip, pointer, len) = (next()?, next()?, next()?);
+        let mut tape = Vec::new();
+        input.by_ref().take(len as u64).read_to_end(&mut tape)?;
+        if tape.len() != len {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        let snapshot = Self {
+            tape,
+            pointer,
+            ip: OpIndex::new(ip),
+            settings,
+        };
+        let digest = input.read_u64::<LittleEndian>()?;
+        if digest != snapshot.digest() {
+            return Err(invalid(format!(
+                "snapshot digest {:016x} doesn't match its contents ({:016x})",
+                digest,
+                snapshot.digest()
+            )));
+        }
+        Ok(snapshot)
+    }
+}
+
+fn shown(settings: &str) -> &str {
+    if settings.is_empty() {
+        "the default settings"
+    } else {
+        settings
+    }
+}
+
+/// 64-bit FNV-1a, used wherever a hash must be stable between builds.
+#[derive(Debug, Clone)]
+pub struct Fnv(u64);
+
+impl Default for Fnv {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Fnv {
+    pub fn new() -> Self {
+        Fnv(FNV_OFFSET)
+    }
+
+    pub fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= u64::from(byte);
+            self.0 = self.0.wrapping_mul(FNV_PRIME);
+        }
+    }
+
+    pub fn finish(&self) -> u64 {
+        self.0
+    }
+}
@@ -0,0 +1,103 @@
+//! The interpreter's state in formats other tools can read without linking
+//! the crate, from `Interpreter::export_state`.
+//!
+//! JSON is an object like this one. `tape` stops after the last cell that
+//! is nonzero or under the pointer, and cells are shown as numbers, signed
+//! when the tape is. `loops` holds the index of the `[` of each loop the
+//! next instruction is inside, outermost first:
+//!
+//! ```json
+//! {
+//!   "ip": 7,
+//!   "pointer": 1,
+//!   "tape": [0, 65],
+//!   "loops": [2],
+//!   "stats": { "instructions": 12, "tape_len": 30000, "nonzero_cells": 1 }
+//! }
+//! ```
+//!
+//! DOT is a graph of the same `tape` as one record node, with an edge from
+//! a `pointer` node to the cell under it and the ip in the graph's label.
+
+use serde::Serialize;
+
+use std::fmt::Write;
+
+use crate::parser::OpCode;
+use crate::program::OpIndex;
+use crate::Interpreter;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum StateFormat {
+    Json,
+    Dot,
+}
+
+#[derive(Debug, Serialize)]
+struct State {
+    ip: usize,
+    pointer: usize,
+    tape: Vec<i64>,
+    loops: Vec<usize>,
+    stats: Stats,
+}
+
+#[derive(Debug, Serialize)]
+struct Stats {
+    instructions: usize,
+    tape_len: usize,
+    nonzero_cells: usize,
+}
+
+impl State {
+    fn of(interp: &Interpreter) -> Self {
+        let (program, tape, ip) = (interp.program(), interp.tape(), interp.pc());
+        let values = tape.values_in(0..tape.len());
+        let shown = values
+            .iter()
+            .rposition(|&v| v != 0)
+            .map_or(0, |last| last + 1)
+            .max(tape.pointer() + 1);
+        let loops = (0..ip.index())
+            .map(OpIndex::new)
+            .filter(|&at| program.get(at) == Some(&OpCode::JmpStart) && program.partner(at) >= ip)
+            .map(OpIndex::index)
+            .collect();
+        State {
+            ip: ip.index(),
+            pointer: tape.pointer(),
+            tape: values[..shown].iter().map(|&v| tape.number(v)).collect(),
+            loops,
+            stats: Stats {
+                instructions: program.positions().len(),
+                tape_len: tape.len(),
+                nonzero_cells: values.iter().filter(|&&v| v != 0).count(),
+            },
+        }
+    }
+
+    fn dot(&self) -> String {
+        let cells: Vec<String> = self
+            .tape
+            .iter()
+            .enumerate()
+            .map(|(i, value)| format!("<c{}> {}", i, value))
+            .collect();
+        let mut dot = String::from("digraph state {\n");
+        let _ = writeln!(dot, "  label=\"ip {}\";", self.ip);
+        dot.push_str("  rankdir=LR;\n");
+        let _ = writeln!(dot, "  tape [shape=record, label=\"{}\"];", cells.join("|"));
+        dot.push_str("  pointer [shape=plaintext];\n");
+        let _ = writeln!(dot, "  pointer -> tape:c{};", self.pointer);
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+pub(crate) fn export(interp: &Interpreter, format: StateFormat) -> String {
+    let state = State::of(interp);
+    match format {
+        StateFormat::Json => serde_json::to_string_pretty(&state).unwrap() + "\n",
+        StateFormat::Dot => state.dot(),
+    }
+}
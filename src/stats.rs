@@ -0,0 +1,170 @@
+use serde::Serialize;
+
+use std::fmt;
+use std::fs;
+use std::time::Duration;
+
+use bf::program::OpIndex;
+use bf::{Interpreter, OpCode};
+
+const PAGE_SIZE: usize = 4096;
+
+/// Resource usage of one program run, printed by `--stats`.
+#[derive(Debug, Clone, Serialize)]
+pub struct RunStats {
+    pub program: String,
+    pub instructions: Instructions,
+    pub output_bytes: u64,
+    /// `,` executions, including those that hit end of input.
+    pub input_reads: u64,
+    pub tape_pages_touched: usize,
+    pub tape_pages: usize,
+    /// High-water resident set size of the whole process, where the
+    /// platform reports it.
+    pub peak_rss_bytes: Option<u64>,
+    pub timing: Timing,
+    /// Why the run stopped early, if it did.
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Instructions {
+    pub total: u64,
+    pub moves: u64,
+    pub arithmetic: u64,
+    pub io: u64,
+    pub loops: u64,
+}
+
+/// Seconds spent in each phase.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Timing {
+    pub parse: f64,
+    pub optimize: f64,
+    pub execute: f64,
+}
+
+/// Collects `RunStats` from `Interpreter::execute_with` callbacks.
+#[derive(Debug)]
+pub struct StatsCollector {
+    instructions: Instructions,
+    output_bytes: u64,
+    input_reads: u64,
+    // Pages from `first_page` on, numbered from the one holding the start
+    // cell, so a tape that grows left keeps its numbering.
+    touched: Vec<bool>,
+    first_page: isize,
+}
+
+impl StatsCollector {
+    pub fn new(interp: &Interpreter) -> Self {
+        let mut touched = vec![false; interp.tape().len().div_ceil(PAGE_SIZE)];
+        touched[interp.tape().pointer() / PAGE_SIZE] = true;
+        Self {
+            instructions: Instructions::default(),
+            output_bytes: 0,
+            input_reads: 0,
+            touched,
+            first_page: 0,
+        }
+    }
+
+    pub fn record(&mut self, interp: &Interpreter, at: OpIndex) {
+        let counts = &mut self.instructions;
+        counts.total += 1;
+        match interp.program()[at] {
+            OpCode::MoveForward | OpCode::MoveBack => {
+                counts.moves += 1;
+                let page = interp.tape().position().div_euclid(PAGE_SIZE as isize);
+                if page < self.first_page {
+                    let grown = (self.first_page - page) as usize;
+                    self.touched.splice(0..0, std::iter::repeat_n(false, grown));
+                    self.first_page = page;
+                }
+                let index = (page - self.first_page) as usize;
+                if index >= self.touched.len() {
+                    // The tape grew.
+                    self.touched.resize(index + 1, false);
+                }
+                self.touched[index] = true;
+            }
+            OpCode::Increment | OpCode::Decrement => counts.arithmetic += 1,
+            OpCode::Output => {
+                counts.io += 1;
+                self.output_bytes += 1;
+            }
+            OpCode::Input => {
+                counts.io += 1;
+                self.input_reads += 1;
+            }
+            OpCode::JmpStart | OpCode::JmpEnd => counts.loops += 1,
+        }
+    }
+
+    pub fn finish(
+        self,
+        program: String,
+        parse: Duration,
+        execute: Duration,
+        error: Option<String>,
+    ) -> RunStats {
+        RunStats {
+            program,
+            instructions: self.instructions,
+            output_bytes: self.output_bytes,
+            input_reads: self.input_reads,
+            tape_pages_touched: self.touched.iter().filter(|&&t| t).count(),
+            tape_pages: self.touched.len(),
+            peak_rss_bytes: peak_rss(),
+            timing: Timing {
+                parse: parse.as_secs_f64(),
+                optimize: 0.0,
+                execute: execute.as_secs_f64(),
+            },
+            error,
+        }
+    }
+}
+
+fn peak_rss() -> Option<u64> {
+    let status = fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|l| l.starts_with("VmHWM:"))?;
+    let kib: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kib * 1024)
+}
+
+impl fmt::Display for RunStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let i = &self.instructions;
+        writeln!(f, "{}:", self.program)?;
+        writeln!(
+            f,
+            "  instructions: {} (moves {}, arithmetic {}, io {}, loops {})",
+            i.total, i.moves, i.arithmetic, i.io, i.loops
+        )?;
+        writeln!(
+            f,
+            "  io: {} bytes out, {} reads in",
+            self.output_bytes, self.input_reads
+        )?;
+        writeln!(
+            f,
+            "  tape: {} of {} pages touched",
+            self.tape_pages_touched, self.tape_pages
+        )?;
+        if let Some(rss) = self.peak_rss_bytes {
+            writeln!(f, "  peak rss: {} KiB", rss / 1024)?;
+        }
+        if let Some(ref error) = self.error {
+            writeln!(f, "  error: {}", error)?;
+        }
+        let t = &self.timing;
+        writeln!(
+            f,
+            "  time: parse {:.3}ms, optimize {:.3}ms, execute {:.3}ms",
+            t.parse * 1e3,
+            t.optimize * 1e3,
+            t.execute * 1e3
+        )
+    }
+}
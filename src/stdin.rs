@@ -0,0 +1,101 @@
+//! Stdin that a deadline or Ctrl+C can cut off while a program waits on it.
+
+#[cfg(unix)]
+use std::io::{self, prelude::*, BufReader};
+
+/// How long a read waits for input before letting the interpreter check
+/// whether it should stop.
+#[cfg(unix)]
+const POLL_MS: i32 = 50;
+
+/// Stdin, reporting `WouldBlock` when no input comes for a while so the
+/// interpreter can check its deadline and `INTERRUPTED` between waits.
+#[cfg(unix)]
+#[derive(Debug)]
+pub struct Stdin {
+    reader: BufReader<Fd>,
+    /// Milliseconds to wait for input before giving up on a read.
+    wait: i32,
+}
+
+#[cfg(not(unix))]
+pub type Stdin = std::io::StdinLock<'static>;
+
+/// File descriptor 0, read without the buffering of `io::Stdin`, whose
+/// buffer `poll` can't see.
+#[cfg(unix)]
+#[derive(Debug)]
+struct Fd;
+
+#[cfg(unix)]
+impl Read for Fd {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        // SAFETY: `buf` is valid for writes of `buf.len()` bytes.
+        let n = unsafe { libc::read(0, buf.as_mut_ptr().cast(), buf.len()) };
+        if n < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(n as usize)
+    }
+}
+
+#[cfg(unix)]
+impl Read for Stdin {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.reader.buffer().is_empty() && !ready(self.wait)? {
+            return Err(io::ErrorKind::WouldBlock.into());
+        }
+        self.reader.read(buf)
+    }
+}
+
+/// Whether stdin has input or has ended, waiting up to `wait` ms to see.
+#[cfg(unix)]
+fn ready(wait: i32) -> io::Result<bool> {
+    let mut fd = libc::pollfd {
+        fd: 0,
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    // SAFETY: `fd` is one valid `pollfd`.
+    match unsafe { libc::poll(&mut fd, 1, wait) } {
+        n if n < 0 => {
+            let e = io::Error::last_os_error();
+            match e.kind() {
+                // A signal such as Ctrl+C; let the interpreter look.
+                io::ErrorKind::Interrupted => Ok(false),
+                _ => Err(e),
+            }
+        }
+        n => Ok(n > 0),
+    }
+}
+
+/// Stdin for programs to read, cancellable while it waits on Unix.
+#[cfg(unix)]
+pub fn input() -> Stdin {
+    Stdin {
+        reader: BufReader::new(Fd),
+        wait: POLL_MS,
+    }
+}
+
+/// Stdin that reports `WouldBlock` at once when no input is ready, for
+/// callers with other work to get on with. Blocks as usual off Unix.
+#[cfg(unix)]
+pub fn nonblocking() -> Stdin {
+    Stdin {
+        reader: BufReader::new(Fd),
+        wait: 0,
+    }
+}
+
+#[cfg(not(unix))]
+pub fn input() -> Stdin {
+    std::io::stdin().lock()
+}
+
+#[cfg(not(unix))]
+pub fn nonblocking() -> Stdin {
+    input()
+}
@@ -0,0 +1,28 @@
+//! Snippets built into the binary, available to `@include <std/NAME>`.
+
+const SNIPPETS: &[(&str, &str)] = &[
+    ("divmod", include_str!("../programs/std/divmod.b")),
+    ("memcpy", include_str!("../programs/std/memcpy.b")),
+    ("multiply", include_str!("../programs/std/multiply.b")),
+    (
+        "print-decimal",
+        include_str!("../programs/std/print-decimal.b"),
+    ),
+];
+
+pub fn get(name: &str) -> Option<&'static str> {
+    SNIPPETS
+        .iter()
+        .find(|&&(n, _)| n == name)
+        .map(|&(_, source)| source)
+}
+
+/// Every snippet's name with the first line of its description.
+pub fn list() -> String {
+    let mut out = String::new();
+    for (name, source) in SNIPPETS {
+        let summary = source.lines().next().unwrap_or("");
+        out.push_str(&format!("std/{:<16} {}\n", name, summary));
+    }
+    out
+}
@@ -0,0 +1,831 @@
+use byteorder::WriteBytesExt;
+#[cfg(feature = "bignum")]
+use num_bigint::BigUint;
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt;
+use std::io::prelude::*;
+use std::ops::Range;
+
+use crate::parser::OpCode;
+
+/// Cells on the tape unless a size is given.
+pub const ARRAY_SIZE: usize = u16::MAX as usize;
+
+/// Cells per page of a sparse tape.
+const PAGE_SIZE: usize = 4096;
+
+#[derive(Debug)]
+enum ModifyDirection {
+    Up,
+    Down,
+}
+
+#[derive(Debug)]
+enum JumpFrom {
+    Start,
+    End,
+}
+
+#[derive(Debug)]
+pub(crate) enum Action {
+    JumpForward,
+    JumpBack,
+    Block,
+    OutputClosed,
+    Exit(String),
+    None,
+}
+
+/// What happens when the data pointer moves off the tape.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Bounds {
+    /// Stop with an error.
+    #[default]
+    Error,
+    /// Come back on at the other end.
+    Wrap,
+    /// Add cells past the end as needed. Moving before the first cell is
+    /// still an error.
+    Grow,
+    /// Add cells at either end as needed, so `<` from the first cell
+    /// reaches cell -1.
+    Unbounded,
+}
+
+/// How many bits each cell holds. Cells wrap around at `max`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, clap::ValueEnum)]
+pub enum CellSize {
+    #[default]
+    #[value(name = "8")]
+    U8,
+    #[value(name = "16")]
+    U16,
+    #[value(name = "32")]
+    U32,
+}
+
+impl CellSize {
+    /// The largest value a cell holds.
+    pub fn max(self) -> u32 {
+        match self {
+            CellSize::U8 => u8::MAX.into(),
+            CellSize::U16 => u16::MAX.into(),
+            CellSize::U32 => u32::MAX,
+        }
+    }
+
+    /// `raw` read as a two's complement number of this many bits.
+    pub fn signed(self, raw: u32) -> i32 {
+        match self {
+            CellSize::U8 => raw as u8 as i8 as i32,
+            CellSize::U16 => raw as u16 as i16 as i32,
+            CellSize::U32 => raw as i32,
+        }
+    }
+
+    /// Bytes a cell takes up.
+    pub fn bytes(self) -> usize {
+        match self {
+            CellSize::U8 => 1,
+            CellSize::U16 => 2,
+            CellSize::U32 => 4,
+        }
+    }
+}
+
+/// What `+` on a cell at its largest value and `-` on a zero cell do.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Overflow {
+    /// Go round to zero or the largest value.
+    #[default]
+    Wrap,
+    /// Stay put.
+    Saturate,
+    /// Stop the program, to catch arithmetic it didn't mean to wrap.
+    Error,
+}
+
+impl Overflow {
+    /// What cell `i`, holding `value`, holds after `+`.
+    pub fn increment(self, i: usize, value: u32, max: u32) -> Result<u32, String> {
+        match self {
+            _ if value < max => Ok(value + 1),
+            Overflow::Wrap => Ok(0),
+            Overflow::Saturate => Ok(max),
+            Overflow::Error => Err(format!("Cell {} overflowed past {}", i, max)),
+        }
+    }
+
+    /// What cell `i`, holding `value`, holds after `-`.
+    pub fn decrement(self, i: usize, value: u32, max: u32) -> Result<u32, String> {
+        match self {
+            _ if value > 0 => Ok(value - 1),
+            Overflow::Wrap => Ok(max),
+            Overflow::Saturate => Ok(0),
+            Overflow::Error => Err(format!("Cell {} went below 0", i)),
+        }
+    }
+}
+
+/// What `,` stores once the input has run out.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Eof {
+    #[default]
+    Zero,
+    /// The cell's largest value, which is -1 in signed cells.
+    MinusOne,
+    /// Nothing: the cell keeps what it held.
+    Unchanged,
+}
+
+/// How a tape keeps its cells.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Storage {
+    /// A byte per cell, all allocated up front.
+    #[default]
+    Dense,
+    /// Pages of cells allocated as they're first written, for programs
+    /// that wander sparsely over a huge tape.
+    Sparse,
+    /// Arbitrary-precision cells that count up without wrapping, in a
+    /// `BigTape`.
+    #[cfg(feature = "bignum")]
+    Bignum,
+}
+
+/// Where a `BfArray` keeps its cells, all zero to begin with. The
+/// `BfArray` holds the data pointer and applies its `Bounds`, calling
+/// `grow` and `prepend` when they add cells. Values passed to `set` fit
+/// the `BfArray`'s `CellSize`, which the tape must be able to hold.
+pub trait Tape: fmt::Debug + Send {
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The value of cell `i`, which is less than `len`.
+    fn get(&self, i: usize) -> u32;
+
+    fn set(&mut self, i: usize, value: u32);
+
+    /// Lengthens the tape to `len` cells.
+    fn grow(&mut self, len: usize);
+
+    /// Adds at least `extra` cells before the first, returning how many
+    /// it added.
+    fn prepend(&mut self, extra: usize) -> usize;
+
+    fn is_zero(&self, i: usize) -> bool {
+        self.get(i) == 0
+    }
+
+    /// Adds one to cell `i`, going past `max` as `overflow` says.
+    fn increment(&mut self, i: usize, max: u32, overflow: Overflow) -> Result<(), String> {
+        self.set(i, overflow.increment(i, self.get(i), max)?);
+        Ok(())
+    }
+
+    /// Takes one from cell `i`, going below zero as `overflow` says.
+    fn decrement(&mut self, i: usize, max: u32, overflow: Overflow) -> Result<(), String> {
+        self.set(i, overflow.decrement(i, self.get(i), max)?);
+        Ok(())
+    }
+}
+
+impl Tape for Vec<u8> {
+    fn len(&self) -> usize {
+        self.as_slice().len()
+    }
+
+    #[inline]
+    fn get(&self, i: usize) -> u32 {
+        self[i].into()
+    }
+
+    #[inline]
+    fn set(&mut self, i: usize, value: u32) {
+        self[i] = value as u8;
+    }
+
+    fn grow(&mut self, len: usize) {
+        self.resize(len, 0);
+    }
+
+    fn prepend(&mut self, extra: usize) -> usize {
+        self.splice(0..0, std::iter::repeat_n(0, extra));
+        extra
+    }
+}
+
+impl Tape for Vec<u32> {
+    fn len(&self) -> usize {
+        self.as_slice().len()
+    }
+
+    fn get(&self, i: usize) -> u32 {
+        self[i]
+    }
+
+    fn set(&mut self, i: usize, value: u32) {
+        self[i] = value;
+    }
+
+    fn grow(&mut self, len: usize) {
+        self.resize(len, 0);
+    }
+
+    fn prepend(&mut self, extra: usize) -> usize {
+        self.splice(0..0, std::iter::repeat_n(0, extra));
+        extra
+    }
+}
+
+/// A tape kept in pages of cells, each allocated the first time it's
+/// written to, so a huge tape costs only what a program touches. Holds
+/// cells of any `CellSize`.
+#[derive(Debug)]
+pub struct SparseTape {
+    len: usize,
+    pages: HashMap<usize, Box<[u32; PAGE_SIZE]>>,
+}
+
+impl SparseTape {
+    pub fn new(len: usize) -> Self {
+        Self {
+            len,
+            pages: HashMap::new(),
+        }
+    }
+}
+
+impl Tape for SparseTape {
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn get(&self, i: usize) -> u32 {
+        self.pages
+            .get(&(i / PAGE_SIZE))
+            .map_or(0, |page| page[i % PAGE_SIZE])
+    }
+
+    fn set(&mut self, i: usize, value: u32) {
+        if value == 0 {
+            if let Some(page) = self.pages.get_mut(&(i / PAGE_SIZE)) {
+                page[i % PAGE_SIZE] = 0;
+            }
+            return;
+        }
+        self.pages
+            .entry(i / PAGE_SIZE)
+            .or_insert_with(|| Box::new([0; PAGE_SIZE]))[i % PAGE_SIZE] = value;
+    }
+
+    fn grow(&mut self, len: usize) {
+        self.len = len;
+    }
+
+    /// Adds whole pages, so cells stay in the pages they were in.
+    fn prepend(&mut self, extra: usize) -> usize {
+        let shift = extra.div_ceil(PAGE_SIZE);
+        self.pages = self
+            .pages
+            .drain()
+            .map(|(i, page)| (i + shift, page))
+            .collect();
+        self.len += shift * PAGE_SIZE;
+        shift * PAGE_SIZE
+    }
+}
+
+/// A tape of arbitrary-precision cells: `+` never wraps, and `-` on a
+/// zero cell is an error. `get` returns a cell's low 32 bits.
+#[cfg(feature = "bignum")]
+#[derive(Debug)]
+pub struct BigTape(Vec<BigUint>);
+
+#[cfg(feature = "bignum")]
+impl BigTape {
+    pub fn new(len: usize) -> Self {
+        Self(vec![BigUint::default(); len])
+    }
+
+    /// The whole value of cell `i`.
+    pub fn cell(&self, i: usize) -> &BigUint {
+        &self.0[i]
+    }
+}
+
+#[cfg(feature = "bignum")]
+impl Tape for BigTape {
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    fn get(&self, i: usize) -> u32 {
+        self.0[i].iter_u32_digits().next().unwrap_or(0)
+    }
+
+    fn set(&mut self, i: usize, value: u32) {
+        self.0[i] = value.into();
+    }
+
+    fn grow(&mut self, len: usize) {
+        self.0.resize(len, BigUint::default());
+    }
+
+    fn prepend(&mut self, extra: usize) -> usize {
+        self.0
+            .splice(0..0, std::iter::repeat_n(BigUint::default(), extra));
+        extra
+    }
+
+    fn is_zero(&self, i: usize) -> bool {
+        self.0[i] == BigUint::default()
+    }
+
+    fn increment(&mut self, i: usize, _: u32, _: Overflow) -> Result<(), String> {
+        self.0[i] += 1u32;
+        Ok(())
+    }
+
+    /// Only `Overflow::Saturate` keeps a zero cell from being an error.
+    fn decrement(&mut self, i: usize, _: u32, overflow: Overflow) -> Result<(), String> {
+        match (self.is_zero(i), overflow) {
+            (false, _) => self.0[i] -= 1u32,
+            (true, Overflow::Saturate) => {}
+            (true, _) => return Err("Decrement of a zero bignum cell".into()),
+        }
+        Ok(())
+    }
+}
+
+/// A `BfArray`'s cells: a `Vec` the lowered run loop can work on directly,
+/// or any other `Tape`.
+#[derive(Debug)]
+pub(crate) enum Cells {
+    Dense(Vec<u8>),
+    Other(Box<dyn Tape>),
+}
+
+impl Cells {
+    fn tape(&self) -> &dyn Tape {
+        match self {
+            Cells::Dense(cells) => cells,
+            Cells::Other(tape) => tape.as_ref(),
+        }
+    }
+
+    fn tape_mut(&mut self) -> &mut dyn Tape {
+        match self {
+            Cells::Dense(cells) => cells,
+            Cells::Other(tape) => tape.as_mut(),
+        }
+    }
+
+    #[inline]
+    fn get(&self, i: usize) -> u32 {
+        match self {
+            Cells::Dense(cells) => cells[i].into(),
+            Cells::Other(tape) => tape.get(i),
+        }
+    }
+
+    #[inline]
+    fn set(&mut self, i: usize, value: u32) {
+        match self {
+            Cells::Dense(cells) => cells[i] = value as u8,
+            Cells::Other(tape) => tape.set(i, value),
+        }
+    }
+
+    #[inline]
+    fn is_zero(&self, i: usize) -> bool {
+        match self {
+            Cells::Dense(cells) => cells[i] == 0,
+            Cells::Other(tape) => tape.is_zero(i),
+        }
+    }
+
+    #[inline]
+    fn modify(
+        &mut self,
+        i: usize,
+        direction: ModifyDirection,
+        max: u32,
+        overflow: Overflow,
+    ) -> Result<(), String> {
+        use ModifyDirection::*;
+
+        match (self, direction, overflow) {
+            (Cells::Dense(cells), Up, Overflow::Wrap) if !cfg!(feature = "paranoid") => {
+                cells[i] = cells[i].wrapping_add(1)
+            }
+            (Cells::Dense(cells), Down, Overflow::Wrap) if !cfg!(feature = "paranoid") => {
+                cells[i] = cells[i].wrapping_sub(1)
+            }
+            (cells, Up, _) => return cells.tape_mut().increment(i, max, overflow),
+            (cells, Down, _) => return cells.tape_mut().decrement(i, max, overflow),
+        }
+        Ok(())
+    }
+
+    pub(crate) fn dense(&self) -> &Vec<u8> {
+        match self {
+            Cells::Dense(cells) => cells,
+            Cells::Other(tape) => panic!("{:?} has no slice of cells", tape),
+        }
+    }
+
+    pub(crate) fn dense_mut(&mut self) -> &mut Vec<u8> {
+        match self {
+            Cells::Dense(cells) => cells,
+            Cells::Other(tape) => panic!("{:?} has no slice of cells", tape),
+        }
+    }
+}
+
+/// The tape of cells and the data pointer into it.
+#[derive(Debug)]
+pub struct BfArray {
+    pub(crate) raw: Cells,
+    pub(crate) pointer: usize,
+    pub(crate) bounds: Bounds,
+    pub(crate) cell_size: CellSize,
+    pub(crate) signed: bool,
+    pub(crate) overflow: Overflow,
+    pub(crate) eof: Eof,
+    /// Index in `raw` of the cell the pointer started on.
+    pub(crate) origin: usize,
+}
+
+impl Default for BfArray {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BfArray {
+    pub fn new() -> Self {
+        Self::with_len(ARRAY_SIZE)
+    }
+
+    pub fn with_len(len: usize) -> Self {
+        Self {
+            raw: Cells::Dense(vec![0; len]),
+            pointer: Default::default(),
+            bounds: Bounds::default(),
+            cell_size: CellSize::default(),
+            signed: false,
+            overflow: Overflow::default(),
+            eof: Eof::default(),
+            origin: 0,
+        }
+    }
+
+    /// A tape of `len` cells kept in a `SparseTape`.
+    pub fn sparse(len: usize) -> Self {
+        Self::with_tape(SparseTape::new(len))
+    }
+
+    /// A tape keeping its cells in `tape`.
+    pub fn with_tape<T: Tape + 'static>(tape: T) -> Self {
+        Self {
+            raw: Cells::Other(Box::new(tape)),
+            ..Self::with_len(0)
+        }
+    }
+
+    /// Whether the cells are in one `Vec`, which `cells` can return and
+    /// the lowered run loop can work on directly.
+    pub fn is_dense(&self) -> bool {
+        matches!(self.raw, Cells::Dense(_))
+    }
+
+    /// The tape with `bounds` deciding what moving off it does.
+    pub fn with_bounds(mut self, bounds: Bounds) -> Self {
+        self.bounds = bounds;
+        self
+    }
+
+    pub fn bounds(&self) -> Bounds {
+        self.bounds
+    }
+
+    /// The tape with cells of `size`, moving a dense tape's cells into a
+    /// `Vec<u32>` if they no longer fit in bytes. Other tapes must hold
+    /// `size` themselves.
+    pub fn with_cell_size(mut self, size: CellSize) -> Self {
+        if let (Cells::Dense(cells), true) = (&self.raw, size > CellSize::U8) {
+            let wide: Vec<u32> = cells.iter().map(|&cell| cell.into()).collect();
+            self.raw = Cells::Other(Box::new(wide));
+        }
+        self.cell_size = size;
+        self
+    }
+
+    pub fn cell_size(&self) -> CellSize {
+        self.cell_size
+    }
+
+    /// The tape with cells counted as signed if `signed`. Arithmetic
+    /// wraps the same either way; only what a value reads as changes.
+    pub fn with_signed(mut self, signed: bool) -> Self {
+        self.signed = signed;
+        self
+    }
+
+    pub fn is_signed(&self) -> bool {
+        self.signed
+    }
+
+    /// The tape with `overflow` deciding what happens to cells counted
+    /// past either end.
+    pub fn with_overflow(mut self, overflow: Overflow) -> Self {
+        self.overflow = overflow;
+        self
+    }
+
+    pub fn overflow(&self) -> Overflow {
+        self.overflow
+    }
+
+    /// The tape with `eof` deciding what `,` stores at the end of input.
+    pub fn with_eof(mut self, eof: Eof) -> Self {
+        self.eof = eof;
+        self
+    }
+
+    pub fn eof(&self) -> Eof {
+        self.eof
+    }
+
+    /// The settings that change what the cells hold, as the `bf run` flags
+    /// that pick them, leaving out defaults: `--cell-size 16 --signed`.
+    pub fn settings(&self) -> String {
+        fn flag<T: clap::ValueEnum + Default + PartialEq>(name: &str, value: T) -> Option<String> {
+            if value == T::default() {
+                return None;
+            }
+            Some(format!(
+                "--{} {}",
+                name,
+                value.to_possible_value()?.get_name()
+            ))
+        }
+        vec![
+            flag("cell-size", self.cell_size),
+            flag("bounds", self.bounds),
+            flag("overflow", self.overflow),
+            flag("eof", self.eof),
+            self.signed.then(|| "--signed".to_owned()),
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>()
+        .join(" ")
+    }
+
+    /// What a cell holding `raw`, as `full_value` or `cell` return it,
+    /// counts as: negative if the cells are signed and its top bit is set.
+    pub fn number(&self, raw: u32) -> i64 {
+        if self.signed {
+            self.cell_size.signed(raw).into()
+        } else {
+            raw.into()
+        }
+    }
+
+    /// Index in `cells` of the cell the pointer started on, which only
+    /// moves when an `Unbounded` tape grows to the left.
+    pub fn origin(&self) -> usize {
+        self.origin
+    }
+
+    /// The pointer relative to the cell it started on, negative left of
+    /// it.
+    pub fn position(&self) -> isize {
+        self.pointer as isize - self.origin as isize
+    }
+
+    /// Where moving `by` cells from the pointer lands under `bounds`,
+    /// growing the tape if that's what it takes. `None` means the move is
+    /// an error.
+    pub(crate) fn destination(&mut self, by: isize) -> Option<usize> {
+        let len = self.len();
+        match self.pointer.checked_add_signed(by) {
+            Some(to) if to < len => Some(to),
+            _ if self.bounds == Bounds::Wrap => {
+                let by = by.rem_euclid(len as isize) as usize;
+                Some((self.pointer + by) % len)
+            }
+            Some(to) if matches!(self.bounds, Bounds::Grow | Bounds::Unbounded) => {
+                self.raw.tape_mut().grow(to.checked_add(1)?.max(len * 2));
+                Some(to)
+            }
+            None if by < 0 && self.bounds == Bounds::Unbounded => {
+                // Double to the left too, so walking left stays cheap.
+                let short = by.unsigned_abs() - self.pointer;
+                let extra = self.raw.tape_mut().prepend(short.max(len));
+                self.origin += extra;
+                Some(self.pointer + extra - by.unsigned_abs())
+            }
+            _ => None,
+        }
+    }
+
+    pub(crate) fn perform_operation<R: Read, W: Write>(
+        &mut self,
+        opcode: &OpCode,
+        reader: &mut R,
+        writer: &mut W,
+    ) -> Action {
+        use JumpFrom::*;
+        use ModifyDirection::*;
+        use OpCode::*;
+
+        match opcode {
+            Output => self.output(writer),
+            Input => self.input(reader),
+            JmpStart => self.jump_from(Start),
+            JmpEnd => self.jump_from(End),
+            Increment => self.modify_value(Up),
+            Decrement => self.modify_value(Down),
+            MoveForward => self.move_pointer(Up),
+            MoveBack => self.move_pointer(Down),
+        }
+    }
+
+    /// The cells of the tape. Panics unless `is_dense`; `cells_in` works
+    /// on any tape.
+    pub fn cells(&self) -> &[u8] {
+        self.raw.dense()
+    }
+
+    /// The cells in `range`, copied out unless the tape `is_dense`. Cells
+    /// wider than a byte come out as `CellSize::bytes` little-endian
+    /// bytes each. Panics if `range` runs past the end, like slicing.
+    pub fn cells_in(&self, range: Range<usize>) -> Cow<'_, [u8]> {
+        match &self.raw {
+            Cells::Dense(cells) => Cow::Borrowed(&cells[range]),
+            Cells::Other(tape) => {
+                assert!(
+                    range.end <= tape.len(),
+                    "cells {:?} are off the tape",
+                    range
+                );
+                let bytes = self.cell_size.bytes();
+                Cow::Owned(
+                    range
+                        .flat_map(|i| {
+                            IntoIterator::into_iter(tape.get(i).to_le_bytes()).take(bytes)
+                        })
+                        .collect(),
+                )
+            }
+        }
+    }
+
+    /// The values of the cells in `range`, whatever their size. Panics if
+    /// `range` runs past the end.
+    pub fn values_in(&self, range: Range<usize>) -> Vec<u32> {
+        assert!(
+            range.end <= self.len(),
+            "cells {:?} are off the tape",
+            range
+        );
+        range.map(|i| self.raw.get(i)).collect()
+    }
+
+    /// The value of cell `i`, if the tape has one.
+    pub fn cell(&self, i: usize) -> Option<u32> {
+        (i < self.len()).then(|| self.raw.get(i))
+    }
+
+    /// Number of cells on the tape.
+    pub fn len(&self) -> usize {
+        self.raw.tape().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Index of the cell under the data pointer.
+    pub fn pointer(&self) -> usize {
+        self.pointer
+    }
+
+    /// Exchanges the cells with `cells`, keeping the pointer, so programs
+    /// can take turns on a shared tape. The tape becomes dense first, so
+    /// it panics if the cells are wider than a byte.
+    pub fn swap_cells(&mut self, cells: &mut Vec<u8>) {
+        assert_eq!(self.cell_size, CellSize::U8, "wide cells don't fit a byte");
+        if !self.is_dense() {
+            self.raw = Cells::Dense(self.cells_in(0..self.len()).into_owned());
+        }
+        std::mem::swap(self.raw.dense_mut(), cells);
+    }
+
+    /// The cell a `[>]`-style loop moving `stride` cells per iteration
+    /// stops on: the first zero cell from the pointer on. `None` if the
+    /// loop would leave the tape first. Only for dense tapes.
+    pub(crate) fn scan(&self, stride: isize) -> Option<usize> {
+        let (at, raw) = (self.pointer, self.raw.dense());
+        match stride {
+            1 => memchr::memchr(0, &raw[at..]).map(|i| at + i),
+            -1 => memchr::memrchr(0, &raw[..=at]),
+            _ => {
+                let mut at = at;
+                while raw[at] != 0 {
+                    at = at.checked_add_signed(stride).filter(|&to| to < raw.len())?;
+                }
+                Some(at)
+            }
+        }
+    }
+
+    /// The low byte of the cell under the data pointer, which `.` writes.
+    #[inline]
+    pub fn value(&self) -> u8 {
+        self.raw.get(self.pointer) as u8
+    }
+
+    /// The whole value of the cell under the data pointer, of which
+    /// `value` is the low byte.
+    #[inline]
+    pub fn full_value(&self) -> u32 {
+        self.raw.get(self.pointer)
+    }
+
+    #[inline]
+    fn set_value(&mut self, val: u32) {
+        self.raw.set(self.pointer, val & self.cell_size.max());
+    }
+
+    pub(crate) fn output<W: Write>(&self, writer: &mut W) -> Action {
+        match writer.write_u8(self.value()) {
+            Ok(()) => Action::None,
+            Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => Action::Block,
+            Err(ref e) if e.kind() == std::io::ErrorKind::BrokenPipe => Action::OutputClosed,
+            Err(e) => Action::Exit(format!("Write error: {}", e)),
+        }
+    }
+
+    pub(crate) fn input<R: Read>(&mut self, reader: &mut R) -> Action {
+        let mut byte = [0];
+        let input = loop {
+            match reader.read(&mut byte) {
+                Ok(0) => match self.eof {
+                    Eof::Zero => break 0,
+                    Eof::MinusOne => break self.cell_size.max(),
+                    Eof::Unchanged => return Action::None,
+                },
+                Ok(_) => break byte[0].into(),
+                Err(ref e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => return Action::Block,
+                Err(e) => return Action::Exit(format!("Read error: {}", e)),
+            }
+        };
+
+        self.set_value(input);
+        Action::None
+    }
+
+    fn jump_from(&self, from: JumpFrom) -> Action {
+        let nonzero = !self.raw.is_zero(self.pointer);
+        match (from, nonzero) {
+            (JumpFrom::Start, false) => Action::JumpForward,
+            (JumpFrom::End, true) => Action::JumpBack,
+            _ => Action::None,
+        }
+    }
+
+    fn modify_value(&mut self, direction: ModifyDirection) -> Action {
+        match self
+            .raw
+            .modify(self.pointer, direction, self.cell_size.max(), self.overflow)
+        {
+            Ok(()) => Action::None,
+            Err(e) => Action::Exit(e),
+        }
+    }
+
+    fn move_pointer(&mut self, direction: ModifyDirection) -> Action {
+        let by = match direction {
+            ModifyDirection::Up => 1,
+            ModifyDirection::Down => -1,
+        };
+
+        match self.destination(by) {
+            None => Action::Exit("Pointer access violation".into()),
+            Some(x) => {
+                self.pointer = x;
+                Action::None
+            }
+        }
+    }
+}
@@ -0,0 +1,205 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use crate::random::Rng;
+use bf::program::{OpIndex, Program};
+use bf::{Interpreter, OpCode};
+
+pub const DEFAULT_SAMPLE_EVERY: u64 = 64;
+
+const KINDS: [OpCode; 8] = [
+    OpCode::MoveForward,
+    OpCode::MoveBack,
+    OpCode::Increment,
+    OpCode::Decrement,
+    OpCode::Output,
+    OpCode::Input,
+    OpCode::JmpStart,
+    OpCode::JmpEnd,
+];
+
+fn kind(op: OpCode) -> usize {
+    match op {
+        OpCode::MoveForward => 0,
+        OpCode::MoveBack => 1,
+        OpCode::Increment => 2,
+        OpCode::Decrement => 3,
+        OpCode::Output => 4,
+        OpCode::Input => 5,
+        OpCode::JmpStart => 6,
+        OpCode::JmpEnd => 7,
+    }
+}
+
+/// Samples the wall time of single instructions from
+/// `Interpreter::execute_with` callbacks. Sampled steps are spaced at
+/// random so they don't fall in step with a loop's period.
+#[derive(Debug)]
+pub struct Timing {
+    rng: Rng,
+    every: u64,
+    until_sample: u64,
+    started: Option<Instant>,
+    counts: [u64; 8],
+    samples: [u64; 8],
+    sampled: [Duration; 8],
+    // Sampled time spent inside each loop, including its nested loops.
+    loops: HashMap<OpIndex, Duration>,
+    // `[`s of the loops currently executing.
+    stack: Vec<OpIndex>,
+}
+
+impl Timing {
+    /// Times about one instruction in `every`.
+    pub fn new(every: u64) -> Self {
+        Self {
+            rng: Rng::new(0),
+            every: every.max(1),
+            until_sample: 0,
+            started: None,
+            counts: [0; 8],
+            samples: [0; 8],
+            sampled: [Duration::ZERO; 8],
+            loops: HashMap::new(),
+            stack: Vec::new(),
+        }
+    }
+
+    /// Records the instruction at `at`, which `interp` has just executed.
+    /// Call this first in the callback, and `arm` last.
+    pub fn record(&mut self, interp: &Interpreter, at: OpIndex) {
+        let op = interp.program()[at];
+        let k = kind(op);
+        self.counts[k] += 1;
+        if let Some(started) = self.started.take() {
+            let took = started.elapsed();
+            self.samples[k] += 1;
+            self.sampled[k] += took;
+            for start in &self.stack {
+                *self.loops.entry(*start).or_default() += took;
+            }
+        }
+
+        let fell_through = at.next() == Some(interp.pc());
+        match op {
+            OpCode::JmpStart if fell_through => self.stack.push(at),
+            OpCode::JmpEnd if fell_through => {
+                self.stack.pop();
+            }
+            _ => {}
+        }
+    }
+
+    /// Starts the clock if the next instruction is to be sampled.
+    pub fn arm(&mut self) {
+        if self.until_sample == 0 {
+            self.until_sample = self.rng.next_u64() % (2 * self.every - 1);
+            self.started = Some(Instant::now());
+        } else {
+            self.until_sample -= 1;
+        }
+    }
+
+    /// Estimated time per instruction kind and for the `top` loops that
+    /// took longest.
+    pub fn report(&self, program: &Program, top: usize) -> TimingReport {
+        let kinds = KINDS
+            .iter()
+            .enumerate()
+            .filter(|&(k, _)| self.counts[k] > 0)
+            .map(|(k, op)| {
+                let mean = match self.samples[k] {
+                    0 => Duration::ZERO,
+                    n => self.sampled[k].div_f64(n as f64),
+                };
+                KindTime {
+                    op: op.symbol(),
+                    count: self.counts[k],
+                    samples: self.samples[k],
+                    mean,
+                    estimate: mean.mul_f64(self.counts[k] as f64),
+                }
+            })
+            .collect();
+
+        let scale = self.samples.iter().sum::<u64>() as f64;
+        let scale = self.counts.iter().sum::<u64>() as f64 / scale.max(1.0);
+        let mut loops: Vec<LoopTime> = self
+            .loops
+            .iter()
+            .map(|(start, sampled)| LoopTime {
+                pos: program.positions()[start.index()].to_string(),
+                estimate: sampled.mul_f64(scale),
+            })
+            .collect();
+        loops.sort_by(|a, b| b.estimate.cmp(&a.estimate).then(a.pos.cmp(&b.pos)));
+        loops.truncate(top);
+
+        TimingReport { kinds, loops }
+    }
+}
+
+#[derive(Debug)]
+pub struct TimingReport {
+    pub kinds: Vec<KindTime>,
+    pub loops: Vec<LoopTime>,
+}
+
+#[derive(Debug)]
+pub struct KindTime {
+    pub op: char,
+    pub count: u64,
+    pub samples: u64,
+    /// Mean sampled time of one instruction of this kind.
+    pub mean: Duration,
+    /// `mean` times `count`.
+    pub estimate: Duration,
+}
+
+#[derive(Debug)]
+pub struct LoopTime {
+    /// Where the loop's `[` is.
+    pub pos: String,
+    pub estimate: Duration,
+}
+
+impl fmt::Display for TimingReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let total: Duration = self.kinds.iter().map(|k| k.estimate).sum();
+        let share = |d: Duration| 100.0 * d.as_secs_f64() / total.as_secs_f64().max(1e-12);
+
+        writeln!(
+            f,
+            "{:<4} {:>12} {:>9} {:>9} {:>12} {:>6}",
+            "op", "count", "samples", "ns/op", "est. time", "share"
+        )?;
+        for k in &self.kinds {
+            writeln!(
+                f,
+                "{:<4} {:>12} {:>9} {:>9.1} {:>12} {:>5.1}%",
+                k.op,
+                k.count,
+                k.samples,
+                k.mean.as_secs_f64() * 1e9,
+                format!("{:.3?}", k.estimate),
+                share(k.estimate)
+            )?;
+        }
+
+        if !self.loops.is_empty() {
+            writeln!(f)?;
+            writeln!(f, "{:<12} {:>12} {:>6}", "loop", "est. time", "share")?;
+            for l in &self.loops {
+                writeln!(
+                    f,
+                    "[@{:<10} {:>12} {:>5.1}%",
+                    l.pos,
+                    format!("{:.3?}", l.estimate),
+                    share(l.estimate)
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,724 @@
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+
+use std::convert::TryFrom;
+use std::fs::File;
+use std::io::{self, prelude::*, BufReader, BufWriter, SeekFrom};
+use std::path::Path;
+
+use bf::compress::{self, Compression, Encoder};
+use bf::program::{OpIndex, Program, SourcePos};
+use bf::snapshot::Snapshot;
+use bf::{Interpreter, OpCode};
+
+// File layout, all integers little-endian:
+//
+//   magic "BFTR", version u16, flags u16,
+//   program hash u64, tape length u64, checkpoint interval u32,
+//   options (u32 length + UTF-8), program source (u32 length + UTF-8),
+//   source positions (offset u32, line u32, column u32 per instruction),
+//   body: records until an End record, zstd- or gzip-compressed if flagged,
+//   index: magic "BFIX", block count u32, then per block of checkpoint
+//     interval steps: file offset of its first record u64 (uncompressed
+//     traces only), lowest and highest pointer u32 and ip u32, output
+//     bytes written before it u64,
+//   footer: file offset of the index u64, magic "BFIX".
+//
+// Version 2 traces end after the body.
+const MAGIC: &[u8; 4] = b"BFTR";
+const INDEX_MAGIC: &[u8; 4] = b"BFIX";
+pub const VERSION: u16 = 3;
+const FLAG_ZSTD: u16 = 1;
+const FLAG_GZIP: u16 = 2;
+pub const CHECKPOINT_INTERVAL: u32 = 4096;
+
+const TAG_STEP: u8 = 0;
+const TAG_CHECKPOINT: u8 = 1;
+const TAG_END: u8 = 2;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Header {
+    pub version: u16,
+    pub compression: Compression,
+    pub program_hash: u64,
+    pub tape_len: u64,
+    pub checkpoint_interval: u32,
+    pub options: String,
+    pub program: String,
+    pub positions: Vec<SourcePos>,
+}
+
+impl Header {
+    pub fn new(
+        program: &Program,
+        tape_len: usize,
+        options: String,
+        compression: Compression,
+    ) -> Self {
+        Self {
+            version: VERSION,
+            compression,
+            program_hash: program.hash(),
+            tape_len: tape_len as u64,
+            checkpoint_interval: CHECKPOINT_INTERVAL,
+            options,
+            program: program.source(),
+            positions: program.positions().to_vec(),
+        }
+    }
+
+    fn write_to<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        out.write_all(MAGIC)?;
+        out.write_u16::<LittleEndian>(self.version)?;
+        out.write_u16::<LittleEndian>(match self.compression {
+            Compression::None => 0,
+            Compression::Zstd => FLAG_ZSTD,
+            Compression::Gzip => FLAG_GZIP,
+        })?;
+        out.write_u64::<LittleEndian>(self.program_hash)?;
+        out.write_u64::<LittleEndian>(self.tape_len)?;
+        out.write_u32::<LittleEndian>(self.checkpoint_interval)?;
+        write_str(out, &self.options)?;
+        write_str(out, &self.program)?;
+        u32_of(self.tape_len as usize)?;
+        u32_of(self.positions.len())?;
+        for pos in &self.positions {
+            out.write_u32::<LittleEndian>(u32_of(pos.offset)?)?;
+            out.write_u32::<LittleEndian>(u32_of(pos.line)?)?;
+            out.write_u32::<LittleEndian>(u32_of(pos.col)?)?;
+        }
+        Ok(())
+    }
+
+    fn read_from<R: Read>(input: &mut R) -> io::Result<Self> {
+        let mut magic = [0; 4];
+        input.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid("not a trace file"));
+        }
+
+        let version = input.read_u16::<LittleEndian>()?;
+        if !(2..=VERSION).contains(&version) {
+            return Err(invalid(&format!(
+                "unsupported trace version {} (expected {})",
+                version, VERSION
+            )));
+        }
+
+        let flags = input.read_u16::<LittleEndian>()?;
+        let mut header = Self {
+            version,
+            compression: if flags & FLAG_ZSTD != 0 {
+                Compression::Zstd
+            } else if flags & FLAG_GZIP != 0 {
+                Compression::Gzip
+            } else {
+                Compression::None
+            },
+            program_hash: input.read_u64::<LittleEndian>()?,
+            tape_len: input.read_u64::<LittleEndian>()?,
+            checkpoint_interval: input.read_u32::<LittleEndian>()?,
+            options: read_str(input)?,
+            program: read_str(input)?,
+            positions: Vec::new(),
+        };
+
+        // The writer refuses longer tapes, so a longer one is corrupt.
+        if header.tape_len > u64::from(u32::MAX) {
+            return Err(invalid("tape length in header is too large"));
+        }
+
+        for _ in 0..header.program.len() {
+            header.positions.push(SourcePos {
+                offset: input.read_u32::<LittleEndian>()? as usize,
+                line: input.read_u32::<LittleEndian>()? as usize,
+                col: input.read_u32::<LittleEndian>()? as usize,
+            });
+        }
+        Ok(header)
+    }
+
+    /// The symbol of the instruction at `ip`.
+    pub fn op(&self, ip: u32) -> Option<char> {
+        self.program
+            .as_bytes()
+            .get(ip as usize)
+            .map(|&op| op as char)
+    }
+
+    /// Describes the instruction at `ip` as its symbol and source position.
+    pub fn describe(&self, ip: u32) -> String {
+        match (self.op(ip), self.positions.get(ip as usize)) {
+            (Some(op), Some(pos)) => format!("'{}' at {}", op, pos),
+            _ => format!("<instruction {}>", ip),
+        }
+    }
+}
+
+/// An instruction executed; `value` is the current cell afterwards.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Step {
+    pub ip: u32,
+    pub pointer: u32,
+    pub value: u8,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Record {
+    Step(Step),
+    /// A digest of the full state after `steps` instructions.
+    Checkpoint {
+        steps: u64,
+        ip: u32,
+        digest: u64,
+    },
+    End {
+        steps: u64,
+    },
+}
+
+impl Record {
+    /// Bytes the record takes in the body.
+    fn len(&self) -> u64 {
+        match self {
+            Record::Step(_) => 10,
+            Record::Checkpoint { .. } => 21,
+            Record::End { .. } => 9,
+        }
+    }
+
+    fn write_to<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        match *self {
+            Record::Step(Step { ip, pointer, value }) => {
+                out.write_u8(TAG_STEP)?;
+                out.write_u32::<LittleEndian>(ip)?;
+                out.write_u32::<LittleEndian>(pointer)?;
+                out.write_u8(value)
+            }
+            Record::Checkpoint { steps, ip, digest } => {
+                out.write_u8(TAG_CHECKPOINT)?;
+                out.write_u64::<LittleEndian>(steps)?;
+                out.write_u32::<LittleEndian>(ip)?;
+                out.write_u64::<LittleEndian>(digest)
+            }
+            Record::End { steps } => {
+                out.write_u8(TAG_END)?;
+                out.write_u64::<LittleEndian>(steps)
+            }
+        }
+    }
+
+    fn read_from<R: Read>(input: &mut R) -> io::Result<Option<Self>> {
+        let tag = match input.read_u8() {
+            Ok(tag) => tag,
+            Err(ref e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e),
+        };
+
+        let record = match tag {
+            TAG_STEP => Record::Step(Step {
+                ip: input.read_u32::<LittleEndian>()?,
+                pointer: input.read_u32::<LittleEndian>()?,
+                value: input.read_u8()?,
+            }),
+            TAG_CHECKPOINT => Record::Checkpoint {
+                steps: input.read_u64::<LittleEndian>()?,
+                ip: input.read_u32::<LittleEndian>()?,
+                digest: input.read_u64::<LittleEndian>()?,
+            },
+            TAG_END => Record::End {
+                steps: input.read_u64::<LittleEndian>()?,
+            },
+            _ => return Err(invalid(&format!("unknown record tag {}", tag))),
+        };
+        Ok(Some(record))
+    }
+}
+
+/// What the index records about a block of steps, letting queries skip
+/// the blocks that can't match.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Block {
+    /// File offset of the block's first record. Meaningless when the body
+    /// is compressed.
+    pub offset: u64,
+    pub pointers: (u32, u32),
+    pub ips: (u32, u32),
+    /// Output bytes written by earlier blocks.
+    pub outputs: u64,
+}
+
+impl Block {
+    fn write_to<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        out.write_u64::<LittleEndian>(self.offset)?;
+        for n in [self.pointers.0, self.pointers.1, self.ips.0, self.ips.1] {
+            out.write_u32::<LittleEndian>(n)?;
+        }
+        out.write_u64::<LittleEndian>(self.outputs)
+    }
+
+    fn read_from<R: Read>(input: &mut R) -> io::Result<Self> {
+        Ok(Self {
+            offset: input.read_u64::<LittleEndian>()?,
+            pointers: (
+                input.read_u32::<LittleEndian>()?,
+                input.read_u32::<LittleEndian>()?,
+            ),
+            ips: (
+                input.read_u32::<LittleEndian>()?,
+                input.read_u32::<LittleEndian>()?,
+            ),
+            outputs: input.read_u64::<LittleEndian>()?,
+        })
+    }
+}
+
+/// Records an execution step by step.
+pub struct TraceWriter {
+    body: Encoder<BufWriter<File>>,
+    steps: u64,
+    interval: u64,
+    // File offset the next record will have, if the body is uncompressed.
+    offset: u64,
+    outputs: u64,
+    blocks: Vec<Block>,
+}
+
+impl std::fmt::Debug for TraceWriter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TraceWriter")
+            .field("steps", &self.steps)
+            .finish()
+    }
+}
+
+impl TraceWriter {
+    pub fn create<P: AsRef<Path>>(path: P, header: &Header) -> io::Result<Self> {
+        let mut file = BufWriter::new(File::create(path)?);
+        let mut head = Vec::new();
+        header.write_to(&mut head)?;
+        file.write_all(&head)?;
+
+        let body = Encoder::new(file, header.compression)?;
+
+        Ok(Self {
+            body,
+            steps: 0,
+            interval: u64::from(header.checkpoint_interval.max(1)),
+            offset: head.len() as u64,
+            outputs: 0,
+            blocks: Vec::new(),
+        })
+    }
+
+    /// Records the instruction at `at`, which `interp` has just executed.
+    pub fn step(&mut self, interp: &Interpreter, at: OpIndex) -> io::Result<()> {
+        // The header checked that instruction indices and cells fit in a u32.
+        let (ip, pointer) = (at.index() as u32, interp.tape().pointer() as u32);
+        if self.steps.is_multiple_of(self.interval) {
+            self.blocks.push(Block {
+                offset: self.offset,
+                pointers: (pointer, pointer),
+                ips: (ip, ip),
+                outputs: self.outputs,
+            });
+        }
+        let block = self.blocks.last_mut().unwrap();
+        block.pointers = (block.pointers.0.min(pointer), block.pointers.1.max(pointer));
+        block.ips = (block.ips.0.min(ip), block.ips.1.max(ip));
+        if let OpCode::Output = interp.program()[at] {
+            self.outputs += 1;
+        }
+
+        self.record(Record::Step(Step {
+            ip,
+            pointer,
+            value: interp.tape().value(),
+        }))?;
+        self.steps += 1;
+        if self.steps.is_multiple_of(self.interval) {
+            self.record(Record::Checkpoint {
+                steps: self.steps,
+                ip: interp.pc().index() as u32,
+                digest: Snapshot::digest_of(
+                    &interp.tape().cells_in(0..interp.tape().len()),
+                    interp.tape().pointer(),
+                    interp.pc(),
+                ),
+            })?;
+        }
+        Ok(())
+    }
+
+    fn record(&mut self, record: Record) -> io::Result<()> {
+        self.offset += record.len();
+        record.write_to(&mut self.body)
+    }
+
+    pub fn finish(mut self) -> io::Result<()> {
+        self.record(Record::End { steps: self.steps })?;
+        let mut file = self.body.finish()?;
+
+        let index = file.stream_position()?;
+        file.write_all(INDEX_MAGIC)?;
+        file.write_u32::<LittleEndian>(u32_of(self.blocks.len())?)?;
+        for block in &self.blocks {
+            block.write_to(&mut file)?;
+        }
+        file.write_u64::<LittleEndian>(index)?;
+        file.write_all(INDEX_MAGIC)?;
+        file.flush()
+    }
+}
+
+/// Reads back a trace written by `TraceWriter`.
+pub struct TraceReader {
+    pub header: Header,
+    body: Box<dyn Read>,
+}
+
+impl std::fmt::Debug for TraceReader {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TraceReader")
+            .field("header", &self.header)
+            .finish()
+    }
+}
+
+impl TraceReader {
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let mut file = BufReader::new(File::open(path)?);
+        let header = Header::read_from(&mut file)?;
+        let body = match header.compression {
+            Compression::None => Box::new(file),
+            _ => compress::decoder(file)?,
+        };
+        Ok(Self { header, body })
+    }
+
+    pub fn next_record(&mut self) -> io::Result<Option<Record>> {
+        Record::read_from(&mut self.body)
+    }
+
+    /// The next `Step`, skipping checkpoints. `None` at the end of the trace.
+    pub fn next_step(&mut self) -> io::Result<Option<Step>> {
+        loop {
+            match self.next_record()? {
+                Some(Record::Step(step)) => return Ok(Some(step)),
+                Some(Record::Checkpoint { .. }) => {}
+                Some(Record::End { .. }) | None => return Ok(None),
+            }
+        }
+    }
+}
+
+/// The index of a version 3 trace, or `None` for an older one.
+pub fn read_index<P: AsRef<Path>>(path: P) -> io::Result<Option<Vec<Block>>> {
+    let mut file = BufReader::new(File::open(path)?);
+    if file.seek(SeekFrom::End(0))? < 12 {
+        return Ok(None);
+    }
+    file.seek(SeekFrom::End(-12))?;
+    let index = file.read_u64::<LittleEndian>()?;
+    let mut magic = [0; 4];
+    file.read_exact(&mut magic)?;
+    if &magic != INDEX_MAGIC {
+        return Ok(None);
+    }
+
+    file.seek(SeekFrom::Start(index))?;
+    file.read_exact(&mut magic)?;
+    if &magic != INDEX_MAGIC {
+        return Err(invalid("corrupt trace index"));
+    }
+    let blocks = file.read_u32::<LittleEndian>()?;
+    (0..blocks)
+        .map(|_| Block::read_from(&mut file))
+        .collect::<io::Result<_>>()
+        .map(Some)
+}
+
+/// Which steps `query` picks out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Query {
+    /// Steps that leave the pointer on `cell`, holding `value` if given.
+    Cell { cell: u32, value: Option<u8> },
+    /// Steps executing the instruction at this source byte offset.
+    Offset(usize),
+    /// The step that wrote this output byte, counting from 0.
+    Output(u64),
+}
+
+/// The numbers of the steps matching `query`, counting from 0. Uses the
+/// index to read only the blocks that can match when the trace has one
+/// and isn't compressed.
+pub fn query<P: AsRef<Path>>(path: P, query: Query) -> io::Result<Vec<u64>> {
+    let mut trace = TraceReader::open(&path)?;
+    let header = trace.header.clone();
+    let ip = match query {
+        Query::Offset(offset) => Some(
+            header
+                .positions
+                .iter()
+                .position(|pos| pos.offset == offset)
+                .ok_or_else(|| invalid(&format!("no instruction at source offset {}", offset)))?
+                as u32,
+        ),
+        _ => None,
+    };
+    // Whether `step`, which follows `outputs` output bytes, matches.
+    let matches = |step: &Step, outputs: u64| match query {
+        Query::Cell { cell, value } => {
+            step.pointer == cell && value.is_none_or(|value| step.value == value)
+        }
+        Query::Offset(_) => Some(step.ip) == ip,
+        Query::Output(n) => outputs == n && header.op(step.ip) == Some('.'),
+    };
+    let is_output = |step: &Step| header.op(step.ip) == Some('.');
+
+    let mut found = Vec::new();
+    let index = match read_index(&path)? {
+        Some(index) if header.compression == Compression::None => index,
+        _ => {
+            let (mut at, mut outputs) = (0, 0);
+            while let Some(step) = trace.next_step()? {
+                if matches(&step, outputs) {
+                    found.push(at);
+                }
+                outputs += is_output(&step) as u64;
+                at += 1;
+            }
+            return Ok(found);
+        }
+    };
+
+    let interval = u64::from(header.checkpoint_interval.max(1));
+    let mut file = BufReader::new(File::open(&path)?);
+    for (i, block) in index.iter().enumerate() {
+        let within = |(low, high): (u32, u32), n: u32| (low..=high).contains(&n);
+        let may_match = match query {
+            Query::Cell { cell, .. } => within(block.pointers, cell),
+            Query::Offset(_) => within(block.ips, ip.unwrap()),
+            Query::Output(n) => {
+                block.outputs <= n && index.get(i + 1).is_none_or(|next| n < next.outputs)
+            }
+        };
+        if !may_match {
+            continue;
+        }
+
+        file.seek(SeekFrom::Start(block.offset))?;
+        let (mut at, mut outputs) = (i as u64 * interval, block.outputs);
+        while at < (i as u64 + 1) * interval {
+            match Record::read_from(&mut file)? {
+                Some(Record::Step(step)) => {
+                    if matches(&step, outputs) {
+                        found.push(at);
+                    }
+                    outputs += is_output(&step) as u64;
+                    at += 1;
+                }
+                Some(Record::Checkpoint { .. }) => {}
+                Some(Record::End { .. }) | None => break,
+            }
+        }
+    }
+    Ok(found)
+}
+
+/// Summarises a trace and re-verifies its checkpoints by replaying the
+/// recorded cell writes.
+pub fn info<P: AsRef<Path>>(path: P) -> io::Result<String> {
+    let mut trace = TraceReader::open(path)?;
+    let header = trace.header.clone();
+
+    let mut tape = vec![0u8; header.tape_len as usize];
+    let mut pointer = 0;
+    let mut steps = 0u64;
+    let mut checkpoints = 0u64;
+    let mut mismatch = None;
+    let mut end = None;
+
+    while let Some(record) = trace.next_record()? {
+        match record {
+            Record::Step(step) => {
+                pointer = step.pointer as usize;
+                let cell = tape
+                    .get_mut(pointer)
+                    .ok_or_else(|| invalid("cell write outside the recorded tape"))?;
+                *cell = step.value;
+                steps += 1;
+            }
+            Record::Checkpoint {
+                steps: at,
+                ip,
+                digest,
+            } => {
+                checkpoints += 1;
+                let actual = Snapshot::digest_of(&tape, pointer, OpIndex::new(ip as usize));
+                if (at != steps || actual != digest) && mismatch.is_none() {
+                    mismatch = Some(at);
+                }
+            }
+            Record::End { steps: total } => {
+                end = Some(total);
+                break;
+            }
+        }
+    }
+
+    let checksum = match (checkpoints, mismatch) {
+        (0, _) => "no checkpoints".to_owned(),
+        (n, None) => format!("ok ({} checkpoints)", n),
+        (n, Some(at)) => format!("MISMATCH at step {} ({} checkpoints)", at, n),
+    };
+    let complete = match end {
+        Some(total) if total == steps => "yes".to_owned(),
+        Some(total) => format!("no (end record claims {} steps)", total),
+        None => "no (truncated)".to_owned(),
+    };
+
+    Ok(format!(
+        "format version:      {}\n\
+         compression:         {}\n\
+         program hash:        {:016x}\n\
+         program length:      {} instructions\n\
+         options:             {}\n\
+         tape length:         {}\n\
+         checkpoint interval: {}\n\
+         steps:               {}\n\
+         checksums:           {}\n\
+         complete:            {}\n",
+        header.version,
+        header.compression.name(),
+        header.program_hash,
+        header.program.len(),
+        if header.options.is_empty() {
+            "(none)"
+        } else {
+            &header.options
+        },
+        header.tape_len,
+        header.checkpoint_interval,
+        steps,
+        checksum,
+        complete,
+    ))
+}
+
+fn u32_of(n: usize) -> io::Result<u32> {
+    u32::try_from(n).map_err(|_| invalid("value too large for the trace format"))
+}
+
+fn write_str<W: Write>(out: &mut W, s: &str) -> io::Result<()> {
+    out.write_u32::<LittleEndian>(u32_of(s.len())?)?;
+    out.write_all(s.as_bytes())
+}
+
+fn read_str<R: Read>(input: &mut R) -> io::Result<String> {
+    let len = input.read_u32::<LittleEndian>()?;
+    // Read no more than is there, whatever length the file claims.
+    let mut buf = Vec::new();
+    input.take(u64::from(len)).read_to_end(&mut buf)?;
+    if buf.len() != len as usize {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    String::from_utf8(buf).map_err(|_| invalid("invalid UTF-8 in header"))
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_owned())
+}
+
+// Steps of agreeing history shown before a divergence, and of each trace
+// after it.
+const DIFF_CONTEXT: usize = 4;
+
+/// Compares two traces step by step and describes the first divergence.
+pub fn diff<P: AsRef<Path>>(a: P, b: P, allow_mismatch: bool) -> io::Result<String> {
+    let mut a = TraceReader::open(a)?;
+    let mut b = TraceReader::open(b)?;
+    // Traces of other settings would differ for reasons that aren't bugs.
+    let (ha, hb) = (&a.header, &b.header);
+    if (ha.options != hb.options || ha.tape_len != hb.tape_len) && !allow_mismatch {
+        return Err(invalid(&format!(
+            "the traces were recorded with different settings ({} cells, {:?} vs {} cells, \
+             {:?}); pass --allow-mismatch to compare them anyway",
+            a.header.tape_len, a.header.options, b.header.tape_len, b.header.options
+        )));
+    }
+
+    let mut out = String::new();
+    if a.header.program_hash != b.header.program_hash {
+        out.push_str("note: the traces were recorded from different programs\n");
+    }
+
+    let mut history = std::collections::VecDeque::with_capacity(DIFF_CONTEXT);
+    let mut step = 0u64;
+    let (first_a, first_b) = loop {
+        let (x, y) = (a.next_step()?, b.next_step()?);
+        match (x, y) {
+            (None, None) => {
+                out.push_str(&format!("traces are identical ({} steps)\n", step));
+                return Ok(out);
+            }
+            (Some(x), Some(y)) if x == y => {
+                if history.len() == DIFF_CONTEXT {
+                    history.pop_front();
+                }
+                history.push_back(x);
+                step += 1;
+            }
+            (x, y) => break (x, y),
+        }
+    };
+
+    let what = match (first_a, first_b) {
+        (Some(x), Some(y)) if x.ip != y.ip || a.header.op(x.ip) != b.header.op(y.ip) => {
+            "instruction"
+        }
+        (Some(x), Some(y)) if x.pointer != y.pointer => "pointer",
+        (Some(_), Some(_)) => "cell write",
+        (None, _) => "a ends first",
+        (_, None) => "b ends first",
+    };
+    out.push_str(&format!("traces diverge at step {} ({})\n", step, what));
+
+    let first = step - history.len() as u64;
+    for (i, record) in history.iter().enumerate() {
+        out.push_str(&format!(
+            "  {:>10}  {}\n",
+            first + i as u64,
+            describe_step(&a.header, record)
+        ));
+    }
+
+    for (name, trace, record) in [("a", &mut a, first_a), ("b", &mut b, first_b)] {
+        out.push_str(&format!("{}:\n", name));
+        let mut record = record;
+        for n in 0..DIFF_CONTEXT as u64 {
+            match record {
+                Some(ref r) => out.push_str(&format!(
+                    "{} {:>10}  {}\n",
+                    if n == 0 { ">" } else { " " },
+                    step + n,
+                    describe_step(&trace.header, r)
+                )),
+                None => {
+                    out.push_str(&format!("  {:>10}  (end of trace)\n", step + n));
+                    break;
+                }
+            }
+            record = trace.next_step()?;
+        }
+    }
+    Ok(out)
+}
+
+fn describe_step(header: &Header, step: &Step) -> String {
+    format!(
+        "{:<16} cell[{}] = {}",
+        header.describe(step.ip),
+        step.pointer,
+        step.value
+    )
+}
@@ -0,0 +1,134 @@
+//! Parsers for option values with units, shared by every subcommand so
+//! `--max-program-bytes 64KiB` and `--timeout 2m30s` read the same way
+//! everywhere.
+
+use std::convert::TryFrom;
+use std::ops::Range;
+use std::time::Duration;
+
+const SIZE_UNITS: &[(&str, u64)] = &[
+    ("b", 1),
+    ("k", 1000),
+    ("kb", 1000),
+    ("kib", 1 << 10),
+    ("m", 1_000_000),
+    ("mb", 1_000_000),
+    ("mib", 1 << 20),
+    ("g", 1_000_000_000),
+    ("gb", 1_000_000_000),
+    ("gib", 1 << 30),
+];
+
+const COUNT_UNITS: &[(&str, u64)] = &[("k", 1000), ("m", 1_000_000), ("g", 1_000_000_000)];
+
+const DURATION_UNITS: &[(&str, f64)] = &[
+    ("ms", 0.001),
+    ("s", 1.0),
+    ("m", 60.0),
+    ("h", 3600.0),
+    ("d", 86400.0),
+];
+
+fn split_number(s: &str) -> (&str, &str) {
+    let end = s
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(s.len());
+    s.split_at(end)
+}
+
+fn scaled(s: &str, what: &str, units: &[(&str, u64)], names: &str) -> Result<u64, String> {
+    let (number, unit) = split_number(s.trim());
+    let scale = if unit.is_empty() {
+        1
+    } else {
+        let unit = unit.trim().to_ascii_lowercase();
+        match units.iter().find(|&&(name, _)| name == unit) {
+            Some(&(_, scale)) => scale,
+            None => {
+                return Err(format!(
+                    "invalid {} '{}': unknown unit '{}' (expected {})",
+                    what, s, unit, names
+                ))
+            }
+        }
+    };
+
+    let value: f64 = number
+        .parse()
+        .map_err(|_| format!("invalid {} '{}': expected a number", what, s))?;
+    let value = value * scale as f64;
+    if value.fract() != 0.0 || value > u64::MAX as f64 {
+        return Err(format!("invalid {} '{}': not a whole number", what, s));
+    }
+    Ok(value as u64)
+}
+
+/// A byte count such as `4096`, `64KiB` or `1.5GB`.
+pub fn parse_size(s: &str) -> Result<usize, String> {
+    scaled(s, "size", SIZE_UNITS, "B, KB, KiB, MB, MiB, GB or GiB").map(|n| n as usize)
+}
+
+/// A plain count such as `500`, `10k` or `2M`.
+pub fn parse_count<T: TryFrom<u64>>(s: &str) -> Result<T, String> {
+    let n = scaled(s, "count", COUNT_UNITS, "k, M or G")?;
+    T::try_from(n).map_err(|_| format!("invalid count '{}': too large", s))
+}
+
+/// A duration such as `30`, `1.5s`, `250ms` or `2m30s`. A bare number is
+/// in seconds.
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    let text = s.trim();
+    if text.is_empty() {
+        return Err("invalid duration '': expected a number".into());
+    }
+
+    let mut rest = text;
+    let mut seconds = 0.0;
+    while !rest.is_empty() {
+        let (number, after) = split_number(rest);
+        let unit_len = after
+            .find(|c: char| c.is_ascii_digit() || c == '.')
+            .unwrap_or(after.len());
+        let (unit, after) = after.split_at(unit_len);
+
+        let value: f64 = number
+            .parse()
+            .map_err(|_| format!("invalid duration '{}': expected a number", s))?;
+        let scale = match unit.trim().to_ascii_lowercase().as_str() {
+            "" if after.is_empty() && rest == text => 1.0,
+            unit => match DURATION_UNITS.iter().find(|&&(name, _)| name == unit) {
+                Some(&(_, scale)) => scale,
+                None => {
+                    return Err(format!(
+                        "invalid duration '{}': unknown unit '{}' (expected ms, s, m, h or d)",
+                        s, unit
+                    ))
+                }
+            },
+        };
+        seconds += value * scale;
+        rest = after;
+    }
+    Ok(Duration::from_secs_f64(seconds))
+}
+
+/// A cell or range of cells: `5`, `10..20` or `10..=20`.
+pub fn parse_cells(s: &str) -> Result<Range<usize>, String> {
+    let number = |n: &str| {
+        n.trim()
+            .parse::<usize>()
+            .map_err(|_| format!("invalid cell '{}': expected N, A..B or A..=B", s))
+    };
+    let range = if let Some((a, b)) = s.split_once("..=") {
+        number(a)?..number(b)? + 1
+    } else if let Some((a, b)) = s.split_once("..") {
+        number(a)?..number(b)?
+    } else {
+        let n = number(s)?;
+        n..n + 1
+    };
+    if range.is_empty() {
+        return Err(format!("invalid cell '{}': empty range", s));
+    }
+    Ok(range)
+}
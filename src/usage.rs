@@ -0,0 +1,114 @@
+//! Local counts of the subcommands and flags a user runs, for auditing
+//! their own workflows or attaching to bug reports. Off unless
+//! `BF_USAGE_FILE` names a file to keep them in. Only the names of
+//! commands and long flags are recorded, never their values, programs or
+//! input, and nothing leaves that file.
+
+use clap::parser::ValueSource;
+use clap::{ArgMatches, Command};
+use serde::{Deserialize, Serialize};
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+
+pub const USAGE_VAR: &str = "BF_USAGE_FILE";
+
+/// The stats file, if the user has opted in.
+pub fn file() -> Option<PathBuf> {
+    std::env::var_os(USAGE_VAR)
+        .filter(|path| !path.is_empty())
+        .map(PathBuf::from)
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Usage {
+    /// Invocations of each command, and of each flag as `COMMAND --FLAG`.
+    pub counts: BTreeMap<String, u64>,
+}
+
+impl Usage {
+    pub fn load(path: &Path) -> io::Result<Self> {
+        match File::open(path) {
+            Ok(file) => Ok(serde_json::from_reader(BufReader::new(file))?),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let file = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(file, self)?;
+        Ok(())
+    }
+
+    /// Counts the command and flags in one invocation of `cmd`.
+    pub fn add(&mut self, cmd: &Command, matches: &ArgMatches) {
+        let (name, cmd, matches) = invoked(cmd, matches);
+        if name.starts_with("usage") {
+            return;
+        }
+        *self.counts.entry(name.clone()).or_default() += 1;
+        for arg in cmd.get_arguments() {
+            let flag = match arg.get_long() {
+                Some(flag) => flag,
+                None => continue,
+            };
+            if matches.value_source(arg.get_id().as_str()) == Some(ValueSource::CommandLine) {
+                *self
+                    .counts
+                    .entry(format!("{} --{}", name, flag))
+                    .or_default() += 1;
+            }
+        }
+    }
+}
+
+/// The innermost subcommand run, such as `trace info`, with its definition
+/// and matches. Plain `bf FILE` counts as `run`.
+fn invoked<'a>(cmd: &'a Command, matches: &'a ArgMatches) -> (String, &'a Command, &'a ArgMatches) {
+    let mut names = Vec::new();
+    let (mut cmd, mut matches) = (cmd, matches);
+    while let Some((name, sub)) = matches.subcommand() {
+        names.push(name);
+        cmd = cmd.find_subcommand(name).unwrap();
+        matches = sub;
+    }
+    if names.is_empty() {
+        names.push("run");
+    }
+    (names.join(" "), cmd, matches)
+}
+
+/// Adds this invocation to the stats file, if there is one. Failing to
+/// record never stops a run.
+pub fn record(cmd: &Command, matches: &ArgMatches) {
+    if let Some(path) = file() {
+        let mut usage = Usage::load(&path).unwrap_or_default();
+        usage.add(cmd, matches);
+        let _ = usage.save(&path);
+    }
+}
+
+pub fn clear(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+impl fmt::Display for Usage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.counts.is_empty() {
+            return writeln!(f, "Nothing recorded yet");
+        }
+        let mut counts: Vec<_> = self.counts.iter().collect();
+        counts.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+        for (name, count) in counts {
+            writeln!(f, "{:>8}  {}", count, name)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,59 @@
+use std::convert::TryFrom;
+use std::ops::Range;
+
+use bf::program::OpIndex;
+use bf::{Interpreter, OpCode};
+
+/// Prints a line from `Interpreter::execute_with` callbacks whenever a
+/// watched cell changes.
+#[derive(Debug)]
+pub struct Watcher {
+    cells: Vec<Range<usize>>,
+    steps: u64,
+    // Last value seen in each watched cell, by cell.
+    seen: Vec<(usize, u32)>,
+}
+
+impl Watcher {
+    pub fn new(cells: &[Range<usize>], interp: &Interpreter) -> Self {
+        let mut seen: Vec<(usize, u32)> = cells
+            .iter()
+            .flat_map(|r| r.clone())
+            .filter_map(|i| interp.tape().cell(i).map(|v| (i, v)))
+            .collect();
+        seen.sort_unstable();
+        seen.dedup_by_key(|&mut (i, _)| i);
+        Self {
+            cells: cells.to_vec(),
+            steps: 0,
+            seen,
+        }
+    }
+
+    pub fn record(&mut self, interp: &Interpreter, at: OpIndex) {
+        self.steps += 1;
+        match interp.program()[at] {
+            OpCode::Increment | OpCode::Decrement | OpCode::Input => {}
+            _ => return,
+        }
+
+        let value = interp.tape().full_value();
+        let cell = match usize::try_from(interp.tape().position()) {
+            Ok(cell) if self.cells.iter().any(|r| r.contains(&cell)) => cell,
+            _ => return,
+        };
+        if let Ok(i) = self.seen.binary_search_by_key(&cell, |&(i, _)| i) {
+            let was = std::mem::replace(&mut self.seen[i].1, value);
+            if was != value {
+                eprintln!(
+                    "step {} at {}: cell[{}] = {} (was {})",
+                    self.steps,
+                    interp.program().positions()[at.index()],
+                    cell,
+                    interp.tape().number(value),
+                    interp.tape().number(was)
+                );
+            }
+        }
+    }
+}
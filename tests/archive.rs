@@ -0,0 +1,61 @@
+use std::fs;
+use std::process::Command;
+
+fn run_member(name: &str, contents: &[u8], member: &str) -> std::process::Output {
+    let path = std::env::temp_dir().join(format!("bf-archive-{}-{}", name, std::process::id()));
+    fs::write(&path, contents).unwrap();
+    let run = Command::new(env!("CARGO_BIN_EXE_bf"))
+        .args(["run", "--member", member])
+        .arg(&path)
+        .output()
+        .unwrap();
+    fs::remove_file(&path).unwrap();
+    run
+}
+
+#[test]
+fn member_needs_an_archive() {
+    let run = run_member("plain", b"+.", "a.b");
+    assert!(!run.status.success());
+    assert!(String::from_utf8_lossy(&run.stderr).contains("--member needs a zip or tar archive"));
+}
+
+/// A tar archive of `files`, in the ustar format.
+#[cfg(feature = "archives")]
+fn tar(files: &[(&str, &[u8])]) -> Vec<u8> {
+    let mut archive = Vec::new();
+    for (name, contents) in files {
+        let mut header = [0u8; 512];
+        header[..name.len()].copy_from_slice(name.as_bytes());
+        header[100..107].copy_from_slice(b"0000644");
+        header[124..135].copy_from_slice(format!("{:011o}", contents.len()).as_bytes());
+        header[136..147].copy_from_slice(b"00000000000");
+        header[148..156].copy_from_slice(b"        ");
+        header[156] = b'0';
+        header[257..263].copy_from_slice(b"ustar\0");
+        header[263..265].copy_from_slice(b"00");
+        let sum: u32 = header.iter().map(|&b| u32::from(b)).sum();
+        header[148..155].copy_from_slice(format!("{:06o}\0", sum).as_bytes());
+        archive.extend_from_slice(&header);
+        archive.extend_from_slice(contents);
+        archive.resize(archive.len() + (512 - contents.len() % 512) % 512, 0);
+    }
+    archive.resize(archive.len() + 1024, 0);
+    archive
+}
+
+#[cfg(feature = "archives")]
+#[test]
+fn member_runs_from_a_tar_archive() {
+    let archive = tar(&[
+        ("a.b", b"++++++++[>++++++++<-]>+."),
+        ("notes.txt", b"not a program"),
+    ]);
+    let run = run_member("tar", &archive, "a.b");
+    assert!(run.status.success());
+    assert_eq!(run.stdout, b"A");
+
+    let missing = run_member("tar-missing", &archive, "b.b");
+    assert!(!missing.status.success());
+    assert!(String::from_utf8_lossy(&missing.stderr).contains("its programs are a.b\n"));
+}
@@ -0,0 +1,66 @@
+mod common;
+
+use std::fs;
+
+use common::TempDir;
+
+#[test]
+fn compare_fails_on_regressions_from_a_saved_baseline() {
+    let dir = TempDir::new("bench");
+    dir.file("loop.b", "++[-]");
+    let out = dir.bf(&["bench", "--save", "base.json", "loop.b"]);
+    assert!(out.status.success());
+    let saved: serde_json::Value =
+        serde_json::from_slice(&fs::read(dir.join("base.json")).unwrap()).unwrap();
+    assert_eq!(saved["version"], 1);
+    assert_eq!(saved["benchmarks"][0]["name"], "loop.b");
+    assert_eq!(saved["benchmarks"][0]["runs"], 1);
+
+    // Baselines far faster and far slower than any real run.
+    let baseline = |seconds: f64| {
+        let mut baseline = saved.clone();
+        baseline["benchmarks"][0]["seconds"] = seconds.into();
+        dir.file("baseline.json", baseline.to_string());
+    };
+    let compare = |extra: &[&str]| {
+        let mut args = vec!["bench", "--baseline", "baseline.json", "--compare"];
+        args.extend_from_slice(extra);
+        args.push("loop.b");
+        dir.bf(&args)
+    };
+
+    baseline(1e-12);
+    let out = compare(&[]);
+    assert_eq!(out.status.code(), Some(1));
+    let table = String::from_utf8_lossy(&out.stdout);
+    assert!(
+        table.lines().nth(1).unwrap().ends_with("%  REGRESSED"),
+        "{}",
+        table
+    );
+    assert_eq!(
+        String::from_utf8_lossy(&out.stderr),
+        "1 benchmark(s) regressed by more than 10%\n"
+    );
+    assert!(compare(&["--warn-only"]).status.success());
+
+    baseline(1e3);
+    let out = compare(&[]);
+    assert!(out.status.success());
+    assert!(!String::from_utf8_lossy(&out.stdout).contains("REGRESSED"));
+}
+
+#[test]
+fn outliers_are_dropped_before_summarizing() {
+    use bf::samples::{quantile, without_outliers};
+
+    let mut samples = vec![1.3, 1.0, 9.0, 1.1, 1.2, 0.01];
+    let kept = without_outliers(&mut samples);
+    assert_eq!(kept, [1.0, 1.1, 1.2, 1.3]);
+    assert_eq!(samples, [0.01, 1.0, 1.1, 1.2, 1.3, 9.0]);
+    assert!((quantile(&kept, 0.5) - 1.15).abs() < 1e-9);
+
+    // Identical samples have no spread to fence off.
+    assert_eq!(without_outliers(&mut [2.0; 3]), [2.0; 3]);
+    assert_eq!(without_outliers(&mut [5.0]), [5.0]);
+}
@@ -0,0 +1,43 @@
+mod common;
+
+use std::fs;
+
+use common::{arg, bf, TempDir};
+
+#[test]
+fn canonical_form_is_stable_and_checkable() {
+    let dir = TempDir::new("canon");
+    let file = dir.file(
+        "prog.b",
+        "set up ++++ ++++ [>++++ ++++<-]\n>+. print A\n+-[[-]>[+]<]",
+    );
+    let file = arg(&file);
+
+    let printed = bf(&["canon", file]);
+    let unformatted = bf(&["canon", "--check", file]);
+    bf(&["canon", "--write", file]);
+    let written = fs::read_to_string(file).unwrap();
+    let formatted = bf(&["canon", "--check", file]);
+
+    let expected = "++++++++[>++++++++<-]>+.[[-]>[-]<]\n";
+    assert_eq!(String::from_utf8(printed.stdout).unwrap(), expected);
+    assert_eq!(unformatted.status.code(), Some(1));
+    assert_eq!(written, expected);
+    assert!(formatted.status.success());
+}
+
+#[test]
+fn merge_driver_merges_canonical_forms() {
+    let dir = TempDir::new("canon-merge");
+    let lines = |first: &str, last: &str| format!("{}\n[\n  [>+<-]>\n]\n>{}\n", first, last);
+    let base = dir.file("base", lines("+++.", "+."));
+    // Same program as `base` apart from the first line, laid out differently.
+    let ours = dir.file("ours", "++++ . [ [>+<-] > ] > + .");
+    let theirs = dir.file("theirs", lines("+++.", "++."));
+
+    let merged = bf(&["canon", "--merge", arg(&base), arg(&ours), arg(&theirs)]);
+    let result = fs::read_to_string(&ours).unwrap();
+
+    assert!(merged.status.success(), "{:?}", merged);
+    assert_eq!(result, lines("++++.", "++."));
+}
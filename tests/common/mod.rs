@@ -0,0 +1,69 @@
+//! Helpers shared by the integration tests, each of which uses only some.
+#![allow(dead_code)]
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// A directory of its own for one test, removed with everything in it when
+/// dropped, even if the test fails.
+#[derive(Debug)]
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    pub fn new(name: &str) -> Self {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        let path = std::env::temp_dir().join(format!(
+            "bf-{}-{}-{}",
+            name,
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::create_dir_all(&path).unwrap();
+        Self(path)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+
+    pub fn join(&self, name: &str) -> PathBuf {
+        self.0.join(name)
+    }
+
+    /// Writes `contents` to `name` in the directory, returning its path.
+    pub fn file(&self, name: &str, contents: impl AsRef<[u8]>) -> PathBuf {
+        let path = self.join(name);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).unwrap();
+        }
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    /// Runs `bf` with `args` from inside the directory.
+    pub fn bf(&self, args: &[&str]) -> Output {
+        command().current_dir(&self.0).args(args).output().unwrap()
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// The `bf` binary under test, to add arguments to.
+pub fn command() -> Command {
+    Command::new(env!("CARGO_BIN_EXE_bf"))
+}
+
+pub fn bf(args: &[&str]) -> Output {
+    command().args(args).output().unwrap()
+}
+
+/// `path` as an argument.
+pub fn arg(path: &Path) -> &str {
+    path.to_str().unwrap()
+}
@@ -0,0 +1,26 @@
+mod common;
+
+use common::bf;
+
+#[test]
+fn every_shell_gets_completions() {
+    for shell in ["bash", "elvish", "fish", "powershell", "zsh"].iter() {
+        let out = bf(&["completions", shell]);
+        assert!(out.status.success(), "{}", shell);
+        let script = String::from_utf8(out.stdout).unwrap();
+        assert!(script.contains("loop-cap"), "{}:\n{}", shell, script);
+        assert!(script.contains("completions"), "{}:\n{}", shell, script);
+    }
+    assert_eq!(bf(&["completions", "tcsh"]).status.code(), Some(2));
+}
+
+#[test]
+fn man_page_documents_the_flags() {
+    let out = bf(&["man"]);
+    assert!(out.status.success());
+    let page = String::from_utf8(out.stdout).unwrap();
+    assert!(page.starts_with(".ie"), "{}", page);
+    assert!(page.contains(".TH bf 1"), "{}", page);
+    assert!(page.contains("\\-\\-loop\\-cap"), "{}", page);
+    assert!(page.contains("Stop when any one loop runs more than N iterations in a row"));
+}
@@ -0,0 +1,35 @@
+use std::fs;
+use std::process::Command;
+
+fn cost(source: &str, args: &[&str]) -> String {
+    let path =
+        std::env::temp_dir().join(format!("bf-cost-{}-{}.bf", std::process::id(), args.len()));
+    fs::write(&path, source).unwrap();
+    let out = Command::new(env!("CARGO_BIN_EXE_bf"))
+        .arg("run")
+        .args(args)
+        .arg(&path)
+        .output()
+        .unwrap();
+    fs::remove_file(&path).unwrap();
+    assert!(out.status.success());
+    String::from_utf8(out.stderr).unwrap()
+}
+
+#[test]
+fn cost_counts_units_per_instruction_and_loop() {
+    let report = cost("++[>+<-].", &["--cost", "--format", "json"]);
+    assert!(report.contains(r#""total":14"#), "{}", report);
+    assert!(
+        report.contains(r#""loops":[{"offset":2,"line":1,"col":3,"cost":10}]"#),
+        "{}",
+        report
+    );
+
+    let report = cost("++[>+<-].", &["--cost=op=2,io=100"]);
+    assert!(
+        report.contains("cost: 126 units (move=2,arith=2,io=100,loop=2)"),
+        "{}",
+        report
+    );
+}
@@ -0,0 +1,52 @@
+#![cfg(unix)]
+
+use std::fs;
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+use std::thread;
+use std::time::Duration;
+
+fn client(socket: &str, program: &std::path::Path, input: &[u8]) -> Output {
+    let mut client = Command::new(env!("CARGO_BIN_EXE_bf"))
+        .args(["client", "--socket", socket])
+        .arg(program)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    client.stdin.take().unwrap().write_all(input).unwrap();
+    client.wait_with_output().unwrap()
+}
+
+#[test]
+fn client_runs_programs_on_the_daemon() {
+    let base = std::env::temp_dir().join(format!("bf-daemon-{}", std::process::id()));
+    let (socket, program) = (base.with_extension("sock"), base.with_extension("b"));
+    let socket = socket.to_str().unwrap();
+    fs::write(&program, ",[.,]").unwrap();
+    let mut daemon = Command::new(env!("CARGO_BIN_EXE_bf"))
+        .args(["daemon", "--socket", socket, "--timeout", "1s"])
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    for _ in 0..100 {
+        if fs::metadata(socket).is_ok() {
+            break;
+        }
+        thread::sleep(Duration::from_millis(20));
+    }
+
+    let echoed = client(socket, &program, b"hello");
+    fs::write(&program, "+[]").unwrap();
+    let stuck = client(socket, &program, b"");
+    daemon.kill().unwrap();
+    daemon.wait().unwrap();
+    fs::remove_file(&program).unwrap();
+    let _ = fs::remove_file(socket);
+
+    assert!(echoed.status.success());
+    assert_eq!(echoed.stdout, b"hello");
+    assert!(!stuck.status.success());
+    assert!(String::from_utf8_lossy(&stuck.stderr).contains("Timed out"));
+}
@@ -0,0 +1,62 @@
+use std::fs;
+use std::process::{Command, Output};
+
+fn run(name: &str, source: &str, args: &[&str]) -> Output {
+    let path =
+        std::env::temp_dir().join(format!("bf-directives-{}-{}.bf", std::process::id(), name));
+    fs::write(&path, source).unwrap();
+    let out = Command::new(env!("CARGO_BIN_EXE_bf"))
+        .args(args)
+        .arg(&path)
+        .output()
+        .unwrap();
+    fs::remove_file(&path).unwrap();
+    out
+}
+
+const PROGRAM: &str =
+    "++++++++[>++++++++<-]>+\n#assert cell == 65\n#assert ptr == 1\n.\n#assert cell 0 != 0\n";
+
+#[test]
+fn assertions_are_only_checked_when_enabled() {
+    let out = run("off", PROGRAM, &[]);
+    assert!(out.status.success());
+    assert_eq!(out.stdout, b"A");
+
+    let out = run("on", PROGRAM, &["--directives"]);
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(!out.status.success());
+    assert_eq!(out.stdout, b"A");
+    assert!(
+        stderr.contains("Assertion failed on line 5: cell 0 != 0 (cell 0 is 0)"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn malformed_assertions_are_rejected() {
+    let out = run("bad", "#assert cell 0 = 1\n", &["--directives"]);
+    assert!(!out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("malformed #assert"));
+}
+
+#[test]
+fn writes_to_read_only_cells_trap() {
+    let out = run("readonly", "#readonly 2 3\n>>>+<<\n+\n", &["--directives"]);
+    assert!(!out.status.success());
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(
+        stderr.contains("Write to read-only cell 3 at 2:4"),
+        "{}",
+        stderr
+    );
+
+    let out = run("readonly-cli", ">+>+", &["--read-only", "2..4"]);
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(
+        stderr.contains("Write to read-only cell 2 at 1:4"),
+        "{}",
+        stderr
+    );
+}
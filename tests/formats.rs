@@ -0,0 +1,186 @@
+//! Byte-level checks of the artifact formats, so files written on one
+//! machine read the same on any other.
+
+mod common;
+
+use std::fs;
+
+use common::{arg, bf, TempDir};
+
+fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash = 0xcbf2_9ce4_8422_2325u64;
+    for &b in bytes {
+        hash ^= u64::from(b);
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    hash
+}
+
+#[test]
+fn trace_layout_is_little_endian() {
+    let dir = TempDir::new("formats");
+    let program = dir.file("layout.bf", "+\n.");
+    let trace = dir.join("layout.bftr");
+    let out = bf(&["--trace", arg(&trace), arg(&program)]);
+    assert!(out.status.success());
+
+    let mut expected = Vec::new();
+    expected.extend_from_slice(b"BFTR");
+    expected.extend_from_slice(&3u16.to_le_bytes());
+    expected.extend_from_slice(&0u16.to_le_bytes());
+    expected.extend_from_slice(&fnv1a(b"+.").to_le_bytes());
+    expected.extend_from_slice(&65535u64.to_le_bytes());
+    expected.extend_from_slice(&4096u32.to_le_bytes());
+    expected.extend_from_slice(&0u32.to_le_bytes());
+    expected.extend_from_slice(&2u32.to_le_bytes());
+    expected.extend_from_slice(b"+.");
+    for (offset, line, col) in [(0u32, 1u32, 1u32), (2, 2, 1)] {
+        expected.extend_from_slice(&offset.to_le_bytes());
+        expected.extend_from_slice(&line.to_le_bytes());
+        expected.extend_from_slice(&col.to_le_bytes());
+    }
+    let body = expected.len() as u64;
+    for ip in 0u32..2 {
+        expected.push(0);
+        expected.extend_from_slice(&ip.to_le_bytes());
+        expected.extend_from_slice(&0u32.to_le_bytes());
+        expected.push(1);
+    }
+    expected.push(2);
+    expected.extend_from_slice(&2u64.to_le_bytes());
+    // One block: where it starts, pointers 0..=0, ips 0..=1, no output
+    // before it.
+    let index = expected.len() as u64;
+    expected.extend_from_slice(b"BFIX");
+    expected.extend_from_slice(&1u32.to_le_bytes());
+    expected.extend_from_slice(&body.to_le_bytes());
+    for n in [0u32, 0, 0, 1] {
+        expected.extend_from_slice(&n.to_le_bytes());
+    }
+    expected.extend_from_slice(&0u64.to_le_bytes());
+    expected.extend_from_slice(&index.to_le_bytes());
+    expected.extend_from_slice(b"BFIX");
+
+    assert_eq!(fs::read(&trace).unwrap(), expected);
+}
+
+#[test]
+fn traces_round_trip() {
+    let dir = TempDir::new("formats-round");
+    // Long enough to write checkpoints.
+    let program = dir.file("round.bf", "->-[<-[-]>-]");
+    let trace = dir.join("round.bftr");
+    assert!(bf(&["--trace", arg(&trace), arg(&program)])
+        .status
+        .success());
+
+    let info = bf(&["trace", "info", arg(&trace)]);
+    let info = String::from_utf8_lossy(&info.stdout);
+    assert!(info.contains("complete:            yes"), "{}", info);
+    assert!(info.contains("checksums:           ok ("), "{}", info);
+
+    let diff = bf(&["trace", "diff", arg(&trace), arg(&trace)]);
+    assert!(String::from_utf8_lossy(&diff.stdout).contains("traces are identical"));
+}
+
+#[test]
+fn snapshot_digest_is_stable() {
+    let dir = TempDir::new("formats-digest");
+    let program = dir.file("digest.bf", "+>++");
+    let out = bf(&["--print-digest", arg(&program)]);
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.ends_with(": e43a8c43b36f140f\n"), "{}", stderr);
+}
+
+#[test]
+fn trace_headers_with_impossible_lengths_are_rejected() {
+    let dir = TempDir::new("formats-header");
+    let header = |tape_len: u64, options_len: u32| {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(b"BFTR");
+        bytes.extend_from_slice(&3u16.to_le_bytes());
+        bytes.extend_from_slice(&0u16.to_le_bytes());
+        bytes.extend_from_slice(&0u64.to_le_bytes());
+        bytes.extend_from_slice(&tape_len.to_le_bytes());
+        bytes.extend_from_slice(&4096u32.to_le_bytes());
+        bytes.extend_from_slice(&options_len.to_le_bytes());
+        bytes.extend_from_slice(&0u32.to_le_bytes());
+        bytes
+    };
+    let huge_tape = dir.file("huge-tape.bftr", header(1 << 46, 0));
+    let huge_string = dir.file("huge-string.bftr", header(4, u32::MAX));
+
+    for (trace, error) in [
+        (huge_tape, "tape length in header is too large"),
+        (huge_string, "unexpected end of file"),
+    ] {
+        let out = bf(&["trace", "info", arg(&trace)]);
+        let stderr = String::from_utf8_lossy(&out.stderr);
+        assert_eq!(out.status.code(), Some(1), "{}", stderr);
+        assert!(stderr.contains(error), "{}", stderr);
+    }
+}
+
+#[test]
+fn traces_and_snapshots_record_the_same_settings() {
+    let dir = TempDir::new("formats-settings");
+    let program = dir.file("settings.bf", "+.");
+    let (trace, dump) = (dir.join("settings.bftr"), dir.join("settings.bfs"));
+    let out = bf(&[
+        "--overflow",
+        "saturate",
+        "--signed",
+        "--trace",
+        arg(&trace),
+        "--dump-tape",
+        arg(&dump),
+        arg(&program),
+    ]);
+    assert!(out.status.success());
+
+    let settings = "--overflow saturate --signed";
+    let info = bf(&["trace", "info", arg(&trace)]);
+    let info = String::from_utf8_lossy(&info.stdout);
+    assert!(
+        info.contains(&format!("options:             {}\n", settings)),
+        "{}",
+        info
+    );
+    let snapshot = fs::read(&dump).unwrap();
+    let mut field = (settings.len() as u32).to_le_bytes().to_vec();
+    field.extend_from_slice(settings.as_bytes());
+    assert!(snapshot.windows(field.len()).any(|w| w == &field[..]));
+}
+
+#[test]
+fn compression_this_build_lacks_is_refused_up_front() {
+    let dir = TempDir::new("formats-compress");
+    let program = dir.file("compress.bf", "+");
+    let dump = dir.join("compress.bfs");
+    let out = bf(&["--dump-tape", arg(&dump), "--dump-compress", arg(&program)]);
+    if cfg!(feature = "zstd") {
+        assert!(out.status.success());
+        assert!(fs::read(&dump)
+            .unwrap()
+            .starts_with(&[0x28, 0xb5, 0x2f, 0xfd]));
+    } else {
+        assert_eq!(out.status.code(), Some(2));
+        let stderr = String::from_utf8_lossy(&out.stderr);
+        assert!(
+            stderr.contains("zstd compression requires the zstd feature"),
+            "{}",
+            stderr
+        );
+        assert!(!dump.exists());
+    }
+
+    let trace = dir.join("compress.bftr");
+    let out = bf(&[
+        "--trace",
+        arg(&trace),
+        "--trace-compress=gzip",
+        arg(&program),
+    ]);
+    assert_eq!(out.status.success(), cfg!(feature = "gzip"));
+    assert_eq!(trace.exists(), cfg!(feature = "gzip"));
+}
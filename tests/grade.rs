@@ -0,0 +1,96 @@
+mod common;
+
+use std::process::Output;
+
+use common::TempDir;
+
+fn grade(dir: &TempDir, args: &[&str]) -> Output {
+    common::command()
+        .arg("grade")
+        .arg("--spec")
+        .arg(dir.join("spec.toml"))
+        .arg(dir.join("submissions"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+const SPEC: &str = r#"
+cost = "op=1,io=10"
+
+[limits]
+timeout = "200ms"
+max_output = "16"
+
+[[test]]
+name = "echo"
+input = "hi"
+output = "hi"
+
+[[test]]
+name = "empty"
+output = ""
+"#;
+
+#[test]
+fn reports_every_submission() {
+    let dir = TempDir::new("grade");
+    dir.file("spec.toml", SPEC);
+    dir.file("submissions/a-good.b", ",[.,]");
+    dir.file("submissions/b-wrong.b", ",+.");
+    dir.file("submissions/c-loops.b", "+[]");
+    dir.file("submissions/d-broken.b", "[");
+    dir.file("submissions/notes.txt", "not a program");
+
+    let out = grade(&dir, &[]);
+    let json = grade(&dir, &["--format", "json"]);
+    let report = String::from_utf8(out.stdout).unwrap();
+
+    assert!(!out.status.success());
+    assert!(!report.contains("notes.txt"), "{}", report);
+    // Three reads and two writes at 10 units, three loop jumps at 1.
+    assert!(
+        report.contains("a-good.b: 2/2 passed, cost 64"),
+        "{}",
+        report
+    );
+    assert!(report.contains("  ok    echo (53 units)"), "{}", report);
+    assert!(report.contains("b-wrong.b: 0/2 passed"), "{}", report);
+    assert!(report.contains("expected: \"hi\""), "{}", report);
+    assert!(report.contains("actual:   \"i\""), "{}", report);
+    assert!(report.contains("c-loops.b: 0/2 passed"), "{}", report);
+    assert!(report.contains("FAIL  empty: Timed out"), "{}", report);
+    assert!(report.contains("d-broken.b: 0/2 passed: "), "{}", report);
+
+    let json = String::from_utf8(json.stdout).unwrap();
+    assert!(json.contains("\"differs_at\":0"), "{}", json);
+}
+
+#[test]
+fn passing_submissions_exit_zero() {
+    let dir = TempDir::new("grade-pass");
+    dir.file("spec.toml", SPEC);
+    dir.file("submissions/echo.bf", ",[.,]");
+
+    let out = grade(&dir, &[]);
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stdout)
+    );
+}
+
+#[test]
+fn unknown_spec_keys_are_rejected() {
+    let dir = TempDir::new("grade-spec");
+    dir.file("spec.toml", "[limits]\nshell = true\n");
+    dir.file("submissions/echo.bf", ",[.,]");
+
+    let out = grade(&dir, &[]);
+    assert!(!out.status.success());
+    assert!(
+        String::from_utf8_lossy(&out.stderr).contains("shell"),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+}
@@ -0,0 +1,167 @@
+mod common;
+
+use std::io::Write;
+use std::path::Path;
+use std::process::Stdio;
+
+use common::TempDir;
+
+fn output(path: &Path, args: &[&str]) -> Vec<u8> {
+    let out = common::command()
+        .arg("run")
+        .args(args)
+        .arg(path)
+        .output()
+        .unwrap();
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    out.stdout
+}
+
+#[test]
+fn random_input_is_reproducible() {
+    let dir = TempDir::new("input");
+    let path = dir.file("random.bf", ",.".repeat(20));
+    let random = ["--input", "random", "--input-limit", "16"];
+    let first = output(&path, &random);
+    let again = output(&path, &random);
+    let other = output(&path, &[&random[..], &["--seed", "1"]].concat());
+
+    assert_eq!(first, again);
+    assert_ne!(first, other);
+    assert_eq!(first.len(), 20);
+    // Reads past the limit see end of file, which leaves zero in the cell.
+    assert_eq!(first[16..], [0; 4]);
+}
+
+#[test]
+fn sha256_pins_the_program() {
+    let dir = TempDir::new("input");
+    let path = dir.file("pinned.bf", "++++++++[>++++++++<-]>+.");
+    let pinned = "281c3b779560b206f6446b6e383b693b5e116967a3cb9f9d3a92a94d8b7fee56";
+    let run = output(&path, &["--sha256", pinned]);
+    let wrong = common::command()
+        .args(["run", "--sha256", &pinned.replace('2', "3")])
+        .arg(&path)
+        .output()
+        .unwrap();
+
+    assert_eq!(run, b"A");
+    assert!(!wrong.status.success());
+    assert!(String::from_utf8_lossy(&wrong.stderr).contains("but --sha256 expects"));
+}
+
+#[test]
+fn eof_conventions_are_selectable() {
+    let dir = TempDir::new("input");
+    let path = dir.file("eof.bf", "+,.");
+    let zero = output(&path, &[]);
+    let minus_one = output(&path, &["--eof", "minus-one"]);
+    let wide = output(&path, &["--eof", "minus-one", "--cell-size", "16"]);
+    let unchanged = output(&path, &["--eof=unchanged"]);
+
+    assert_eq!(zero, [0]);
+    assert_eq!(minus_one, [255]);
+    assert_eq!(wide, [255]);
+    assert_eq!(unchanged, [1]);
+}
+
+#[test]
+fn input_can_come_from_a_file() {
+    let dir = TempDir::new("input");
+    let path = dir.file("from-file.bf", ",.,.");
+    let input = dir.file("from-file.in", "abc");
+    let out = common::command()
+        .arg("run")
+        .arg("--input")
+        .arg(&input)
+        .args([&path, &path])
+        .output()
+        .unwrap();
+    let missing = common::command()
+        .args(["run", "--input", "/nonexistent/input"])
+        .arg(&path)
+        .output()
+        .unwrap();
+
+    // The second run reads on from where the first stopped.
+    assert_eq!(out.stdout, b"abc\0");
+    assert!(!missing.status.success());
+    assert!(String::from_utf8_lossy(&missing.stderr).contains("Failed to open /nonexistent/input"));
+}
+
+#[test]
+fn input_can_be_given_inline() {
+    let dir = TempDir::new("input");
+    let path = dir.file("inline.bf", ",.,.");
+    let out = output(&path, &["--input-str", "hi"]);
+
+    assert_eq!(out, b"hi");
+}
+
+#[test]
+fn input_can_follow_a_bang() {
+    let dir = TempDir::new("input");
+    let path = dir.file("bang.bf", ",[.,]!hi");
+    let bang = output(&path, &["--bang-input"]);
+    let plain = output(&path, &["--input-str", "yo"]);
+
+    assert_eq!(bang, b"hi");
+    // Without the flag, what follows the `!` is a comment.
+    assert_eq!(plain, b"yo");
+}
+
+#[test]
+fn bang_input_is_all_the_input() {
+    let dir = TempDir::new("input");
+    let path = dir.file("bang.bf", ",[.,]!hi");
+    let mut child = common::command()
+        .args(["run", "--bang-input"])
+        .arg(&path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(b"yo").unwrap();
+    let out = child.wait_with_output().unwrap();
+    let both = common::command()
+        .args(["run", "--bang-input", "--input-str", "yo"])
+        .arg(&path)
+        .output()
+        .unwrap();
+
+    assert_eq!(out.stdout, b"hi");
+    assert!(!both.status.success());
+    assert!(String::from_utf8_lossy(&both.stderr).contains("cannot be used with"));
+}
+
+#[test]
+fn prompts_only_at_a_terminal() {
+    let dir = TempDir::new("input");
+    let path = dir.file("prompt.bf", ",.");
+    let mut child = common::command()
+        .args(["run", "--prompt"])
+        .arg(&path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(b"x").unwrap();
+    let out = child.wait_with_output().unwrap();
+
+    assert_eq!(out.stdout, b"x");
+    assert!(out.stderr.is_empty());
+}
+
+#[test]
+fn echoed_input_comes_before_the_output_it_led_to() {
+    let dir = TempDir::new("input");
+    let path = dir.file("echo.bf", ",[+.,]");
+    let echoed = output(&path, &["--echo-input", "--input-str", "ab"]);
+
+    assert_eq!(echoed, b"abbc");
+}
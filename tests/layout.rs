@@ -0,0 +1,33 @@
+mod common;
+
+use common::TempDir;
+
+#[test]
+fn layout_reports_the_cells_a_program_uses() {
+    let dir = TempDir::new("layout");
+    // Moves a value from cell 5 to cell 0, then steps past both.
+    dir.file("move.b", "+>>>>>++[<<<<<+>>>>>-]>");
+
+    let out = dir.bf(&["--layout", "move.b"]);
+    assert!(out.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&out.stderr),
+        "move.b:\n  \
+         read: 0, 5\n  \
+         written: 0, 5\n  \
+         largest gap: 1..=4 (4 cells)\n  \
+         suggested tape size: 6 cells\n"
+    );
+
+    let out = dir.bf(&["--layout", "--format", "json", "move.b"]);
+    assert!(out.status.success());
+    let layout: serde_json::Value = serde_json::from_slice(&out.stderr).unwrap();
+    let cells = serde_json::json!([{"start": 0, "end": 0}, {"start": 5, "end": 5}]);
+    assert_eq!(layout["read"], cells);
+    assert_eq!(layout["written"], cells);
+    assert_eq!(
+        layout["largest_gap"],
+        serde_json::json!({"start": 1, "end": 4})
+    );
+    assert_eq!(layout["suggested_tape_size"], 6);
+}
@@ -0,0 +1,747 @@
+use std::io::{self, Read, Write};
+
+use bf::flush::FlushPolicy;
+use bf::ir::{Ir, Op, OptLevel};
+use bf::opt::Balance;
+use bf::render::{Layout, Renderer, Target, Visualizer};
+use bf::state::StateFormat;
+use bf::tape::{Bounds, CellSize, Eof, Overflow};
+use bf::{parse, BfArray, Interpreter, InterpreterBuilder, OpIndex, RunError, Status, Tape};
+
+fn interpreter(source: &str) -> Interpreter {
+    Interpreter::new(parse(source.bytes(), &Default::default()).unwrap())
+}
+
+#[test]
+fn step_runs_one_instruction_at_a_time() {
+    let mut interp = interpreter("+>++<[-]");
+    let (mut input, mut output) = (io::empty(), io::sink());
+
+    let mut seen = Vec::new();
+    loop {
+        let at = interp.pc();
+        match interp.step(&mut input, &mut output) {
+            Status::Running => seen.push((at.index(), interp.tape().pointer())),
+            Status::Halted => break,
+            status => panic!("unexpected {:?}", status),
+        }
+    }
+    assert_eq!(
+        seen,
+        [
+            (0, 0),
+            (1, 1),
+            (2, 1),
+            (3, 1),
+            (4, 0),
+            (5, 0),
+            (6, 0),
+            (7, 0)
+        ]
+    );
+    assert_eq!(interp.tape().cells()[..2], [0, 2]);
+    assert!(matches!(
+        interp.step(&mut input, &mut output),
+        Status::Halted
+    ));
+}
+
+/// Refuses the first read, as a non-blocking pipe with no data would.
+struct NotYet(bool);
+
+impl Read for NotYet {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if !self.0 {
+            self.0 = true;
+            return Err(io::ErrorKind::WouldBlock.into());
+        }
+        buf[0] = b'x';
+        Ok(1)
+    }
+}
+
+#[test]
+fn blocked_input_is_retried() {
+    let mut interp = interpreter(",");
+    let (mut input, mut output) = (NotYet(false), io::sink());
+
+    assert!(matches!(
+        interp.step(&mut input, &mut output),
+        Status::Blocked
+    ));
+    assert_eq!(interp.pc(), OpIndex::new(0));
+    assert!(matches!(
+        interp.step(&mut input, &mut output),
+        Status::Running
+    ));
+    assert_eq!(interp.tape().value(), b'x');
+}
+
+#[test]
+fn errors_are_reported_by_step() {
+    let mut interp = interpreter("<");
+    match interp.step(&mut io::empty(), &mut io::sink()) {
+        Status::Error(e) => assert_eq!(e, "Pointer access violation"),
+        status => panic!("unexpected {:?}", status),
+    }
+}
+
+#[test]
+fn input_can_be_scripted() {
+    let mut interp = interpreter(",[.,]");
+    let mut output = Vec::new();
+    interp
+        .execute_all(&mut &b"scripted"[..], &mut output)
+        .unwrap();
+    assert_eq!(output, b"scripted");
+}
+
+#[test]
+fn brackets_are_linked_at_parse_time() {
+    let program = parse("[[-]>[]]".bytes(), &Default::default()).unwrap();
+    let partner = |at| program.partner(OpIndex::new(at)).index();
+    assert_eq!(
+        [partner(0), partner(1), partner(3), partner(5)],
+        [7, 3, 1, 6]
+    );
+    assert_eq!(partner(7), 0);
+}
+
+#[test]
+fn runs_are_folded_when_lowered() {
+    let program = parse("+++>>[-<]<,".bytes(), &Default::default()).unwrap();
+    let ir = Ir::lower(&program);
+    assert_eq!(
+        ir.ops(),
+        [
+            Op::Increment(3),
+            Op::MoveForward(2),
+            Op::JmpStart(5),
+            Op::Decrement(1),
+            Op::MoveBack(1),
+            Op::JmpEnd(2),
+            Op::MoveBack(1),
+            Op::Input,
+        ]
+    );
+    assert_eq!(ir.origin(2), OpIndex::new(5));
+    assert_eq!(
+        Ir::lower(&parse("+".repeat(257).bytes(), &Default::default()).unwrap()).ops(),
+        [Op::Increment(1)]
+    );
+}
+
+#[test]
+fn clear_loops_become_set_zero() {
+    let program = parse("+[-]>[+]>[-+-][->+<.]".bytes(), &Default::default()).unwrap();
+    let ir = Ir::lower(&program);
+    assert_eq!(
+        ir.ops()[..6],
+        [
+            Op::Increment(1),
+            Op::SetZero,
+            Op::MoveForward(1),
+            Op::SetZero,
+            Op::MoveForward(1),
+            Op::SetZero
+        ]
+    );
+    assert_eq!(ir.ops()[6], Op::JmpStart(12));
+
+    let mut interp = interpreter("+++++[-]>-[+]>+++[+-+]>++");
+    *interp.tape_mut() = BfArray::with_len(4);
+    interp
+        .execute_all(&mut io::empty(), &mut io::sink())
+        .unwrap();
+    assert_eq!(interp.tape().cells(), [0, 0, 0, 2]);
+}
+
+#[test]
+fn multiply_and_scan_loops_are_lowered() {
+    let program = parse("+++[->++>+++<<]".bytes(), &Default::default()).unwrap();
+    let ir = Ir::lower(&program);
+    assert_eq!(ir.ops(), [Op::Increment(3), Op::Multiply(0)]);
+    assert_eq!(ir.factors(0), [(1, 2), (2, 3)]);
+
+    let program = parse("[>][<<]".bytes(), &Default::default()).unwrap();
+    assert_eq!(Ir::lower(&program).ops(), [Op::Scan(1), Op::Scan(-2)]);
+}
+
+/// Runs `source` on a tape of `len` cells both lowered and one instruction
+/// at a time, which must agree.
+fn agree(source: &str, len: usize) {
+    let run = |lowered: bool| {
+        let mut interp = interpreter(source);
+        *interp.tape_mut() = BfArray::with_len(len);
+        let result = if lowered {
+            interp.execute_all(&mut io::empty(), &mut io::sink())
+        } else {
+            interp.execute_with(&mut io::empty(), &mut io::sink(), |_, _| {})
+        };
+        (
+            result,
+            interp.pc(),
+            interp.tape().pointer(),
+            interp.tape().cells().to_vec(),
+        )
+    };
+    assert_eq!(run(true), run(false), "{}", source);
+}
+
+#[test]
+fn lowered_runs_match_single_steps() {
+    agree("+++++[->++>-----<<]>>>--[-<+<+>>]", 8);
+    agree("-[-<<+>>]", 8);
+    agree(">>>-[->>+<<]", 4);
+    agree("[->>>>>>>>>>+<]", 2);
+    agree("++[>[-]+++[>+<-]<-]", 4);
+    agree("+>+>+>+>>+<<<<<[>]>[>]<<[<]", 8);
+    agree("+>+>+>+>+>+>+>+[<<]>[>>>]", 8);
+    agree("+>+>+[>]", 3);
+    agree(">>+<<+[<]", 3);
+}
+
+#[test]
+fn fused_programs_match_the_plain_interpreter() {
+    let dbfi = include_str!("../programs/dbfi.b");
+    let cases = [
+        ("++++++++[>++++++++<-]>+.+.>++++++++++.", "", &b"AB\n"[..]),
+        (",[>+>++<<-]>.>.,[->+<]>[>]<.", "\x05\x03", &[5, 10, 3]),
+        (dbfi, "++++++++[>++++++++<-]>+.[-]+++[>+<-]>.!", &[65, 3]),
+        (dbfi, ",[.,]!hi", b"hi"),
+    ];
+    for &(source, input, expected) in &cases {
+        let run = |level: Option<OptLevel>| {
+            let mut interp = interpreter(source);
+            let mut output = Vec::new();
+            let result = match level {
+                Some(level) => {
+                    interp.opt_level = level;
+                    interp.execute_all(&mut input.as_bytes(), &mut output)
+                }
+                None => interp.execute_with(&mut input.as_bytes(), &mut output, |_, _| {}),
+            };
+            (
+                result,
+                output,
+                interp.tape().pointer(),
+                interp.tape().cells()[..64].to_vec(),
+            )
+        };
+        let plain = run(None);
+        assert_eq!((&plain.0, &plain.1[..]), (&Ok(()), expected), "{}", source);
+        for &level in &[OptLevel::O0, OptLevel::O1, OptLevel::O2] {
+            assert_eq!(run(Some(level)), plain, "{:?}: {}", level, source);
+        }
+    }
+}
+
+#[test]
+fn folded_moves_fail_where_single_moves_would() {
+    let mut interp = interpreter("+>>>>>+");
+    *interp.tape_mut() = BfArray::with_len(3);
+    let result = interp.execute_all(&mut io::empty(), &mut io::sink());
+    assert_eq!(
+        result,
+        Err(RunError::Other("Pointer access violation".into()))
+    );
+    assert_eq!(interp.tape().pointer(), 2);
+    assert_eq!(interp.pc(), OpIndex::new(3));
+}
+
+#[test]
+fn tapes_render_as_rows_or_grids() {
+    let mut interp = interpreter("+>++>+++>>>>>-<<<<");
+    *interp.tape_mut() = BfArray::with_len(10);
+    interp
+        .execute_all(&mut io::empty(), &mut io::sink())
+        .unwrap();
+
+    let render = |renderer: &Renderer| {
+        let mut out = Vec::new();
+        renderer
+            .render(
+                interp.tape().cells(),
+                interp.tape().pointer(),
+                Some(7),
+                &mut out,
+            )
+            .unwrap();
+        String::from_utf8(out).unwrap()
+    };
+    assert_eq!(
+        render(&Renderer::default()),
+        "   0   1   2   3   4   5   6\n   1   2   3   0   0   0   0\n               ^\n"
+    );
+    let grid = Renderer {
+        layout: Layout::Grid,
+        cells: 10,
+        columns: 5,
+        width: 3,
+    };
+    assert_eq!(
+        render(&grid),
+        "   0   1   2   3   4\n   1   2   3   0   0\n               ^\n   \
+         5   6   7   8   9\n   0   0 255   0   0\n           *\n"
+    );
+}
+
+#[test]
+fn optimization_levels_agree() {
+    let source = "++++++[->++++++<]>[>+>[-]<<-]>>>[<]<[>+<<+>-]";
+    let program = parse(source.bytes(), &Default::default()).unwrap();
+    assert!(!Ir::lower_at(&program, OptLevel::O1)
+        .ops()
+        .iter()
+        .any(|op| matches!(op, Op::SetZero | Op::Multiply(_) | Op::Scan(_))));
+
+    let run = |level| {
+        let mut interp = interpreter(source);
+        interp.opt_level = level;
+        interp
+            .execute_all(&mut io::empty(), &mut io::sink())
+            .unwrap();
+        (interp.tape().pointer(), interp.tape().cells()[..8].to_vec())
+    };
+    let expected = run(OptLevel::O0);
+    assert_eq!(run(OptLevel::O1), expected);
+    assert_eq!(run(OptLevel::O2), expected);
+}
+
+/// Records what it had been sent at each flush.
+#[derive(Default)]
+struct Flushes {
+    written: Vec<u8>,
+    flushed: Vec<usize>,
+}
+
+impl Write for Flushes {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.written.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if self.flushed.last() != Some(&self.written.len()) {
+            self.flushed.push(self.written.len());
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn output_is_flushed_by_policy() {
+    // "ab\ncd\n", then a read, then "e".
+    let source = "++++++++++[>++++++++++<-]>---.+.>++++++++++.<+.+.>.,<+.";
+    let flushes = |policy| {
+        let mut interp = interpreter(source);
+        interp.flush = policy;
+        let mut out = Flushes::default();
+        interp.execute_all(&mut io::empty(), &mut out).unwrap();
+        assert_eq!(out.written, b"ab\ncd\ne");
+        out.flushed
+    };
+    assert_eq!(flushes(FlushPolicy::Byte), [1, 2, 3, 4, 5, 6, 7]);
+    assert_eq!(flushes(FlushPolicy::Line), [3, 6, 7]);
+    assert_eq!(flushes(FlushPolicy::Every(4)), [4, 6, 7]);
+    assert_eq!(flushes(FlushPolicy::Exit), [6, 7]);
+}
+
+#[test]
+fn balance_sees_through_balanced_nested_loops() {
+    let balance = |body: &str| {
+        let program = parse(body.bytes(), &Default::default()).unwrap();
+        Balance::of(program.ops())
+    };
+
+    let outer = balance(">+[-]>++<<-");
+    assert_eq!(outer.movement, Some(0));
+    assert_eq!(outer.cell_step(), Some(-1));
+    assert_eq!(
+        outer.hints(),
+        [
+            "net pointer delta 0, cell 0 decremented by 1 per iteration → runs exactly initial(cell0) times",
+            "cell +2 changes by +2 per iteration → +2 × initial(cell0) over the whole loop",
+        ]
+    );
+
+    assert_eq!(balance("[>]+").movement, None);
+    assert_eq!(balance("<[-<]>>").cell_step(), None);
+    assert_eq!(
+        balance("++").hints(),
+        ["net pointer delta 0, cell 0 changed by +2 per iteration → ends only if initial(cell0) is a multiple of 2, otherwise never"]
+    );
+}
+
+#[test]
+fn moves_off_the_tape_follow_the_bounds_policy() {
+    let run = |source: &str, bounds, level| {
+        let mut interp = interpreter(source);
+        *interp.tape_mut() = BfArray::with_len(4).with_bounds(bounds);
+        interp.opt_level = level;
+        let result = interp.execute_all(&mut io::empty(), &mut io::sink());
+        (
+            result,
+            interp.tape().pointer(),
+            interp.tape().cells().to_vec(),
+        )
+    };
+
+    for level in [OptLevel::O0, OptLevel::O2] {
+        let (result, pointer, _) = run("<+", Bounds::Error, level);
+        assert_eq!(
+            result,
+            Err(RunError::Other("Pointer access violation".into()))
+        );
+        assert_eq!(pointer, 0);
+        // The multiply loop only adds to cell 3, but steps past it to 4.
+        let (result, pointer, cells) = run(">+[->>><+<<]", Bounds::Error, level);
+        assert!(result.is_err());
+        assert_eq!((pointer, cells), (3, vec![0, 0, 0, 0]));
+
+        assert_eq!(
+            run("<+>>>>>++", Bounds::Wrap, level),
+            (Ok(()), 0, vec![2, 0, 0, 1])
+        );
+        assert_eq!(
+            run(">>>>>>+", Bounds::Grow, level),
+            (Ok(()), 6, vec![0, 0, 0, 0, 0, 0, 1, 0])
+        );
+        assert!(run("<", Bounds::Grow, level).0.is_err());
+
+        let mut interp = interpreter("<<<<<+>>>>>>+");
+        *interp.tape_mut() = BfArray::with_len(2).with_bounds(Bounds::Unbounded);
+        interp.opt_level = level;
+        interp
+            .execute_all(&mut io::empty(), &mut io::sink())
+            .unwrap();
+        let tape = interp.tape();
+        assert_eq!((tape.position(), tape.value()), (1, 1));
+        assert_eq!(tape.cells()[tape.origin() - 5], 1);
+    }
+}
+
+#[test]
+fn sparse_tapes_only_hold_what_is_written() {
+    let far = 1 << 40;
+    let source = format!("++++[{}+{}-]>.", ">".repeat(10_001), "<".repeat(10_001));
+    for level in [OptLevel::O0, OptLevel::O2] {
+        let mut interp = interpreter(&source);
+        *interp.tape_mut() = BfArray::sparse(far);
+        interp.opt_level = level;
+        let mut out = Vec::new();
+        interp.execute_all(&mut io::empty(), &mut out).unwrap();
+        let tape = interp.tape();
+        assert!(!tape.is_dense());
+        assert_eq!((tape.len(), tape.pointer()), (far, 1));
+        assert_eq!(tape.cell(10_001), Some(4));
+        assert_eq!(tape.cell(far), None);
+        assert_eq!(out, [0]);
+    }
+
+    let mut interp = interpreter("<<<<<+>>>>>>+");
+    *interp.tape_mut() = BfArray::sparse(2).with_bounds(Bounds::Unbounded);
+    interp
+        .execute_all(&mut io::empty(), &mut io::sink())
+        .unwrap();
+    let tape = interp.tape();
+    assert_eq!((tape.position(), tape.value()), (1, 1));
+    assert_eq!(tape.cell(tape.origin() - 5), Some(1));
+}
+
+/// A tape that grows to the left as cheaply as to the right.
+#[derive(Debug)]
+struct Deque(std::collections::VecDeque<u32>);
+
+impl Tape for Deque {
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    fn get(&self, i: usize) -> u32 {
+        self.0[i]
+    }
+
+    fn set(&mut self, i: usize, value: u32) {
+        self.0[i] = value;
+    }
+
+    fn grow(&mut self, len: usize) {
+        self.0.resize(len, 0);
+    }
+
+    fn prepend(&mut self, extra: usize) -> usize {
+        for _ in 0..extra {
+            self.0.push_front(0);
+        }
+        extra
+    }
+}
+
+#[test]
+fn tapes_can_bring_their_own_storage() {
+    for level in [OptLevel::O0, OptLevel::O2] {
+        let mut interp = interpreter("+++[>++<-]<+");
+        *interp.tape_mut() =
+            BfArray::with_tape(Deque(vec![0; 2].into())).with_bounds(Bounds::Unbounded);
+        interp.opt_level = level;
+        interp
+            .execute_all(&mut io::empty(), &mut io::sink())
+            .unwrap();
+        let tape = interp.tape();
+        assert!(!tape.is_dense());
+        assert_eq!(tape.position(), -1);
+        let start = tape.origin();
+        assert_eq!(tape.cells_in(start - 1..start + 2), [1, 0, 6].as_slice());
+    }
+}
+
+#[test]
+fn cells_wrap_at_their_size() {
+    // Prints the width of the cells it runs on.
+    let detector = "++++++++[>++++++++<-]>[<++++>-]+<[>-<[>++++<-]>[<++++++++>-]<[>++++++++<-]\
+        +>[>++++++++++[>+++++<-]>+.-.[-]<<[-]<->]<[>>+++++++[>+++++++<-]>.+++++.[-]<<<-]]\
+        >[>++++++++[>+++++++<-]>.[-]<<-]<";
+    for (size, printed) in [
+        (CellSize::U8, "8"),
+        (CellSize::U16, "16"),
+        (CellSize::U32, "32"),
+    ] {
+        let program = parse(detector.bytes(), &Default::default()).unwrap();
+        let mut interp = InterpreterBuilder::new(program).cell_size(size).build();
+        let mut out = Vec::new();
+        interp.execute_all(&mut io::empty(), &mut out).unwrap();
+        assert_eq!(out, printed.as_bytes());
+    }
+
+    let program = parse("-.".bytes(), &Default::default()).unwrap();
+    let mut interp = InterpreterBuilder::new(program)
+        .cell_size(CellSize::U16)
+        .build();
+    let mut out = Vec::new();
+    interp.execute_all(&mut io::empty(), &mut out).unwrap();
+    assert_eq!((out, interp.tape().full_value()), (vec![255], 65535));
+}
+
+#[test]
+fn signed_cells_read_as_negative() {
+    let program = parse("->-".bytes(), &Default::default()).unwrap();
+    let mut interp = InterpreterBuilder::new(program)
+        .cell_size(CellSize::U16)
+        .signed(true)
+        .build();
+    interp
+        .execute_all(&mut io::empty(), &mut Vec::new())
+        .unwrap();
+    let tape = interp.tape();
+    assert_eq!(tape.full_value(), 65535);
+    assert_eq!(tape.number(tape.full_value()), -1);
+    assert_eq!(CellSize::U8.signed(128), -128);
+}
+
+#[test]
+fn frame_rate_visualizing_draws_the_end_of_the_run() {
+    let frames = std::env::temp_dir().join(format!("bf-frames-{}", std::process::id()));
+    let mut interp = interpreter("++++++++[>++++++++[>+<-]<-]>>+");
+    interp.frame_rate = Some(30);
+    interp.visualizer = Visualizer {
+        target: Target::File(frames.clone()),
+        ..Visualizer::default()
+    };
+    interp
+        .execute_all(&mut io::empty(), &mut io::sink())
+        .unwrap();
+    let drawn = std::fs::read_to_string(&frames).unwrap();
+    std::fs::remove_file(&frames).unwrap();
+    assert!(drawn.contains("0   0  65"), "{}", drawn);
+}
+
+#[cfg(feature = "bignum")]
+#[test]
+fn bignum_cells_never_wrap() {
+    use bf::tape::Storage;
+
+    let source = "+".repeat(256) + "[->+<]>.";
+    let program = parse(source.bytes(), &Default::default()).unwrap();
+    let mut interp = InterpreterBuilder::new(program)
+        .storage(Storage::Bignum)
+        .build();
+    let mut out = Vec::new();
+    interp.execute_all(&mut io::empty(), &mut out).unwrap();
+    assert_eq!((out, interp.tape().full_value()), (vec![0], 256));
+
+    let program = parse("-".bytes(), &Default::default()).unwrap();
+    let mut interp = InterpreterBuilder::new(program)
+        .storage(Storage::Bignum)
+        .build();
+    assert!(interp
+        .execute_all(&mut io::empty(), &mut io::sink())
+        .is_err());
+}
+
+#[test]
+fn snapshots_read_back_however_they_were_compressed() {
+    use bf::compress::Compression;
+    use bf::snapshot::Snapshot;
+
+    let mut interp = interpreter("+>++>+++");
+    interp
+        .execute_all(&mut io::empty(), &mut io::sink())
+        .unwrap();
+    let snapshot = interp.snapshot();
+    let mut compressions = vec![Compression::None];
+    if cfg!(feature = "gzip") {
+        compressions.push(Compression::Gzip);
+    }
+    if cfg!(feature = "zstd") {
+        compressions.push(Compression::Zstd);
+    }
+    for compression in compressions {
+        let written = snapshot.write_to(Vec::new(), compression).unwrap();
+        assert_eq!(Compression::detect(&written), compression);
+        let read = Snapshot::read_from(io::Cursor::new(written), "", false).unwrap();
+        assert_eq!(read, snapshot);
+    }
+}
+
+#[test]
+fn snapshots_check_their_settings_and_digest() {
+    use bf::compress::Compression;
+    use bf::snapshot::Snapshot;
+
+    let mut interp =
+        InterpreterBuilder::new(parse(b"+>++".iter().copied(), &Default::default()).unwrap())
+            .cell_size(CellSize::U16)
+            .build();
+    interp
+        .execute_all(&mut io::empty(), &mut io::sink())
+        .unwrap();
+    let snapshot = interp.snapshot();
+    assert_eq!(snapshot.settings, "--cell-size 16");
+    let written = snapshot.write_to(Vec::new(), Compression::None).unwrap();
+    let read = |bytes: &[u8], expected, allow_mismatch| {
+        Snapshot::read_from(io::Cursor::new(bytes.to_vec()), expected, allow_mismatch)
+    };
+
+    let mismatch = read(&written, "", false).unwrap_err();
+    assert_eq!(
+        mismatch.to_string(),
+        "snapshot taken with --cell-size 16, not the default settings"
+    );
+    assert_eq!(read(&written, "", true).unwrap(), snapshot);
+
+    // A settings length past the end of the file is an error, not an
+    // allocation of that size.
+    let mut truncated = written.clone();
+    let settings = truncated
+        .windows(14)
+        .position(|w| w == b"--cell-size 16")
+        .unwrap();
+    truncated[settings - 4..settings].copy_from_slice(&u32::MAX.to_le_bytes());
+    let error = read(&truncated, "--cell-size 16", false).unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+
+    let mut corrupt = written.clone();
+    let last_cell = corrupt.len() - 9;
+    corrupt[last_cell] ^= 1;
+    let error = read(&corrupt, "--cell-size 16", false).unwrap_err();
+    assert!(
+        error.to_string().contains("doesn't match its contents"),
+        "{}",
+        error
+    );
+}
+
+#[test]
+fn export_state_shows_the_open_loops() {
+    let mut interp = interpreter("+[>++<-]");
+    let (mut input, mut output) = (io::empty(), io::sink());
+    for _ in 0..4 {
+        interp.step(&mut input, &mut output);
+    }
+
+    let json: serde_json::Value =
+        serde_json::from_str(&interp.export_state(StateFormat::Json)).unwrap();
+    assert_eq!(
+        json,
+        serde_json::json!({
+            "ip": 4,
+            "pointer": 1,
+            "tape": [1, 1],
+            "loops": [1],
+            "stats": { "instructions": 8, "tape_len": 65535, "nonzero_cells": 2 }
+        })
+    );
+    let dot = interp.export_state(StateFormat::Dot);
+    assert!(dot.contains("tape [shape=record, label=\"<c0> 1|<c1> 1\"];"));
+    assert!(dot.contains("pointer -> tape:c1;"));
+}
+
+#[test]
+fn loop_caps_can_be_set_inside_a_loop() {
+    let mut interp = interpreter("+[]");
+    let (mut input, mut output) = (io::empty(), io::sink());
+    interp.step(&mut input, &mut output);
+    interp.step(&mut input, &mut output);
+    interp.loop_cap = Some(3);
+
+    let stopped = (0..10)
+        .map(|_| interp.step(&mut input, &mut output))
+        .position(|status| matches!(status, Status::Error(_)));
+    assert_eq!(stopped, Some(2));
+}
+
+#[cfg(feature = "debug-invariants")]
+#[test]
+#[should_panic(expected = "Invariant violated: data pointer is outside the tape")]
+fn invariant_checks_catch_a_pointer_off_the_tape() {
+    let mut interp = interpreter(">>>+");
+    let (mut input, mut output) = (io::empty(), io::sink());
+    for _ in 0..3 {
+        interp.step(&mut input, &mut output);
+    }
+    // Cells swapped in from elsewhere can leave the pointer past the end.
+    interp.tape_mut().swap_cells(&mut vec![0; 2]);
+    interp.step(&mut input, &mut output);
+}
+
+#[test]
+fn builder_settings_take_effect() {
+    let run = |source: &str, configure: &dyn Fn(InterpreterBuilder) -> InterpreterBuilder| {
+        let program = parse(source.bytes(), &Default::default()).unwrap();
+        let mut interp = configure(InterpreterBuilder::new(program)).build();
+        let result = interp.execute_all(&mut io::empty(), &mut io::sink());
+        (result, interp)
+    };
+    let error = |e: &str| Err(RunError::Other(e.into()));
+
+    let (result, interp) = run("<", &|b| b.tape_size(4).bounds(Bounds::Wrap));
+    assert_eq!((result, interp.tape().pointer()), (Ok(()), 3));
+    assert_eq!(interp.tape().len(), 4);
+    let (result, _) = run("<", &|b| b);
+    assert_eq!(result, error("Pointer access violation"));
+    let (result, _) = run(">>>", &|b| b.tape_size(3));
+    assert_eq!(result, error("Pointer access violation"));
+
+    let (_, interp) = run("-", &|b| b);
+    assert_eq!(interp.tape().value(), 255);
+    let (_, interp) = run("-", &|b| b.overflow(Overflow::Saturate));
+    assert_eq!(interp.tape().value(), 0);
+    let (result, _) = run("+[+]", &|b| b.overflow(Overflow::Error));
+    assert_eq!(result, error("Cell 0 overflowed past 255"));
+
+    for &(eof, value) in &[(Eof::Zero, 0), (Eof::MinusOne, 255), (Eof::Unchanged, 1)] {
+        let (_, interp) = run("+,", &|b| b.eof(eof));
+        assert_eq!(interp.tape().value(), value, "{:?}", eof);
+    }
+
+    let (result, _) = run("+[]", &|b| b.loop_cap(Some(5)));
+    let stopped = result.unwrap_err().to_string();
+    assert!(
+        stopped.starts_with("Loop at 1:2-1:3 exceeded 5 iterations"),
+        "{}",
+        stopped
+    );
+    let (result, _) = run("+[]", &|b| b.deadline(Some(std::time::Instant::now())));
+    assert_eq!(result, error("Timed out"));
+}
@@ -0,0 +1,234 @@
+mod common;
+
+use std::path::Path;
+use std::process::Stdio;
+
+use common::TempDir;
+
+fn run(path: &Path, args: &[&str]) -> (bool, String) {
+    let out = common::command().args(args).arg(path).output().unwrap();
+    (
+        out.status.success(),
+        String::from_utf8_lossy(&out.stderr).into_owned(),
+    )
+}
+
+#[test]
+fn million_deep_nesting_is_rejected() {
+    let dir = TempDir::new("limits");
+    let depth = 1_000_000;
+    let path = dir.file("deep.bf", "[".repeat(depth) + &"]".repeat(depth));
+    let (ok, stderr) = run(&path, &[]);
+    assert!(!ok);
+    assert!(
+        stderr.contains("Loops nested deeper than 100000 at 1:100001"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn nesting_at_the_limit_runs() {
+    let dir = TempDir::new("limits");
+    let depth = 100_000;
+    let path = dir.file("limit.bf", "[".repeat(depth) + &"]".repeat(depth));
+    let (ok, stderr) = run(&path, &[]);
+    assert!(ok, "{}", stderr);
+}
+
+#[test]
+fn unmatched_brackets_report_their_position() {
+    let dir = TempDir::new("limits");
+    let path = dir.file("open.bf", "+\n[[-]");
+    assert!(run(&path, &[]).1.contains("Unmatched '[' at 2:1"));
+
+    let path = dir.file("close.bf", "+]");
+    assert!(run(&path, &[]).1.contains("Unmatched ']' at 1:2"));
+}
+
+#[test]
+fn configured_limits_are_enforced() {
+    let dir = TempDir::new("limits");
+    let path = dir.file("bytes.bf", "+++ +++");
+    let (ok, stderr) = run(&path, &["--max-program-bytes", "4"]);
+    assert!(!ok);
+    assert!(stderr.contains("longer than 4 bytes"), "{}", stderr);
+
+    let path = dir.file("instructions.bf", "+++ +++");
+    let (ok, stderr) = run(&path, &["--max-instructions", "5"]);
+    assert!(!ok);
+    assert!(stderr.contains("more than 5 instructions"), "{}", stderr);
+
+    let path = dir.file("depth.bf", "+[[[-]]]");
+    let (ok, stderr) = run(&path, &["--max-loop-depth", "2"]);
+    assert!(!ok);
+    assert!(stderr.contains("nested deeper than 2 at 1:4"), "{}", stderr);
+
+    let path = dir.file("within.bf", "+[[-]]");
+    assert!(run(&path, &["--max-instructions", "6", "--max-loop-depth", "2"]).0);
+}
+
+#[test]
+fn limits_accept_units() {
+    let dir = TempDir::new("limits");
+    let path = dir.file("units.bf", "+".repeat(2000));
+    let (ok, stderr) = run(&path, &["--max-program-bytes", "1KiB"]);
+    assert!(!ok);
+    assert!(stderr.contains("longer than 1024 bytes"), "{}", stderr);
+
+    let path = dir.file("units-ok.bf", "+".repeat(2000));
+    assert!(
+        run(
+            &path,
+            &["--max-program-bytes", "2kb", "--max-instructions", "2k"]
+        )
+        .0
+    );
+
+    let path = dir.file("units-bad.bf", "+");
+    let (ok, stderr) = run(&path, &["--max-program-bytes", "12 parsecs"]);
+    assert!(!ok);
+    assert!(stderr.contains("unknown unit 'parsecs'"), "{}", stderr);
+}
+
+#[test]
+fn runaway_programs_are_stopped() {
+    let dir = TempDir::new("limits");
+    let path = dir.file("timeout.bf", "+[]");
+    let (_, stderr) = run(&path, &["--timeout", "200ms"]);
+    assert!(stderr.contains("Timed out"), "{}", stderr);
+
+    let path = dir.file("output.bf", "+[.]");
+    let (_, stderr) = run(&path, &["--max-output", "1k"]);
+    assert!(
+        stderr.contains("output limit of 1000 bytes exceeded"),
+        "{}",
+        stderr
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn timeouts_cut_off_reads_that_never_return() {
+    let dir = TempDir::new("limits");
+    let path = dir.file("blocked.bf", ",");
+    let mut child = common::command()
+        .args(["run", "--timeout", "200ms"])
+        .arg(&path)
+        .stdin(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    // Hold stdin open without writing, so the read would wait forever.
+    let held = child.stdin.take();
+    let out = child.wait_with_output().unwrap();
+    drop(held);
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(!out.status.success());
+    assert!(stderr.contains("Timed out"), "{}", stderr);
+}
+
+#[cfg(unix)]
+#[test]
+fn timeouts_cut_off_reads_from_a_silent_pipe() {
+    let dir = TempDir::new("limits");
+    let path = dir.file("piped.bf", ",");
+    // The child says nothing until it exits, well after the deadline.
+    let (ok, stderr) = run(
+        &path,
+        &["--pipe-to", "sleep 1", "--pipe-back", "--timeout", "200ms"],
+    );
+    assert!(!ok);
+    assert!(stderr.contains("Timed out"), "{}", stderr);
+}
+
+#[cfg(unix)]
+#[test]
+fn ctrl_c_stops_the_program_but_not_the_reports() {
+    let dir = TempDir::new("limits");
+    let path = dir.file("spin.bf", "+[]");
+    let child = common::command()
+        .args(["run", "--stats"])
+        .arg(&path)
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    // Long enough for the handler to be installed.
+    std::thread::sleep(std::time::Duration::from_millis(500));
+    let killed = std::process::Command::new("kill")
+        .args(["-INT", &child.id().to_string()])
+        .status()
+        .unwrap();
+    assert!(killed.success());
+    let out = child.wait_with_output().unwrap();
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert_eq!(out.status.code(), Some(130), "{}", stderr);
+    assert!(stderr.contains("Interrupted"), "{}", stderr);
+    assert!(stderr.contains("instructions"), "{}", stderr);
+}
+
+#[test]
+fn tape_size_sets_the_right_edge() {
+    let dir = TempDir::new("limits");
+    let path = dir.file("tape-fits.bf", ">>>");
+    assert!(run(&path, &["--tape-size", "4"]).0);
+
+    let path = dir.file("tape-overrun.bf", ">>>>");
+    let (ok, stderr) = run(&path, &["--tape-size", "4"]);
+    assert!(!ok);
+    assert!(stderr.contains("Pointer access violation"), "{}", stderr);
+}
+
+#[test]
+fn pointer_wrap_joins_the_ends_of_the_tape() {
+    let dir = TempDir::new("limits");
+    let path = dir.file("wrap-left.bf", "<+<<<-.");
+    let (ok, stderr) = run(&path, &["--tape-size", "4", "--pointer-wrap"]);
+    assert!(ok, "{}", stderr);
+
+    let path = dir.file("wrap-right.bf", ">>>>>>>>");
+    assert!(run(&path, &["--tape-size", "4", "--pointer-wrap"]).0);
+}
+
+#[test]
+fn overflow_can_saturate_or_stop_the_run() {
+    let dir = TempDir::new("limits");
+    let path = dir.file("overflow.bf", "+[+]");
+    let (ok, stderr) = run(&path, &["--overflow", "error"]);
+    assert!(!ok);
+    assert!(stderr.contains("Cell 0 overflowed past 255"), "{}", stderr);
+
+    // Saturating, `-` on an empty cell leaves it empty, ending the loop.
+    let path = dir.file("saturate.bf", ">-[<+>-]<[-]+.");
+    let out = common::command()
+        .args(["--overflow", "saturate"])
+        .arg(&path)
+        .output()
+        .unwrap();
+    assert_eq!(out.stdout, [1]);
+}
+
+#[test]
+fn unreadable_sources_are_reported_not_panicked_on() {
+    let dir = TempDir::new("limits");
+    for command in ["analyze", "profile"].iter() {
+        let (ok, stderr) = run(dir.path(), &[command]);
+        assert!(!ok);
+        assert!(stderr.starts_with("Failed to read "), "{}", stderr);
+        assert!(!stderr.contains("panicked"), "{}", stderr);
+    }
+}
+
+#[test]
+fn loop_cap_allows_exactly_cap_iterations() {
+    let dir = TempDir::new("limits");
+    let path = dir.file("three.bf", "+++[-]");
+    assert!(run(&path, &["--loop-cap", "3"]).0);
+
+    let (ok, stderr) = run(&path, &["--loop-cap", "2"]);
+    assert!(!ok);
+    assert_eq!(
+        stderr,
+        "Loop at 1:4-1:6 exceeded 2 iterations\n  cells from 0: [1] 0 0 0 0 0 0 0 0\n"
+    );
+}
@@ -0,0 +1,59 @@
+mod common;
+
+use common::{arg, bf, TempDir};
+
+#[test]
+fn links_modules_in_order() {
+    let dir = TempDir::new("link");
+    let letter = dir.file("letter.b", "@owns 0 1\n++++++++[>++++++++<-]>+.<\n");
+    let newline = dir.file("newline.b", "@owns 2\n>>++++++++++.<<");
+    let linked = dir.join("linked.b");
+
+    let link = bf(&["link", arg(&letter), arg(&newline), "-o", arg(&linked)]);
+    let run = bf(&["run", arg(&linked)]);
+
+    assert!(
+        link.status.success(),
+        "{}",
+        String::from_utf8_lossy(&link.stderr)
+    );
+    assert_eq!(run.stdout, b"A\n");
+}
+
+#[test]
+fn refuses_modules_claiming_the_same_cells() {
+    let dir = TempDir::new("link-conflict");
+    let a = dir.file("a.b", "@owns 0 9\n");
+    let b = dir.file("b.b", "@owns 5 20\n");
+
+    let out = bf(&["link", arg(&a), arg(&b)]);
+
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(!out.status.success());
+    assert!(
+        stderr.contains("both claim cells 5 through 9"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn check_owns_traps_writes_to_other_modules_cells() {
+    let dir = TempDir::new("link-trample");
+    let a = dir.file("a.b", "@owns 0\n+\n");
+    let b = dir.file("b.b", "@owns 1\n>+<\n+\n");
+    let linked = dir.join("linked.b");
+    let link = bf(&["link", arg(&a), arg(&b), "-o", arg(&linked)]);
+    let checked = bf(&["run", "--check-owns", arg(&linked)]);
+    let unchecked = bf(&["run", arg(&linked)]);
+
+    assert!(link.status.success());
+    let stderr = String::from_utf8_lossy(&checked.stderr);
+    assert!(!checked.status.success());
+    assert!(
+        stderr.contains("Write to cell 0 at 7:1, which the module from line 4 doesn't own"),
+        "{}",
+        stderr
+    );
+    assert!(unchecked.status.success());
+}
@@ -0,0 +1,17 @@
+mod common;
+
+use common::TempDir;
+
+#[test]
+fn explain_opt_names_each_recognized_loop() {
+    let dir = TempDir::new("opt");
+    dir.file("loops.b", "+[-]>+[->+<]>>\n[>]");
+    let out = dir.bf(&["--explain-opt", "loops.b"]);
+    assert!(out.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&out.stderr),
+        "loops.b:1:2: clear loop\n\
+         loops.b:1:7: multiply loop ([+1] += 1*x)\n\
+         loops.b:2:1: scan loop (stride 1)\n"
+    );
+}
@@ -0,0 +1,62 @@
+mod common;
+
+use std::fs;
+
+use common::TempDir;
+
+#[test]
+fn each_encoding_writes_the_output_file_and_tee() {
+    let dir = TempDir::new("output");
+    dir.file("cat.b", ",[.,]");
+    dir.file("input", b"Hello, world!\n\x01\xfe!");
+    let expected: [(&str, &[u8]); 4] = [
+        ("raw", b"Hello, world!\n\x01\xfe!"),
+        (
+            "hex",
+            b"48 65 6c 6c 6f 2c 20 77 6f 72 6c 64 21 0a 01 fe\n21\n",
+        ),
+        (
+            "decimal",
+            b"72\n101\n108\n108\n111\n44\n32\n119\n111\n114\n108\n100\n33\n10\n1\n254\n33\n",
+        ),
+        ("escaped", b"Hello, world!\n\\x01\\xfe!"),
+    ];
+    for &(encoding, expected) in &expected {
+        let out = dir.bf(&[
+            "--input",
+            "input",
+            "--output",
+            "out",
+            "--output-encoding",
+            encoding,
+            "--also-stdout",
+            "--stdout-encoding",
+            encoding,
+            "cat.b",
+        ]);
+        assert!(out.status.success(), "{}", encoding);
+        assert_eq!(fs::read(dir.join("out")).unwrap(), expected, "{}", encoding);
+        assert_eq!(out.stdout, expected, "{}", encoding);
+    }
+
+    // Each side of the tee keeps its own encoding.
+    let out = dir.bf(&[
+        "--input",
+        "input",
+        "--output",
+        "out",
+        "--output-encoding",
+        "hex",
+        "--also-stdout",
+        "cat.b",
+    ]);
+    assert!(out.status.success());
+    assert_eq!(fs::read(dir.join("out")).unwrap(), expected[1].1);
+    assert_eq!(out.stdout, expected[0].1);
+
+    // Without --also-stdout, stdout stays empty.
+    let out = dir.bf(&["--input", "input", "--output", "out", "cat.b"]);
+    assert!(out.status.success());
+    assert_eq!(fs::read(dir.join("out")).unwrap(), expected[0].1);
+    assert!(out.stdout.is_empty());
+}
@@ -0,0 +1,94 @@
+mod common;
+
+use common::TempDir;
+
+// Prints the width of the cells it runs on.
+const DETECTOR: &str = "++++++++[>++++++++<-]>[<++++>-]+<[>-<[>++++<-]>[<++++++++>-]<[>++++++++<-]\
+    +>[>++++++++++[>+++++<-]>+.-.[-]<<[-]<->]<[>>+++++++[>+++++++<-]>.+++++.[-]<<<-]]\
+    >[>++++++++[>+++++++<-]>.[-]<<-]<";
+
+#[test]
+fn packages_carry_their_options_and_tests() {
+    let dir = TempDir::new("package");
+    dir.file("width.b", DETECTOR);
+    dir.file("in", "");
+    dir.file("out", "16");
+
+    let pack = dir.bf(&[
+        "pack",
+        "width.b",
+        "--cell-size",
+        "16",
+        "--test",
+        "in",
+        "out",
+    ]);
+    assert!(pack.status.success(), "{:?}", pack);
+    let packaged = dir.bf(&["run", "width.bfpkg"]);
+    let contradicted = dir.bf(&["run", "--cell-size", "8", "width.bfpkg"]);
+    let overridden = dir.bf(&["run", "--cell-size", "8", "--allow-mismatch", "width.bfpkg"]);
+    let checked = dir.bf(&["run", "--check", "width.bfpkg"]);
+    let failed = dir.bf(&[
+        "run",
+        "--check",
+        "--allow-mismatch",
+        "--cell-size",
+        "32",
+        "width.bfpkg",
+    ]);
+
+    assert_eq!(packaged.stdout, b"16");
+    assert!(!contradicted.status.success());
+    assert!(
+        String::from_utf8_lossy(&contradicted.stderr)
+            .contains("packaged to run with --cell-size 16; pass --allow-mismatch"),
+        "{:?}",
+        contradicted
+    );
+    assert_eq!(overridden.stdout, b"8");
+    assert!(checked.status.success());
+    assert_eq!(checked.stdout, b"width.bfpkg: test 1: passed\n");
+    assert!(!failed.status.success());
+    assert!(
+        String::from_utf8_lossy(&failed.stdout).contains("FAILED, expected \"16\", got \"32\""),
+        "{:?}",
+        failed
+    );
+}
+
+#[test]
+fn bad_options_are_rejected() {
+    let dir = TempDir::new("package-bad");
+    dir.file(
+        "bad.bfpkg",
+        "source = \".\"\n[options]\ncell_size = \"12\"\n",
+    );
+    let out = dir.bf(&["run", "bad.bfpkg"]);
+    assert!(!out.status.success());
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(
+        stderr.contains("cell_size can't be '12' (one of: 8, 16, 32)"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn signedness_is_checked_like_other_options() {
+    let dir = TempDir::new("package-signed");
+    dir.file(
+        "unsigned.bfpkg",
+        "source = \"+.\"\n[options]\nsigned = false\n",
+    );
+    let contradicted = dir.bf(&["run", "--signed", "unsigned.bfpkg"]);
+    let overridden = dir.bf(&["run", "--signed", "--allow-mismatch", "unsigned.bfpkg"]);
+
+    assert!(!contradicted.status.success());
+    assert!(
+        String::from_utf8_lossy(&contradicted.stderr)
+            .contains("packaged to run with no --signed; pass --allow-mismatch"),
+        "{:?}",
+        contradicted
+    );
+    assert_eq!(overridden.stdout, [1]);
+}
@@ -0,0 +1,115 @@
+mod common;
+
+use std::fs;
+use std::io::Read;
+use std::process::{Command, Stdio};
+
+use common::TempDir;
+
+#[test]
+fn closed_output_pipe_exits_quietly() {
+    let path = std::env::temp_dir().join(format!("bf-pipes-{}.bf", std::process::id()));
+    // Prints 'd' forever.
+    fs::write(&path, "++++++++++[>++++++++++<-]>[.]").unwrap();
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_bf"))
+        .arg("run")
+        .arg(&path)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut head = [0; 10];
+    child.stdout.take().unwrap().read_exact(&mut head).unwrap();
+    assert_eq!(&head, b"dddddddddd");
+
+    let out = child.wait_with_output().unwrap();
+    fs::remove_file(&path).unwrap();
+    assert_eq!(out.status.code(), Some(141));
+    assert_eq!(String::from_utf8_lossy(&out.stderr), "");
+}
+
+#[test]
+fn pipe_commands_are_split_like_a_shell_would() {
+    let dir = TempDir::new("pipes");
+    let path = dir.file("echo.bf", ",[.,]");
+    let out = common::command()
+        .args(["run", "--input-str", "a b", "--pipe-to", "tr 'a b' x"])
+        .arg(&path)
+        .output()
+        .unwrap();
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    assert_eq!(out.stdout, b"xxx");
+}
+
+#[cfg(unix)]
+#[test]
+fn on_exit_commands_are_split_like_a_shell_would() {
+    let dir = TempDir::new("pipes-on-exit");
+    dir.file("add.bf", "+++");
+    let out = dir.bf(&["run", "--on-exit", "sh -c 'cat > stats.json'", "add.bf"]);
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    let stats = fs::read_to_string(dir.join("stats.json")).unwrap();
+    assert!(stats.contains("\"total\":3"), "{}", stats);
+}
+
+#[cfg(unix)]
+#[test]
+fn on_exit_commands_run_after_each_program() {
+    let dir = TempDir::new("pipes-on-exit-runs");
+    dir.file("three.bf", "+++");
+    dir.file("two.bf", "++");
+    let hook =
+        "sh -c 'echo \"$0|$1|$BF_HOOK_TEST\" >> hook.log; cat >> hook.log' first 'second word'";
+    let out = common::command()
+        .current_dir(dir.path())
+        .env("BF_HOOK_TEST", "inherited")
+        .args(["run", "--on-exit", hook, "three.bf", "two.bf"])
+        .output()
+        .unwrap();
+    assert!(out.status.success());
+    assert!(
+        out.stderr.is_empty(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+
+    let log = fs::read_to_string(dir.join("hook.log")).unwrap();
+    let lines: Vec<&str> = log.lines().collect();
+    assert_eq!(lines.len(), 4, "{}", log);
+    for (run, program, total) in [(&lines[..2], "three.bf", 3), (&lines[2..], "two.bf", 2)] {
+        assert_eq!(run[0], "first|second word|inherited");
+        let stats: serde_json::Value = serde_json::from_str(run[1]).unwrap();
+        assert_eq!(stats["program"], program);
+        assert_eq!(stats["instructions"]["total"], total);
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn failing_on_exit_commands_are_reported() {
+    let dir = TempDir::new("pipes-on-exit-fails");
+    dir.file("add.bf", "+");
+    let out = dir.bf(&["run", "--on-exit", "sh -c 'exit 3'", "add.bf"]);
+    assert!(out.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&out.stderr),
+        "On-exit command exited with exit status: 3\n"
+    );
+
+    let out = dir.bf(&["run", "--on-exit", "bf-no-such-hook", "add.bf"]);
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(
+        stderr.starts_with("Failed to run on-exit command 'bf-no-such-hook': "),
+        "{}",
+        stderr
+    );
+}
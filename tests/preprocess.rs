@@ -0,0 +1,53 @@
+mod common;
+
+use common::{arg, bf, TempDir};
+
+#[test]
+fn includes_std_snippets_and_files() {
+    let dir = TempDir::new("preprocess");
+    dir.file("five.b", "+++++\n");
+    let main = dir.file(
+        "main.b",
+        "Divide 23 by 5 then print the quotient and the remainder\n\
+         +++++++++++++++++++++++>\n\
+         @include \"five.b\"\n\
+         <\n\
+         @include <std/divmod>\n\
+         >>>\n\
+         @include <std/print-decimal>\n\
+         [-]<\n\
+         @include <std/print-decimal>\n",
+    );
+
+    let out = bf(&["run", "--preprocess", arg(&main)]);
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    assert_eq!(out.stdout, b"43");
+}
+
+#[test]
+fn include_cycles_are_reported() {
+    let dir = TempDir::new("preprocess-cycle");
+    let a = dir.file("a.b", "@include \"b.b\"\n");
+    dir.file("b.b", "+\n@include \"a.b\"\n");
+
+    let out = bf(&["run", "--preprocess", arg(&a)]);
+    assert!(!out.status.success());
+    assert!(
+        String::from_utf8_lossy(&out.stderr).contains("include cycle"),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+}
+
+#[test]
+fn std_list_names_every_snippet() {
+    let list = String::from_utf8(bf(&["std", "list"]).stdout).unwrap();
+    for name in ["divmod", "memcpy", "multiply", "print-decimal"] {
+        assert!(list.contains(&format!("std/{} ", name)), "{}", list);
+        assert!(bf(&["std", "show", name]).status.success());
+    }
+}
@@ -0,0 +1,122 @@
+mod common;
+
+use std::fs;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use common::TempDir;
+
+#[test]
+fn timing_table_counts_every_instruction() {
+    let dir = std::env::temp_dir();
+    let path = dir.join(format!("bf-profile-{}.bf", std::process::id()));
+    let profile = dir.join(format!("bf-profile-{}.json", std::process::id()));
+    fs::write(&path, "++++[>++++<-]").unwrap();
+
+    let out = Command::new(env!("CARGO_BIN_EXE_bf"))
+        .args(["profile", "--timing", "--sample-every", "1", "-o"])
+        .arg(&profile)
+        .arg(&path)
+        .output()
+        .unwrap();
+    fs::remove_file(&path).unwrap();
+    fs::remove_file(&profile).unwrap();
+    assert!(out.status.success());
+
+    let table = String::from_utf8_lossy(&out.stderr);
+    let row = |op: &str| -> Vec<String> {
+        table
+            .lines()
+            .find(|line| line.starts_with(op))
+            .unwrap_or_else(|| panic!("no row for {} in\n{}", op, table))
+            .split_whitespace()
+            .map(str::to_owned)
+            .collect()
+    };
+    // Every instruction after the first is sampled.
+    assert_eq!(row("+")[1..3], ["20", "19"]);
+    assert_eq!(row("]")[1..3], ["4", "4"]);
+    assert!(table.contains("[@1:5"), "{}", table);
+}
+
+#[test]
+fn merged_profiles_add_up_their_counts() {
+    let dir = TempDir::new("profile-merge");
+    dir.file("move.b", ",[>+<-]");
+    dir.file("other.b", "+");
+    let profile = |output: &str, program: &str, input: &[u8]| {
+        let mut child = common::command()
+            .current_dir(dir.path())
+            .args(["profile", "-o", output, program])
+            .stdin(Stdio::piped())
+            .spawn()
+            .unwrap();
+        child.stdin.take().unwrap().write_all(input).unwrap();
+        assert!(child.wait().unwrap().success());
+    };
+    profile("two.json", "move.b", &[2]);
+    profile("three.json", "move.b", &[3]);
+    profile("other.json", "other.b", &[]);
+
+    let out = dir.bf(&[
+        "profile",
+        "merge",
+        "-o",
+        "merged.json",
+        "two.json",
+        "three.json",
+    ]);
+    assert!(out.status.success());
+    let merged: serde_json::Value =
+        serde_json::from_slice(&fs::read(dir.join("merged.json")).unwrap()).unwrap();
+    assert_eq!(merged["runs"], 2);
+    let counts: Vec<(&str, u64)> = merged["counts"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|c| (c["op"].as_str().unwrap(), c["count"].as_u64().unwrap()))
+        .collect();
+    assert_eq!(
+        counts,
+        [
+            (",", 2),
+            ("[", 2),
+            (">", 5),
+            ("+", 5),
+            ("<", 5),
+            ("-", 5),
+            ("]", 5)
+        ]
+    );
+    assert_eq!(merged["loops"][0]["entries"], 2);
+    assert_eq!(
+        merged["loops"][0]["iterations"],
+        serde_json::json!({"2": 1, "3": 1})
+    );
+
+    let out = dir.bf(&[
+        "profile",
+        "merge",
+        "-o",
+        "bad.json",
+        "two.json",
+        "other.json",
+    ]);
+    assert_eq!(out.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&out.stderr).starts_with("profiles are for different programs"));
+}
+
+#[test]
+fn folded_stacks_match_the_loop_nesting() {
+    let dir = TempDir::new("profile-folded");
+    dir.file("nested.b", "++[>+++[>+<-]<-]\n>>[-]");
+    let out = dir.bf(&["profile", "--folded", "nested.folded", "nested.b"]);
+    assert!(out.status.success());
+    assert_eq!(
+        fs::read_to_string(dir.join("nested.folded")).unwrap(),
+        "main 6\n\
+         main;[@1:3 16\n\
+         main;[@1:3;[@1:8 30\n\
+         main;[@2:3 12\n"
+    );
+}
@@ -0,0 +1,113 @@
+mod common;
+
+use std::fs;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use common::{arg, TempDir};
+
+#[test]
+fn provenance_names_the_instruction_behind_a_byte() {
+    let base = std::env::temp_dir().join(format!("bf-provenance-{}", std::process::id()));
+    let (program, record) = (base.with_extension("b"), base.with_extension("json"));
+    fs::write(&program, "++++++++[>++++++++<-]>+.\n+.").unwrap();
+    let run = Command::new(env!("CARGO_BIN_EXE_bf"))
+        .arg("run")
+        .arg("--provenance")
+        .arg(&record)
+        .arg(&program)
+        .output()
+        .unwrap();
+    assert_eq!(run.stdout, b"AB");
+
+    let explain = |byte: &str| {
+        Command::new(env!("CARGO_BIN_EXE_bf"))
+            .args(["provenance", record.to_str().unwrap(), "--byte", byte])
+            .output()
+            .unwrap()
+    };
+    let second = explain("1");
+    let missing = explain("2");
+    fs::remove_file(&program).unwrap();
+    fs::remove_file(&record).unwrap();
+
+    assert_eq!(
+        String::from_utf8(second.stdout).unwrap(),
+        format!(
+            "output byte 1: 'B' written at step 109 by '.' at {}:2:2 (offset 26)\n",
+            program.display()
+        )
+    );
+    assert!(!missing.status.success());
+    assert!(String::from_utf8_lossy(&missing.stderr).contains("2 byte(s)"));
+}
+
+#[test]
+fn provenance_maps_input_to_reads_and_warns_about_leftovers() {
+    let base = std::env::temp_dir().join(format!("bf-input-provenance-{}", std::process::id()));
+    let (program, record) = (base.with_extension("b"), base.with_extension("json"));
+    fs::write(&program, ",>,,").unwrap();
+    let mut run = Command::new(env!("CARGO_BIN_EXE_bf"))
+        .arg("run")
+        .arg("--provenance")
+        .arg(&record)
+        .arg(&program)
+        .stdin(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    run.stdin.take().unwrap().write_all(b"xyz12").unwrap();
+    let run = run.wait_with_output().unwrap();
+    let third = Command::new(env!("CARGO_BIN_EXE_bf"))
+        .args(["provenance", record.to_str().unwrap(), "--input-byte", "2"])
+        .output()
+        .unwrap();
+    fs::remove_file(&program).unwrap();
+    fs::remove_file(&record).unwrap();
+
+    assert!(run.status.success());
+    assert_eq!(
+        String::from_utf8(run.stderr).unwrap(),
+        format!(
+            "warning: {} left 2 byte(s) of input unread\n",
+            program.display()
+        )
+    );
+    assert_eq!(
+        String::from_utf8(third.stdout).unwrap(),
+        format!(
+            "input byte 2: 'z' read at step 3 by ',' at {}:1:4 (offset 3)\n",
+            program.display()
+        )
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn endless_unread_input_is_counted_up_to_a_limit() {
+    let dir = TempDir::new("provenance");
+    let program = dir.file("read.b", ",.");
+    let out = dir.bf(&[
+        "--provenance",
+        "read.json",
+        "--input",
+        "/dev/zero",
+        arg(&program),
+    ]);
+    assert!(out.status.success());
+    assert_eq!(
+        String::from_utf8(out.stderr).unwrap(),
+        format!(
+            "warning: {} left at least 1048576 byte(s) of input unread\n",
+            program.display()
+        )
+    );
+
+    let explain = dir.bf(&["provenance", "read.json", "--input-byte", "1"]);
+    let stderr = String::from_utf8(explain.stderr).unwrap();
+    assert!(
+        stderr.contains("; at least 1048576 more were left unread"),
+        "{}",
+        stderr
+    );
+}
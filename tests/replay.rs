@@ -0,0 +1,55 @@
+use std::fs;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+#[test]
+fn replay_seeks_through_a_trace() {
+    let base = std::env::temp_dir().join(format!("bf-replay-{}", std::process::id()));
+    let (program, trace) = (base.with_extension("b"), base.with_extension("bftr"));
+    fs::write(&program, "++.>+++.<[-]").unwrap();
+    let recorded = Command::new(env!("CARGO_BIN_EXE_bf"))
+        .arg("run")
+        .arg("--trace")
+        .arg(&trace)
+        .arg(&program)
+        .output()
+        .unwrap();
+    assert!(recorded.status.success());
+
+    let mut player = Command::new(env!("CARGO_BIN_EXE_bf"))
+        .arg("replay")
+        .arg("--play")
+        .arg(&trace)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    player
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(b"o\no\nb 2\nc 0\ng 100\nq\n")
+        .unwrap();
+    let out = player.wait_with_output().unwrap();
+    fs::remove_file(&program).unwrap();
+    fs::remove_file(&trace).unwrap();
+    assert!(out.status.success());
+
+    let frames: Vec<&str> = std::str::from_utf8(&out.stdout)
+        .unwrap()
+        .split("> ")
+        .map(|frame| frame.lines().next().unwrap_or(""))
+        .collect();
+    assert_eq!(
+        frames,
+        [
+            "step 0/14, next '+' at 1:1",
+            "step 3/14, next '>' at 1:4",
+            "step 8/14, next '<' at 1:9",
+            "step 6/14, next '+' at 1:7",
+            "step 9/14, next '[' at 1:10",
+            "step 14/14, end of trace",
+            "",
+        ]
+    );
+}
@@ -0,0 +1,196 @@
+mod common;
+
+use std::io::{Read, Write};
+use std::process::Stdio;
+
+use common::{arg, TempDir};
+
+#[test]
+fn a_faulting_program_fails_the_pair() {
+    let dir = TempDir::new("sched");
+    let bad = dir.file("bad.b", "<");
+    let echo = dir.file("echo.b", ",[.,]");
+    let out = common::command()
+        .args(["run", "--pair", arg(&bad), arg(&echo)])
+        .stdin(Stdio::null())
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&out.stderr);
+
+    assert_eq!(out.status.code(), Some(1), "{}", stderr);
+    assert!(stderr.starts_with("a: "), "{}", stderr);
+    assert!(!stderr.contains("Deadlock"), "{}", stderr);
+}
+
+#[test]
+fn waiting_on_stdin_leaves_the_others_running() {
+    let dir = TempDir::new("sched-stdin");
+    dir.file("reader.b", ",.");
+    dir.file("writer.b", "++++++++[>++++++++<-]>+.");
+    let topology = dir.file(
+        "topology",
+        "reader = reader.b\nwriter = writer.b\nstdin -> reader\nreader -> stdout\nwriter -> stdout\n",
+    );
+    let mut child = common::command()
+        .args(["run", "--topology", arg(&topology)])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    // The writer finishes while the reader still waits for its byte.
+    let mut stdout = child.stdout.take().unwrap();
+    let mut first = [0];
+    stdout.read_exact(&mut first).unwrap();
+    child.stdin.take().unwrap().write_all(b"x").unwrap();
+    let mut rest = Vec::new();
+    stdout.read_to_end(&mut rest).unwrap();
+
+    assert_eq!(first, *b"A");
+    assert_eq!(rest, b"x");
+    assert!(child.wait().unwrap().success());
+}
+
+#[test]
+fn stats_show_each_programs_traffic() {
+    let dir = TempDir::new("sched-stats");
+    let writer = dir.file("writer.b", "++++++++[>++++++++<-]>+.");
+    let reader = dir.file("reader.b", ",");
+    let out = common::command()
+        .args(["run", "--stats", "--format", "json", "--pair"])
+        .args([arg(&writer), arg(&reader)])
+        .stdin(Stdio::null())
+        .output()
+        .unwrap();
+    assert!(out.status.success());
+
+    let stats: Vec<serde_json::Value> = String::from_utf8_lossy(&out.stderr)
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(stats.len(), 2);
+    assert_eq!(stats[0]["program"], "a");
+    assert_eq!(stats[0]["steps"], 108);
+    assert_eq!(stats[0]["bytes_sent"], 1);
+    assert_eq!(stats[1]["bytes_received"], 1);
+    assert_eq!(stats[1]["blocked_polls"], 0);
+}
+
+#[test]
+fn limits_apply_to_each_program() {
+    let dir = TempDir::new("sched-limits");
+    let spin = dir.file("spin.b", "+[]");
+    let echo = dir.file("echo.b", ",.");
+    let run = |args: &[&str]| {
+        let out = common::command()
+            .args(["run", "--pair", arg(&spin), arg(&echo)])
+            .args(args)
+            .stdin(Stdio::null())
+            .output()
+            .unwrap();
+        (
+            out.status.code(),
+            String::from_utf8_lossy(&out.stderr).into_owned(),
+        )
+    };
+
+    let (code, stderr) = run(&["--timeout", "200ms"]);
+    assert_eq!(code, Some(1));
+    assert!(stderr.starts_with("Timed out:"), "{}", stderr);
+
+    let (code, stderr) = run(&["--loop-cap", "100"]);
+    assert_eq!(code, Some(1));
+    assert!(
+        stderr.contains("a: Loop at 1:2-1:3 exceeded 100 iterations"),
+        "{}",
+        stderr
+    );
+
+    let (code, stderr) = run(&["-O", "0"]);
+    assert_eq!(code, Some(2));
+    assert!(stderr.contains("cannot be used with"), "{}", stderr);
+}
+
+#[test]
+fn output_limits_apply_to_each_program() {
+    let dir = TempDir::new("sched-output");
+    let chatty = dir.file("chatty.b", "+[.]");
+    let echo = dir.file("echo.b", ",[.,]");
+    let out = common::command()
+        .args([
+            "run",
+            "--max-output",
+            "10",
+            "--pair",
+            arg(&chatty),
+            arg(&echo),
+        ])
+        .stdin(Stdio::null())
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert_eq!(out.status.code(), Some(1));
+    assert!(
+        stderr.contains("output limit of 10 bytes exceeded"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn a_pair_waiting_on_each_other_deadlocks() {
+    let dir = TempDir::new("sched-deadlock");
+    let reader = dir.file("reader.b", ",.");
+    let out = common::command()
+        .args(["run", "--pair", arg(&reader), arg(&reader)])
+        .stdin(Stdio::null())
+        .output()
+        .unwrap();
+
+    assert_eq!(out.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8_lossy(&out.stderr),
+        "Deadlock detected:\n  \
+         a: blocked reading at instruction 0 (',')\n  \
+         b: blocked reading at instruction 0 (',')\n"
+    );
+}
+
+#[test]
+fn a_program_spinning_without_io_livelocks_the_topology() {
+    let dir = TempDir::new("sched-livelock");
+    dir.file("reader.b", ",.");
+    dir.file("spin.b", "+[]");
+    let topology = dir.file(
+        "topology",
+        "reader = reader.b\nspin = spin.b\nspin -> reader\nreader -> stdout\n",
+    );
+    let out = common::command()
+        .args([
+            "run",
+            "--livelock-steps",
+            "1000",
+            "--topology",
+            arg(&topology),
+        ])
+        .stdin(Stdio::null())
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&out.stderr);
+
+    assert_eq!(out.status.code(), Some(1), "{}", stderr);
+    assert!(
+        stderr.starts_with("Livelock detected: no I/O in 1000 instructions:\n"),
+        "{}",
+        stderr
+    );
+    assert!(
+        stderr.contains("\n  reader: blocked reading at instruction 0 (',')"),
+        "{}",
+        stderr
+    );
+    assert!(
+        stderr.contains("\n  spin: running at instruction "),
+        "{}",
+        stderr
+    );
+}
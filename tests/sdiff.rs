@@ -0,0 +1,43 @@
+use std::fs;
+use std::process::{Command, Output};
+
+fn sdiff(name: &str, old: &str, new: &str) -> Output {
+    let base = std::env::temp_dir().join(format!("bf-sdiff-{}-{}", name, std::process::id()));
+    let (a, b) = (base.with_extension("old.b"), base.with_extension("new.b"));
+    fs::write(&a, old).unwrap();
+    fs::write(&b, new).unwrap();
+    let out = Command::new(env!("CARGO_BIN_EXE_bf"))
+        .arg("sdiff")
+        .arg(&a)
+        .arg(&b)
+        .output()
+        .unwrap();
+    fs::remove_file(&a).unwrap();
+    fs::remove_file(&b).unwrap();
+    out
+}
+
+#[test]
+fn formatting_and_folding_are_not_changes() {
+    let out = sdiff(
+        "folding",
+        "++++ ++++ [>++++++++<-] eight [-]\n",
+        "+++++++++-[\n  >>+-<++++++++ <-\n][+]",
+    );
+    assert!(out.status.success());
+    assert!(String::from_utf8(out.stdout).unwrap().ends_with(
+        "are equivalent: their instructions differ only in runs that fold the same way\n"
+    ));
+}
+
+#[test]
+fn semantic_changes_are_listed_with_positions() {
+    let out = sdiff("changes", "+++[>++<-]>.\n", "+++[>+++<-]\n>.,");
+    assert_eq!(out.status.code(), Some(1));
+    let report = String::from_utf8(out.stdout).unwrap();
+    let lines: Vec<&str> = report.lines().collect();
+    assert!(lines[0].contains("old.b 1:6, "), "{}", report);
+    assert_eq!(&lines[1..3], ["- ++", "+ +++"]);
+    assert!(lines[3].ends_with("new.b 2:3"), "{}", report);
+    assert_eq!(&lines[4..], ["+ ,", "2 semantic change(s)"]);
+}
@@ -0,0 +1,26 @@
+use std::process::Command;
+
+#[test]
+fn self_interpreter_runs_the_samples() {
+    let out = Command::new(env!("CARGO_BIN_EXE_bf"))
+        .arg("selftest")
+        .output()
+        .unwrap();
+    let report = String::from_utf8_lossy(&out.stdout);
+    assert!(out.status.success(), "{}", report);
+    assert!(report.contains("dbfi"), "{}", report);
+}
+
+#[test]
+#[ignore = "opt-in: cargo test -- --ignored"]
+fn torture_tests_pass() {
+    let out = Command::new(env!("CARGO_BIN_EXE_bf"))
+        .args(["selftest", "--torture"])
+        .output()
+        .unwrap();
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stdout)
+    );
+}
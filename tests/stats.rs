@@ -0,0 +1,34 @@
+mod common;
+
+use std::io::Write;
+use std::process::Stdio;
+
+use common::{arg, TempDir};
+
+#[test]
+fn json_stats_count_steps_and_io() {
+    let dir = TempDir::new("stats");
+    let program = dir.file("cat.b", ",[.,]");
+    let mut child = common::command()
+        .args(["run", "--stats", "--format", "json", arg(&program)])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(b"ab").unwrap();
+    let out = child.wait_with_output().unwrap();
+    assert!(out.status.success());
+    assert_eq!(out.stdout, b"ab");
+
+    let stats: serde_json::Value = serde_json::from_slice(&out.stderr).unwrap();
+    assert_eq!(stats["program"], arg(&program));
+    assert_eq!(
+        stats["instructions"],
+        serde_json::json!({"total": 8, "moves": 0, "arithmetic": 0, "io": 5, "loops": 3})
+    );
+    assert_eq!(stats["output_bytes"], 2);
+    // Both bytes, then end of input.
+    assert_eq!(stats["input_reads"], 3);
+    assert_eq!(stats["error"], serde_json::Value::Null);
+}
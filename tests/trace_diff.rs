@@ -0,0 +1,44 @@
+use std::fs;
+use std::process::Command;
+
+#[test]
+fn traces_of_other_settings_need_allow_mismatch() {
+    let base = std::env::temp_dir().join(format!("bf-trace-diff-{}", std::process::id()));
+    let program = base.with_extension("b");
+    let (a, b) = (base.with_extension("a.bftr"), base.with_extension("b.bftr"));
+    fs::write(&program, "+,.").unwrap();
+    let bf = || Command::new(env!("CARGO_BIN_EXE_bf"));
+    for (trace, eof) in [(&a, "zero"), (&b, "unchanged")] {
+        let recorded = bf()
+            .args(["--eof", eof, "--trace"])
+            .arg(trace)
+            .arg(&program)
+            .output()
+            .unwrap();
+        assert!(recorded.status.success());
+    }
+
+    let diff = |args: &[&str]| {
+        bf().args(["trace", "diff"])
+            .arg(&a)
+            .arg(&b)
+            .args(args)
+            .output()
+            .unwrap()
+    };
+    let refused = diff(&[]);
+    let allowed = diff(&["--allow-mismatch"]);
+    for path in [&program, &a, &b] {
+        fs::remove_file(path).unwrap();
+    }
+
+    let stderr = String::from_utf8_lossy(&refused.stderr);
+    assert!(!refused.status.success());
+    assert!(
+        stderr.contains("(65535 cells, \"\" vs 65535 cells, \"--eof unchanged\")"),
+        "{}",
+        stderr
+    );
+    let report = String::from_utf8_lossy(&allowed.stdout);
+    assert!(report.contains("traces diverge at step 1"), "{}", report);
+}
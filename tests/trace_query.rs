@@ -0,0 +1,50 @@
+use std::fs;
+use std::process::Command;
+
+#[test]
+fn queries_find_steps_across_index_blocks() {
+    let base = std::env::temp_dir().join(format!("bf-trace-query-{}", std::process::id()));
+    let (program, trace) = (base.with_extension("b"), base.with_extension("bftr"));
+    // Eight countdowns of 767 steps each, over two blocks of 4096 steps.
+    fs::write(&program, "-[.-]".repeat(8)).unwrap();
+    let bf = || Command::new(env!("CARGO_BIN_EXE_bf"));
+    let recorded = bf()
+        .arg("--trace")
+        .arg(&trace)
+        .arg(&program)
+        .output()
+        .unwrap();
+    assert!(recorded.status.success());
+
+    let query = |args: &[&str]| -> Vec<u64> {
+        let out = bf()
+            .args(["trace", "query"])
+            .arg(&trace)
+            .args(args)
+            .output()
+            .unwrap();
+        assert!(
+            out.status.success(),
+            "{}",
+            String::from_utf8_lossy(&out.stderr)
+        );
+        String::from_utf8(out.stdout)
+            .unwrap()
+            .lines()
+            .map(|line| line.parse().unwrap())
+            .collect()
+    };
+    // Output byte 255 * r + k is written at step 767 * r + 2 + 3 * k.
+    let outputs = [query(&["--output", "1337"]), query(&["--output", "1600"])];
+    let dots = query(&["--offset", "2"]);
+    let zeros = query(&["--cell", "0", "--value", "0"]);
+    fs::remove_file(&program).unwrap();
+    fs::remove_file(&trace).unwrap();
+
+    assert_eq!(outputs, [[4023], [4814]]);
+    assert_eq!(dots.len(), 255);
+    assert_eq!((dots[0], dots[254]), (2, 764));
+    // Each countdown's last `-` and its `]`.
+    assert_eq!(zeros.len(), 16);
+    assert_eq!(zeros[15], 8 * 767 - 1);
+}
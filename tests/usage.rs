@@ -0,0 +1,48 @@
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+
+fn bf(usage: Option<&Path>, args: &[&str]) -> Output {
+    let mut cmd = Command::new(env!("CARGO_BIN_EXE_bf"));
+    cmd.args(args).env_remove("BF_USAGE_FILE");
+    if let Some(path) = usage {
+        cmd.env("BF_USAGE_FILE", path);
+    }
+    cmd.output().unwrap()
+}
+
+#[test]
+fn records_commands_and_flags_but_not_values() {
+    let dir = std::env::temp_dir().join(format!("bf-usage-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let program = dir.join("secret-name.b");
+    fs::write(&program, "+.").unwrap();
+    let usage = dir.join("usage.json");
+    let program = program.to_str().unwrap();
+
+    bf(Some(&usage), &["run", "--tape-size", "123", program]);
+    bf(Some(&usage), &[program]);
+    bf(None, &["run", "--stats", program]);
+    let shown = bf(Some(&usage), &["usage", "show"]);
+    let shown = String::from_utf8(shown.stdout).unwrap();
+    let stored = fs::read_to_string(&usage).unwrap();
+    bf(Some(&usage), &["usage", "clear"]);
+    let left = usage.exists();
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert!(shown.contains("       2  run\n"), "{}", shown);
+    assert!(shown.contains("       1  run --tape-size\n"), "{}", shown);
+    assert!(!shown.contains("--stats"), "{}", shown);
+    assert!(!shown.contains("  usage"), "{}", shown);
+    for private in ["123", "secret-name"] {
+        assert!(!stored.contains(private), "{}", stored);
+    }
+    assert!(!left);
+}
+
+#[test]
+fn show_explains_how_to_opt_in() {
+    let out = bf(None, &["usage", "show"]);
+    assert!(!out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("BF_USAGE_FILE"));
+}
@@ -0,0 +1,20 @@
+mod common;
+
+use common::{arg, bf, TempDir};
+
+#[test]
+fn watched_cells_report_each_change() {
+    let dir = TempDir::new("watch");
+    let program = dir.file("watch.b", "+>++>+++<<[-]");
+    let out = bf(&["--watch-cell", "0", "--watch-cell", "2..3", arg(&program)]);
+    assert!(out.status.success());
+    // Cell 1 changes too, but isn't watched.
+    assert_eq!(
+        String::from_utf8_lossy(&out.stderr),
+        "step 1 at 1:1: cell[0] = 1 (was 0)\n\
+         step 6 at 1:6: cell[2] = 1 (was 0)\n\
+         step 7 at 1:7: cell[2] = 2 (was 1)\n\
+         step 8 at 1:8: cell[2] = 3 (was 2)\n\
+         step 12 at 1:12: cell[0] = 0 (was 1)\n"
+    );
+}